@@ -1,7 +1,15 @@
 use std::fmt;
+use std::cmp::Ordering;
+use std::hash::{Hash, Hasher};
 use std::collections::BTreeMap;
 
+use super::lexer::Lexer;
+
+#[cfg(feature = "serde")]
+use serde::{Serialize, Deserialize};
+
 #[derive(Debug,PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Program {
     pub statements: Vec<Statement>
 }
@@ -15,23 +23,59 @@ impl fmt::Display for Program {
 }
 
 #[derive(Debug,PartialEq, Clone, Eq, Hash, Ord, PartialOrd)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum Statement {
+    // the annotation is the name of a type like `let x: Int = 5;`, which is checked only if the config asks.
     LetStatement{identifier: Expression,
+                 annotation: Option<String>,
                  value: Expression},
+    ConstStatement{identifier: Expression,
+                   value: Expression},
+    // rebind a name which has been bound, or replace a value inside an array or a hash like a[0] = 9.
+    Assign{target: Expression,
+           value: Expression},
     Return(Expression),
+    Break(Option<Expression>),
     ExpressionStatement(Expression),
     Block(Vec<Statement>),
+    // evaluate another file in the current scope, so that its names can be used.
+    // with an alias, the names are bound in a hash of the alias instead.
+    Import{path: String,
+           alias: Option<String>},
+    // a statement with the comments around it, which the parser keeps only when it is asked for them.
+    // the leading comments are the lines above the statement, and the trailing comment is on the same line after it.
+    Commented{leading_comments: Vec<String>,
+              statement: Box<Statement>,
+              trailing_comment: Option<String>},
 }
 
 impl fmt::Display for Statement {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
             Statement::LetStatement{
+                               identifier,
+                               annotation,
+                               value
+                                    } => match annotation {
+                                             Some(annotation) => write!(f, "let {}: {} = {};", identifier, annotation, value),
+                                             None => write!(f, "let {} = {};",identifier,  value),
+                                         },
+            Statement::ConstStatement{
                                identifier,
                                value
-                                    } =>write!(f, "let {} = {};",identifier,  value),
+                                    } =>write!(f, "const {} = {};",identifier,  value),
+            Statement::Assign{target, value} => write!(f, "{} = {};", target, value),
             Statement::Return(Expression) =>write!(f, "return {}", Expression),
+            Statement::Break(value) => match value {
+                                                 Some(value) => write!(f, "break {}", value),
+                                                 None => write!(f, "break"),
+                                             },
             Statement::ExpressionStatement(Expression) =>write!(f, "{}", Expression),
+            Statement::Import{path, alias} => match alias {
+                                                 Some(alias) => write!(f, "import \"{}\" as {};", path, alias),
+                                                 None => write!(f, "import \"{}\";", path),
+                                             },
+            Statement::Commented{statement, ..} => write!(f, "{}", statement),
             Statement::Block(Statements) => {
                                              for stmt in Statements.iter()
                                                  {
@@ -44,17 +88,37 @@ impl fmt::Display for Statement {
                 }
             }
 
+impl Statement {
+    // the statement without the comments around it.
+    pub fn without_comments(&self) -> &Statement {
+        match self {
+            Statement::Commented{statement, ..} => statement.without_comments(),
+            _ => self
+        }
+    }
+}
+
 #[derive(Debug,PartialEq, Clone, Eq, Hash, Ord, PartialOrd)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum Expression {
     Identifier(String),
+    // an identifier annotated by resolver::resolve with the number of function scopes
+    // between where it is used and where it is bound.
+    ResolvedIdentifier{name: String,
+                       depth: usize
+                      },
     String(String),
     Integer(i32),
+    Float(Float),
     LParen(String),
     Array(Vec<Expression>),
-    Hashmap(BTreeMap<Box<Expression>, Box<Expression>>),
+    Hashmap(#[cfg_attr(feature = "serde", serde(with = "hashmap_pairs"))] BTreeMap<Box<Expression>, Box<Expression>>),
     Bool(bool),
     IndexExpression{array: Box<Expression>,
                     subscript: Box<Expression>},
+    // a?.[k] or a?.k, which is null without evaluating k when a is null.
+    OptionalIndexExpression{array: Box<Expression>,
+                            subscript: Box<Expression>},
     PrefixExpression{operator: String,
                      right_expression: Box<Expression>
                      },
@@ -66,6 +130,11 @@ pub enum Expression {
                  consequence: Box<Statement>,
                  alternative: Option<Box<Statement>>
                 },
+    WhileExpression{condition: Box<Expression>,
+                    body: Box<Statement>
+                   },
+    // a block which is evaluated into the value of the last statement.
+    DoExpression{body: Box<Statement>},
     FunctionLiteral{parameters: Vec<Expression>,
                     body: Box<Statement>,
                    },
@@ -79,18 +148,27 @@ impl fmt::Display for Expression {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
             Expression::Identifier(value) => write!(f, "{}",&value),
+            Expression::ResolvedIdentifier{name, ..} => write!(f, "{}",&name),
             Expression::String(value) => write!(f, "{}", &value),
             Expression::Integer(value) => write!(f, "{}",value),
+            Expression::Float(value) => write!(f, "{}",value),
             Expression::LParen(value) => write!(f, "{}",value),
             Expression::Bool(value) => write!(f, "{}",value),
-            Expression::PrefixExpression{operator,right_expression} => write!(f, "{}{}",operator, right_expression),
-            Expression::InfixExpression{left_expression,operator,right_expression} => write!(f, "{} {} {}",left_expression, operator, right_expression),
+            // parentheses are written only where the precedence needs them, like (a + b) * c.
+            // operators are left associative, so the right side of the same precedence is also wrapped like a - (b - c).
+            Expression::PrefixExpression{operator,right_expression} => write!(f, "{}{}",operator, right_expression.parenthesized_below(Precedence::PREFIX)),
+            Expression::InfixExpression{left_expression,operator,right_expression} => {
+                let precedence = operator_precedence(operator);
+                write!(f, "{} {} {}", left_expression.parenthesized_below(precedence), operator, right_expression.parenthesized_up_to(precedence))
+            },
             Expression::IfExpression{condition, consequence, alternative} => {
                                                     match alternative {
                                                         Some(alternative) =>write!(f, "if ({}) {{{}}} else {{{}}}",condition, consequence, alternative),
                                                         None => write!(f, "if ({}) {{{}}}",condition, consequence),
                                                     }
                                                     }//write!(f, "if ({}) {{{}}} else {{{}}}",condition, consequence, alternative),
+            Expression::WhileExpression{condition, body} => write!(f, "while ({}) {{{}}}",condition, body),
+            Expression::DoExpression{body} => write!(f, "do {{{}}}", body),
             Expression::FunctionLiteral{parameters, body} => write!(f, "fn ({}) {{{}}}",parameters.iter().map(|expression| -> &str {
                                                                                                                         match expression {
                                                                                                                             Expression::Identifier(identifier) => identifier,
@@ -98,11 +176,12 @@ impl fmt::Display for Expression {
                                                                                                                         }}).collect::<Vec<_>>().join(", ")
                                                                                                                       , body),
             Expression::CallExpression{function, body} => write!(f, "{}({});",
-                                                                function,
+                                                                function.parenthesized_up_to(Precedence::PREFIX),
                                                                 body.iter().map(|expression| format!("{}", &expression)).collect::<Vec<_>>().join(", "),
                                                                 ),
             Expression::Array(value) => write!(f, "[{}]", value.iter().map(|expression| format!("{}", &expression)).collect::<Vec<_>>().join(", ")),
-            Expression::IndexExpression{array, subscript} => write!(f, "{}[{}]",array, subscript),
+            Expression::IndexExpression{array, subscript} => write!(f, "{}[{}]",array.parenthesized_up_to(Precedence::PREFIX), subscript),
+            Expression::OptionalIndexExpression{array, subscript} => write!(f, "{}?.[{}]",array.parenthesized_up_to(Precedence::PREFIX), subscript),
             Expression::Hashmap(tree) => {
                 match tree {
                     tree => write!(f, "{{{}}}", tree.iter().map(|(key, value)| format!("{}: {}", key, value)).collect::<Vec<_>>().join(", ")),
@@ -113,10 +192,107 @@ impl fmt::Display for Expression {
     }
 }
 
+impl Expression {
+    // the precedence of an operator expression, and none for the others which never need parentheses.
+    pub fn precedence(&self) -> Option<Precedence> {
+        match self {
+            Expression::PrefixExpression{..} => Some(Precedence::PREFIX),
+            Expression::InfixExpression{operator, ..} => Some(operator_precedence(operator)),
+            _ => None
+        }
+    }
+
+    // the expression as an operand, which is wrapped if it binds more loosely than the operator.
+    fn parenthesized_below(&self, precedence: Precedence) -> String {
+        match self.precedence() {
+            Some(own) if own < precedence => format!("({})", self),
+            _ => format!("{}", self)
+        }
+    }
+
+    // the same as parenthesized_below, but the same precedence is also wrapped.
+    fn parenthesized_up_to(&self, precedence: Precedence) -> String {
+        match self.precedence() {
+            Some(own) if own <= precedence => format!("({})", self),
+            _ => format!("{}", self)
+        }
+    }
+}
+
+// the precedence is taken from the token of the operator, so that it is the same as the parser.
+pub fn operator_precedence(operator: &str) -> Precedence {
+    Lexer::new(operator).next_token().get_precedence()
+}
+
+// f64 doesn't implement Eq, Ord and Hash, which the AST and objects derive,
+// so a float is compared by its bits in the same order as f64::total_cmp.
+// use the f64 inside to compare values in Monkey, where 0.0 == -0.0.
+#[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Float(pub f64);
+
+impl Float {
+    fn total_order_key(&self) -> i64 {
+        let bits = self.0.to_bits() as i64;
+        bits ^ ((((bits >> 63) as u64) >> 1) as i64)
+    }
+}
+
+impl PartialEq for Float {
+    fn eq(&self, other: &Float) -> bool {
+        self.0.to_bits() == other.0.to_bits()
+    }
+}
+
+impl Eq for Float {}
+
+impl Ord for Float {
+    fn cmp(&self, other: &Float) -> Ordering {
+        self.total_order_key().cmp(&other.total_order_key())
+    }
+}
+
+impl PartialOrd for Float {
+    fn partial_cmp(&self, other: &Float) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Hash for Float {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.0.to_bits().hash(state);
+    }
+}
+
+impl fmt::Display for Float {
+    // Debug keeps the fraction of a whole number like 1000.0.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?}", self.0)
+    }
+}
+
+// keys of JSON must be strings, so the pairs of a hash literal are serialized as a list.
+#[cfg(feature = "serde")]
+mod hashmap_pairs {
+    use std::collections::BTreeMap;
+    use serde::{Serializer, Deserialize, Deserializer};
+    use super::Expression;
+
+    pub fn serialize<S: Serializer>(tree: &BTreeMap<Box<Expression>, Box<Expression>>, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(tree.iter())
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<BTreeMap<Box<Expression>, Box<Expression>>, D::Error> {
+        let pairs: Vec<(Box<Expression>, Box<Expression>)> = Vec::deserialize(deserializer)?;
+        Ok(pairs.into_iter().collect())
+    }
+}
 
-#[derive(Debug, PartialEq, PartialOrd)]
+#[derive(Debug, PartialEq, PartialOrd, Clone, Copy)]
 pub enum Precedence {
     LOWEST,      
+    COALESCE,     // ??
+    PIPE,         // |>
     EQUALS,       // ==
     LESSGREATER,  // > or <
     SUM,          // +
@@ -0,0 +1,122 @@
+use super::ast::{Program, Statement, Expression};
+
+// serialize the AST into JSON for tools written in other languages.
+// every node is an object which has "type" and the fields of the node.
+// spans aren't included because the AST doesn't keep positions of nodes.
+pub fn ast_to_json(program: &Program) -> String {
+    format!("{{\"type\":\"Program\",\"statements\":{}}}", statements_to_json(&program.statements))
+}
+
+fn statements_to_json(statements: &[Statement]) -> String {
+    format!("[{}]", statements.iter().map(statement_to_json).collect::<Vec<_>>().join(","))
+}
+
+fn expressions_to_json(expressions: &[Expression]) -> String {
+    format!("[{}]", expressions.iter().map(expression_to_json).collect::<Vec<_>>().join(","))
+}
+
+fn statement_to_json(statement: &Statement) -> String {
+    match statement {
+        Statement::LetStatement{identifier, annotation, value} => format!("{{\"type\":\"LetStatement\",\"identifier\":{},\"annotation\":{},\"value\":{}}}",
+                                                              expression_to_json(identifier),
+                                                              annotation.as_ref().map(|annotation| string_to_json(annotation)).unwrap_or_else(|| String::from("null")),
+                                                              expression_to_json(value)),
+        Statement::ConstStatement{identifier, value} => format!("{{\"type\":\"ConstStatement\",\"identifier\":{},\"value\":{}}}",
+                                                                expression_to_json(identifier), expression_to_json(value)),
+        Statement::Assign{target, value} => format!("{{\"type\":\"AssignStatement\",\"target\":{},\"value\":{}}}",
+                                                    expression_to_json(target), expression_to_json(value)),
+        Statement::Return(value) => format!("{{\"type\":\"ReturnStatement\",\"value\":{}}}", expression_to_json(value)),
+        Statement::Break(value) => format!("{{\"type\":\"BreakStatement\",\"value\":{}}}",
+                                           value.as_ref().map(expression_to_json).unwrap_or_else(|| String::from("null"))),
+        Statement::ExpressionStatement(expression) => format!("{{\"type\":\"ExpressionStatement\",\"expression\":{}}}", expression_to_json(expression)),
+        Statement::Block(statements) => format!("{{\"type\":\"BlockStatement\",\"statements\":{}}}", statements_to_json(statements)),
+        Statement::Import{path, alias} => format!("{{\"type\":\"ImportStatement\",\"path\":{},\"alias\":{}}}", string_to_json(path),
+                                                  alias.as_ref().map(|alias| string_to_json(alias)).unwrap_or_else(|| String::from("null"))),
+        Statement::Commented{leading_comments, statement, trailing_comment} => format!("{{\"type\":\"CommentedStatement\",\"leading_comments\":[{}],\"statement\":{},\"trailing_comment\":{}}}",
+                                                  leading_comments.iter().map(|comment| string_to_json(comment)).collect::<Vec<_>>().join(","),
+                                                  statement_to_json(statement),
+                                                  trailing_comment.as_ref().map(|comment| string_to_json(comment)).unwrap_or_else(|| String::from("null"))),
+    }
+}
+
+fn expression_to_json(expression: &Expression) -> String {
+    match expression {
+        Expression::Identifier(value) => format!("{{\"type\":\"Identifier\",\"name\":{}}}", string_to_json(value)),
+        Expression::ResolvedIdentifier{name, depth} => format!("{{\"type\":\"ResolvedIdentifier\",\"name\":{},\"depth\":{}}}", string_to_json(name), depth),
+        Expression::String(value) => format!("{{\"type\":\"StringLiteral\",\"value\":{}}}", string_to_json(value)),
+        Expression::Integer(value) => format!("{{\"type\":\"IntegerLiteral\",\"value\":{}}}", value),
+        Expression::Float(value) => format!("{{\"type\":\"FloatLiteral\",\"value\":{}}}", value),
+        Expression::LParen(value) => format!("{{\"type\":\"LParen\",\"value\":{}}}", string_to_json(value)),
+        Expression::Bool(value) => format!("{{\"type\":\"BooleanLiteral\",\"value\":{}}}", value),
+        Expression::Array(values) => format!("{{\"type\":\"ArrayLiteral\",\"elements\":{}}}", expressions_to_json(values)),
+        Expression::Hashmap(tree) => format!("{{\"type\":\"HashLiteral\",\"pairs\":[{}]}}",
+                                             tree.iter().map(|(key, value)| format!("{{\"key\":{},\"value\":{}}}", expression_to_json(key), expression_to_json(value)))
+                                                        .collect::<Vec<_>>().join(",")),
+        Expression::IndexExpression{array, subscript} => format!("{{\"type\":\"IndexExpression\",\"left\":{},\"index\":{}}}",
+                                                                 expression_to_json(array), expression_to_json(subscript)),
+        Expression::OptionalIndexExpression{array, subscript} => format!("{{\"type\":\"OptionalIndexExpression\",\"left\":{},\"index\":{}}}",
+                                                                         expression_to_json(array), expression_to_json(subscript)),
+        Expression::PrefixExpression{operator, right_expression} => format!("{{\"type\":\"PrefixExpression\",\"operator\":{},\"right\":{}}}",
+                                                                            string_to_json(operator), expression_to_json(right_expression)),
+        Expression::InfixExpression{left_expression, operator, right_expression} => format!("{{\"type\":\"InfixExpression\",\"left\":{},\"operator\":{},\"right\":{}}}",
+                                                                                            expression_to_json(left_expression), string_to_json(operator), expression_to_json(right_expression)),
+        Expression::IfExpression{condition, consequence, alternative} => format!("{{\"type\":\"IfExpression\",\"condition\":{},\"consequence\":{},\"alternative\":{}}}",
+                                                                                 expression_to_json(condition), statement_to_json(consequence),
+                                                                                 alternative.as_ref().map(|alternative| statement_to_json(alternative)).unwrap_or_else(|| String::from("null"))),
+        Expression::WhileExpression{condition, body} => format!("{{\"type\":\"WhileExpression\",\"condition\":{},\"body\":{}}}",
+                                                                expression_to_json(condition), statement_to_json(body)),
+        Expression::DoExpression{body} => format!("{{\"type\":\"DoExpression\",\"body\":{}}}", statement_to_json(body)),
+        Expression::FunctionLiteral{parameters, body} => format!("{{\"type\":\"FunctionLiteral\",\"parameters\":{},\"body\":{}}}",
+                                                                 expressions_to_json(parameters), statement_to_json(body)),
+        Expression::CallExpression{function, body} => format!("{{\"type\":\"CallExpression\",\"function\":{},\"arguments\":{}}}",
+                                                              expression_to_json(function), expressions_to_json(body)),
+        Expression::Null => String::from("{\"type\":\"Null\"}"),
+    }
+}
+
+fn string_to_json(value: &str) -> String {
+    let mut escaped = String::from("\"");
+    for ch in value.chars() {
+        match ch {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\t' => escaped.push_str("\\t"),
+            ch if (ch as u32) < 0x20 => escaped.push_str(&format!("\\u{:04x}", ch as u32)),
+            ch => escaped.push(ch),
+        }
+    }
+    escaped.push('"');
+    escaped
+}
+
+#[cfg(test)]// test runs only when execute cargo run
+mod testing {
+    use crate::parser::{Parser};
+    use crate::ast_json::{ast_to_json};
+
+    fn to_json(input: &str) -> String {
+        let mut parser = Parser::new_from_source(input);
+        ast_to_json(&parser.parse_program().unwrap())
+    }
+
+    #[test]
+    fn test_ast_to_json() {
+        let json = to_json("1 + 2");
+        assert!(json.contains("\"type\":\"InfixExpression\""));
+        assert!(json.contains("\"operator\":\"+\""));
+        assert_eq!(json, concat!("{\"type\":\"Program\",\"statements\":[{\"type\":\"ExpressionStatement\",\"expression\":",
+                                 "{\"type\":\"InfixExpression\",\"left\":{\"type\":\"IntegerLiteral\",\"value\":1},",
+                                 "\"operator\":\"+\",\"right\":{\"type\":\"IntegerLiteral\",\"value\":2}}}]}"));
+
+        let json = to_json("let f = fn(x) { if (x) { \"a\\b\" } else { [x, 1.5] } };");
+        assert!(json.contains("\"type\":\"LetStatement\",\"identifier\":{\"type\":\"Identifier\",\"name\":\"f\"}"));
+        assert!(json.contains("\"parameters\":[{\"type\":\"Identifier\",\"name\":\"x\"}]"));
+        assert!(json.contains("\"value\":\"a\\\\b\""));
+        assert!(json.contains("{\"type\":\"FloatLiteral\",\"value\":1.5}"));
+
+        let json = to_json("if (true) { 1 }");
+        assert!(json.contains("\"alternative\":null"));
+    }
+}
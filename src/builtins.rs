@@ -1,15 +1,74 @@
-use super::object::{Object};
+use super::object::{Object, HashKey};
 use super::errors::{Errors};
+use super::evaluator::{is_truthy, apply_function, EvalConfig, Input, Rng};
+use super::ast::Float;
 use std::collections::BTreeMap;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+// builtins which affect or read the outside of the program, which a sandbox disables.
+pub const SIDE_EFFECTS: &[&str] = &["print", "env", "read_line", "now"];
+
+// builtins which call a function given as an argument.
+pub const CALLS_FUNCTIONS: &[&str] = &["repeat"];
 
 pub fn new() -> BTreeMap<String, Object> {
     let mut builtins = BTreeMap::new();
     builtins.insert(String::from("len"), Object::Builtin{func: len});
+    builtins.insert(String::from("byte_len"), Object::Builtin{func: byte_len});
     builtins.insert(String::from("first"), Object::Builtin{func: first});
     builtins.insert(String::from("last"), Object::Builtin{func: last});
     builtins.insert(String::from("rest"), Object::Builtin{func: rest}); 
     builtins.insert(String::from("push"), Object::Builtin{func: push}); 
     builtins.insert(String::from("print"), Object::Builtin{func: print});
+    builtins.insert(String::from("assert"), Object::Builtin{func: assert});
+    builtins.insert(String::from("assert_eq"), Object::Builtin{func: assert_eq});
+    builtins.insert(String::from("between"), Object::Builtin{func: between});
+    builtins.insert(String::from("repeat"), Object::Builtin{func: repeat});
+    builtins.insert(String::from("sort"), Object::Builtin{func: sort});
+    builtins.insert(String::from("reverse"), Object::Builtin{func: reverse});
+    builtins.insert(String::from("slice"), Object::Builtin{func: slice});
+    builtins.insert(String::from("clone"), Object::Builtin{func: clone});
+    builtins.insert(String::from("pairs"), Object::Builtin{func: pairs});
+    builtins.insert(String::from("has"), Object::Builtin{func: has});
+    builtins.insert(String::from("format"), Object::Builtin{func: format});
+    builtins.insert(String::from("env"), Object::Builtin{func: env});
+    builtins.insert(String::from("now"), Object::Builtin{func: now});
+    builtins.insert(String::from("compose"), Object::Builtin{func: compose});
+    builtins.insert(String::from("curry"), Object::Builtin{func: curry});
+    builtins.insert(String::from("arity"), Object::Builtin{func: arity});
+    builtins.insert(String::from("ord"), Object::Builtin{func: ord});
+    builtins.insert(String::from("char"), Object::Builtin{func: char});
+    builtins.insert(String::from("abs"), Object::Builtin{func: abs});
+    builtins.insert(String::from("pow"), Object::Builtin{func: pow});
+    builtins.insert(String::from("sign"), Object::Builtin{func: sign});
+    builtins.insert(String::from("is_array"), Object::Builtin{func: is_array});
+    builtins.insert(String::from("is_hash"), Object::Builtin{func: is_hash});
+    builtins.insert(String::from("is_int"), Object::Builtin{func: is_int});
+    builtins.insert(String::from("is_string"), Object::Builtin{func: is_string});
+    builtins.insert(String::from("is_fn"), Object::Builtin{func: is_fn});
+    builtins.insert(String::from("is_null"), Object::Builtin{func: is_null});
+    builtins
+}
+
+// builtins which the config allows. a builtin which isn't allowed is replaced with the error,
+// so that using it returns the error instead of acting.
+pub fn with_config(config: &EvalConfig) -> BTreeMap<String, Object> {
+    let mut builtins = new();
+    builtins.insert(String::from("read_line"), Object::ReadLine{input: config.input.clone()});
+    let rng = Rng::new(config.seed);
+    builtins.insert(String::from("rand"), Object::Rand{rng: rng.clone(), ranged: false});
+    builtins.insert(String::from("rand_range"), Object::Rand{rng: rng, ranged: true});
+    if !config.allow_env {
+        builtins.insert(String::from("env"), Object::Error(Errors::EnvNotAllowed));
+    }
+    if !config.allow_time {
+        builtins.insert(String::from("now"), Object::Error(Errors::SandboxViolation(String::from("now"))));
+    }
+    if config.sandbox {
+        for name in SIDE_EFFECTS.iter() {
+            builtins.insert(name.to_string(), Object::Error(Errors::SandboxViolation(name.to_string())));
+        }
+    }
     builtins
 }
 
@@ -22,13 +81,26 @@ fn len(args: Vec<Object>) -> Object {
         Object::Array(value) => {
             Object::Integer(value.len() as i32)
         }
-        Object::String(string) => Object::Integer(string.len() as i32),
+        // the length of a string is the number of characters, not bytes.
+        Object::String(string) => Object::Integer(string.chars().count() as i32),
         _ => {
             Object::Error(Errors::LenInvalidTypeError(Box::new(args[0].clone())))
             },
     }
 }
 
+// the number of bytes of a string encoded in UTF-8.
+fn byte_len(args: Vec<Object>) -> Object {
+    if args.len() != 1 {
+        return Object::Error(Errors::InvalidNumberOfArguments{got: args.len(), want:1});
+    }
+
+    match &args[0] {
+        Object::String(string) => Object::Integer(string.len() as i32),
+        _ => Object::Error(Errors::ByteLenTypeError(Box::new(args[0].clone())))
+    }
+}
+
 fn first(args: Vec<Object>) -> Object {
     match &args[0] {
         Object::Array(value) => {
@@ -76,9 +148,423 @@ fn push(args: Vec<Object>) -> Object {
     }
 }
 
+// a copy of the value, where arrays and hashes inside are also copied.
+// a value is never shared between variables, so the copy is independent of the original.
+fn clone(args: Vec<Object>) -> Object {
+    if args.len() != 1 {
+        return Object::Error(Errors::InvalidNumberOfArguments{got: args.len(), want:1});
+    }
+    args[0].clone()
+}
+
+// an array of [key, value] arrays of a hash, in the order of the keys.
+// keys are ordered by their types as integer < string < boolean, and by values in the same type.
+fn pairs(args: Vec<Object>) -> Object {
+    if args.len() != 1 {
+        return Object::Error(Errors::InvalidNumberOfArguments{got: args.len(), want:1});
+    }
+    match &args[0] {
+        Object::Hash(tree) => Object::Array(tree.values().map(|pair| Object::Array(vec![pair.key.clone(), pair.value.clone()])).collect()),
+        _ => Object::Error(Errors::PairsTypeError(Box::new(args[0].clone())))
+    }
+}
+
+// whether a hash has the key, even if the value of it is null.
+fn has(args: Vec<Object>) -> Object {
+    if args.len() != 2 {
+        return Object::Error(Errors::InvalidNumberOfArguments{got: args.len(), want:2});
+    }
+    match (&args[0], HashKey::get_hashkey(&args[1])) {
+        (Object::Hash(_), HashKey::Null) => Object::Error(Errors::HashKeyTypeError(Box::new(args[1].clone()))),
+        (Object::Hash(tree), key) => Object::Boolean(tree.contains_key(&key)),
+        _ => Object::Error(Errors::HasTypeError(Box::new(args[0].clone())))
+    }
+}
+
+// a new array whose values are sorted in ascending order.
+// values of different types are ordered by their types, see Object.
+fn sort(args: Vec<Object>) -> Object {
+    if args.len() != 1 {
+        return Object::Error(Errors::InvalidNumberOfArguments{got: args.len(), want:1});
+    }
+    match &args[0] {
+        Object::Array(value) => {
+            let mut array = value.clone();
+            array.sort();
+            Object::Array(array)
+        },
+        _ =>  Object::Error(Errors::SortTypeError(Box::new(args[0].clone())))
+    }
+}
+
+// a reversed copy of an array, or of a string by characters like reverse("héllo") == "olléh".
+fn reverse(args: Vec<Object>) -> Object {
+    if args.len() != 1 {
+        return Object::Error(Errors::InvalidNumberOfArguments{got: args.len(), want:1});
+    }
+    match &args[0] {
+        Object::Array(value) => Object::Array(value.iter().rev().cloned().collect()),
+        Object::String(value) => Object::String(value.chars().rev().collect::<String>().into()),
+        _ =>  Object::Error(Errors::ReverseTypeError(Box::new(args[0].clone())))
+    }
+}
+
+// the values of an array or the characters of a string from start until before end.
+// a negative index counts from the last like -1 is the last one, and an index outside is moved to the nearest end,
+// so slice([1, 2, 3, 4], 1, 3) == [2, 3], slice("hello", -3) == "llo" and slice([1], 0, 10) == [1].
+fn slice(args: Vec<Object>) -> Object {
+    let (start, end) = match args.as_slice() {
+        [_, Object::Integer(start)] => (*start, None),
+        [_, Object::Integer(start), Object::Integer(end)] => (*start, Some(*end)),
+        [_, start, Object::Integer(_)] | [_, start] => return Object::Error(Errors::SliceTypeError(Box::new(start.clone()))),
+        [_, _, end] => return Object::Error(Errors::SliceTypeError(Box::new(end.clone()))),
+        _ => return Object::Error(Errors::ArgumentCountOutOfRange{got: args.len(), min: 2, max: 3}),
+    };
+    match &args[0] {
+        Object::Array(value) => {
+            let (start, end) = slice_range(value.len(), start, end);
+            Object::Array(value[start..end].to_vec())
+        },
+        Object::String(value) => {
+            let (start, end) = slice_range(value.chars().count(), start, end);
+            Object::String(value.chars().skip(start).take(end - start).collect::<String>().into())
+        },
+        _ =>  Object::Error(Errors::SliceTypeError(Box::new(args[0].clone())))
+    }
+}
+
+fn slice_range(len: usize, start: i32, end: Option<i32>) -> (usize, usize) {
+    let clamp = |index: i32| -> usize {
+        let index = if index < 0 { index as i64 + len as i64 } else { index as i64 };
+        index.max(0).min(len as i64) as usize
+    };
+    let start = clamp(start);
+    let end = end.map(clamp).unwrap_or(len);
+    (start, end.max(start))
+}
+
+// a function which calls the first function with the result of the second one,
+// so compose(f, g)(x) is f(g(x)).
+fn compose(args: Vec<Object>) -> Object {
+    if args.len() != 2 {
+        return Object::Error(Errors::InvalidNumberOfArguments{got: args.len(), want:2});
+    }
+    for arg in args.iter() {
+        match arg {
+            Object::Function{..} | Object::Builtin{..} | Object::ReadLine{..} | Object::Rand{..} | Object::Composed{..} | Object::Partial{..} => {},
+            _ => return Object::Error(Errors::ComposeTypeError(Box::new(arg.clone())))
+        }
+    }
+    Object::Composed{outer: Box::new(args[0].clone()), inner: Box::new(args[1].clone())}
+}
+
+// a function which can be called with some of n arguments at a time,
+// and calls the function when all of them are given, like curry(add, 2)(1)(2).
+// n can be omitted for a user function, and then the number of its parameters is used.
+fn curry(args: Vec<Object>) -> Object {
+    let arity = match args.as_slice() {
+        [Object::Function{params, ..}] => params.len(),
+        [_, Object::Integer(arity)] if *arity >= 0 => *arity as usize,
+        [_, arity @ Object::Integer(_)] => return Object::Error(Errors::CurryTypeError(Box::new(arity.clone()))),
+        [_] | [_, _] => return Object::Error(Errors::CurryTypeError(Box::new(args[0].clone()))),
+        _ => return Object::Error(Errors::InvalidNumberOfArguments{got: args.len(), want:2}),
+    };
+    match &args[0] {
+        Object::Function{..} | Object::Builtin{..} | Object::ReadLine{..} | Object::Rand{..} | Object::Composed{..} | Object::Partial{..} => {
+            Object::Partial{function: Box::new(args[0].clone()), arity: arity, args: vec![]}
+        },
+        _ => Object::Error(Errors::CurryTypeError(Box::new(args[0].clone())))
+    }
+}
+
+// the number of arguments which a function takes.
+// a builtin accepts various numbers of arguments, so it is -1.
+fn arity(args: Vec<Object>) -> Object {
+    if args.len() != 1 {
+        return Object::Error(Errors::InvalidNumberOfArguments{got: args.len(), want:1});
+    }
+    match function_arity(&args[0]) {
+        Some(arity) => Object::Integer(arity),
+        None => Object::Error(Errors::ArityTypeError(Box::new(args[0].clone())))
+    }
+}
+
+fn function_arity(function: &Object) -> Option<i32> {
+    match function {
+        Object::Function{params, ..} => Some(params.len() as i32),
+        Object::Builtin{..} => Some(-1),
+        Object::ReadLine{..} => Some(0),
+        Object::Rand{ranged, ..} => Some(if *ranged { 2 } else { 0 }),
+        // compose(f, g) is called with the arguments of g.
+        Object::Composed{inner, ..} => function_arity(inner),
+        // the arguments which haven't been given yet.
+        Object::Partial{arity, args, ..} => Some(arity.saturating_sub(args.len()) as i32),
+        _ => None
+    }
+}
+
 fn print(args: Vec<Object>) -> Object {
     for arg in args.iter() {
         eprint!("{}", arg);
     }
     Object::Null
-}
\ No newline at end of file
+}
+
+fn assert(args: Vec<Object>) -> Object {
+    if args.len() != 1 {
+        return Object::Error(Errors::InvalidNumberOfArguments{got: args.len(), want:1});
+    }
+    if is_truthy(args[0].clone()) {
+        Object::Null
+    } else {
+        Object::Error(Errors::AssertionFailed)
+    }
+}
+
+fn assert_eq(args: Vec<Object>) -> Object {
+    if args.len() != 2 {
+        return Object::Error(Errors::InvalidNumberOfArguments{got: args.len(), want:2});
+    }
+    // objects are compared by their structure, so arrays or hashes
+    // which have the same elements are equal.
+    if args[0] == args[1] {
+        Object::Null
+    } else {
+        Object::Error(Errors::AssertEqFailed{left: Box::new(args[0].clone()), right: Box::new(args[1].clone())})
+    }
+}
+
+// between(x, lo, hi) is true when lo <= x <= hi.
+// this is used instead of chained comparison like lo <= x < hi, which isn't supported.
+fn between(args: Vec<Object>) -> Object {
+    if args.len() != 3 {
+        return Object::Error(Errors::InvalidNumberOfArguments{got: args.len(), want:3});
+    }
+    match (&args[0], &args[1], &args[2]) {
+        (Object::Integer(value), Object::Integer(low), Object::Integer(high)) => {
+            Object::Boolean(low <= value && value <= high)
+        },
+        _ => {
+            let invalid = args.iter().find(|arg| !matches!(arg, Object::Integer(_))).unwrap();
+            Object::Error(Errors::BetweenTypeError(Box::new(invalid.clone())))
+        }
+    }
+}
+
+// repeat(n, f) calls f n times, and returns the result of the last call.
+// if f has a parameter, the index of the iteration is passed.
+fn repeat(args: Vec<Object>) -> Object {
+    if args.len() != 2 {
+        return Object::Error(Errors::InvalidNumberOfArguments{got: args.len(), want:2});
+    }
+    let count = match &args[0] {
+        Object::Integer(count) => *count,
+        _ => return Object::Error(Errors::RepeatTypeError(Box::new(args[0].clone())))
+    };
+    let takes_index = match &args[1] {
+        Object::Function{params, ..} if params.len() <= 1 => params.len() == 1,
+        _ => return Object::Error(Errors::RepeatTypeError(Box::new(args[1].clone())))
+    };
+    let mut result = Object::Null;
+    for i in 0..count {
+        let arguments = if takes_index { vec![Object::Integer(i)] } else { vec![] };
+        result = match apply_function(args[1].clone(), arguments) {
+            Ok(Object::Error(error)) => return Object::Error(error),
+            Ok(value) => value,
+            Err(error) => return Object::Error(error)
+        };
+    }
+    result
+}
+
+// replace each `{}` in the template with the next argument, and `{{`, `}}` with a brace.
+// the number of `{}` must be the same as the number of arguments after the template.
+fn format(args: Vec<Object>) -> Object {
+    let template = match args.first() {
+        Some(Object::String(template)) => template,
+        Some(value) => return Object::Error(Errors::FormatTypeError(Box::new(value.clone()))),
+        None => return Object::Error(Errors::InvalidNumberOfArguments{got: 0, want:1})
+    };
+    let values = &args[1..];
+    let mut formatted = String::new();
+    let mut placeholders = 0;
+    let mut chars = template.chars().peekable();
+    while let Some(ch) = chars.next() {
+        match (ch, chars.peek()) {
+            ('{', Some('}')) => {
+                chars.next();
+                if let Some(value) = values.get(placeholders) {
+                    formatted.push_str(&value.to_string());
+                }
+                placeholders += 1;
+            },
+            ('{', Some('{')) | ('}', Some('}')) => {
+                chars.next();
+                formatted.push(ch);
+            },
+            _ => formatted.push(ch)
+        }
+    }
+    if placeholders != values.len() {
+        return Object::Error(Errors::InvalidNumberOfArguments{got: args.len(), want: placeholders + 1});
+    }
+    Object::String(formatted.into())
+}
+
+// the value of an environment variable of the host, or null if it isn't set.
+fn env(args: Vec<Object>) -> Object {
+    if args.len() != 1 {
+        return Object::Error(Errors::InvalidNumberOfArguments{got: args.len(), want:1});
+    }
+    match &args[0] {
+        Object::String(name) => match std::env::var(&**name) {
+            Ok(value) => Object::String(value.into()),
+            Err(_) => Object::Null
+        },
+        _ => Object::Error(Errors::EnvTypeError(Box::new(args[0].clone())))
+    }
+}
+
+// milliseconds since the unix epoch. it is a float because the milliseconds don't fit in an integer.
+fn now(args: Vec<Object>) -> Object {
+    if !args.is_empty() {
+        return Object::Error(Errors::InvalidNumberOfArguments{got: args.len(), want:0});
+    }
+    match SystemTime::now().duration_since(UNIX_EPOCH) {
+        Ok(elapsed) => Object::Float(Float(elapsed.as_millis() as f64)),
+        Err(_) => Object::Float(Float(0.0)),
+    }
+}
+
+// rand() returns an integer from 0 to 2147483647,
+// and rand_range(low, high) returns an integer from low to high - 1.
+pub fn rand(rng: &Rng, ranged: bool, args: Vec<Object>) -> Object {
+    if !ranged {
+        if !args.is_empty() {
+            return Object::Error(Errors::InvalidNumberOfArguments{got: args.len(), want:0});
+        }
+        return Object::Integer((rng.next_u64() >> 33) as i32)
+    }
+    if args.len() != 2 {
+        return Object::Error(Errors::InvalidNumberOfArguments{got: args.len(), want:2});
+    }
+    match (&args[0], &args[1]) {
+        (Object::Integer(low), Object::Integer(high)) if low < high => {
+            let width = (*high as i64 - *low as i64) as u64;
+            Object::Integer((*low as i64 + (rng.next_u64() % width) as i64) as i32)
+        },
+        (Object::Integer(low), Object::Integer(high)) => Object::Error(Errors::EmptyRange{low: *low, high: *high}),
+        (Object::Integer(_), invalid) | (invalid, _) => Object::Error(Errors::RandRangeTypeError(Box::new(invalid.clone())))
+    }
+}
+
+// a line read from the input without the trailing newline, or null at the end of the input.
+pub fn read_line(input: &Input, args: Vec<Object>) -> Object {
+    if !args.is_empty() {
+        return Object::Error(Errors::InvalidNumberOfArguments{got: args.len(), want:0});
+    }
+    match input.read_line() {
+        Ok(line) if line.is_empty() => Object::Null,
+        Ok(line) => Object::String(line.trim_end_matches(&['\n', '\r'][..]).into()),
+        Err(error) => Object::Error(Errors::InputNotReadable(error.to_string()))
+    }
+}
+
+// the unicode code point of a string which has only one character.
+fn ord(args: Vec<Object>) -> Object {
+    if args.len() != 1 {
+        return Object::Error(Errors::InvalidNumberOfArguments{got: args.len(), want:1});
+    }
+    if let Object::String(string) = &args[0] {
+        let mut chars = string.chars();
+        if let (Some(ch), None) = (chars.next(), chars.next()) {
+            return Object::Integer(ch as i32)
+        }
+    }
+    Object::Error(Errors::OrdTypeError(Box::new(args[0].clone())))
+}
+
+// the string of one character whose unicode code point is the argument.
+fn char(args: Vec<Object>) -> Object {
+    if args.len() != 1 {
+        return Object::Error(Errors::InvalidNumberOfArguments{got: args.len(), want:1});
+    }
+    if let Object::Integer(code) = &args[0] {
+        if let Some(ch) = std::char::from_u32(*code as u32).filter(|_| *code >= 0) {
+            return Object::String(ch.to_string().into())
+        }
+    }
+    Object::Error(Errors::CharTypeError(Box::new(args[0].clone())))
+}
+
+fn abs(args: Vec<Object>) -> Object {
+    if args.len() != 1 {
+        return Object::Error(Errors::InvalidNumberOfArguments{got: args.len(), want:1});
+    }
+    match &args[0] {
+        Object::Integer(value) => match value.checked_abs() {
+            Some(value) => Object::Integer(value),
+            None => Object::Error(Errors::IntegerOverflow)
+        },
+        _ => Object::Error(Errors::AbsTypeError(Box::new(args[0].clone())))
+    }
+}
+
+// the exponent must not be negative, because the result of an integer power is an integer.
+fn pow(args: Vec<Object>) -> Object {
+    if args.len() != 2 {
+        return Object::Error(Errors::InvalidNumberOfArguments{got: args.len(), want:2});
+    }
+    match (&args[0], &args[1]) {
+        (Object::Integer(base), Object::Integer(exp)) if *exp >= 0 => match base.checked_pow(*exp as u32) {
+            Some(value) => Object::Integer(value),
+            None => Object::Error(Errors::IntegerOverflow)
+        },
+        (Object::Integer(_), exp) => Object::Error(Errors::PowTypeError(Box::new(exp.clone()))),
+        (base, _) => Object::Error(Errors::PowTypeError(Box::new(base.clone())))
+    }
+}
+
+// -1, 0 or 1 for a negative integer, zero or a positive one.
+fn sign(args: Vec<Object>) -> Object {
+    if args.len() != 1 {
+        return Object::Error(Errors::InvalidNumberOfArguments{got: args.len(), want:1});
+    }
+    match &args[0] {
+        Object::Integer(value) => Object::Integer(value.signum()),
+        _ => Object::Error(Errors::SignTypeError(Box::new(args[0].clone())))
+    }
+}
+
+// whether the only argument is of the type.
+fn is_type(args: Vec<Object>, test: fn(&Object) -> bool) -> Object {
+    if args.len() != 1 {
+        return Object::Error(Errors::InvalidNumberOfArguments{got: args.len(), want:1});
+    }
+    Object::Boolean(test(&args[0]))
+}
+
+fn is_array(args: Vec<Object>) -> Object {
+    is_type(args, |value| matches!(value, Object::Array(_)))
+}
+
+fn is_hash(args: Vec<Object>) -> Object {
+    is_type(args, |value| matches!(value, Object::Hash(_)))
+}
+
+fn is_int(args: Vec<Object>) -> Object {
+    is_type(args, |value| matches!(value, Object::Integer(_)))
+}
+
+fn is_string(args: Vec<Object>) -> Object {
+    is_type(args, |value| matches!(value, Object::String(_)))
+}
+
+// builtins and functions made by compose or curry are also functions.
+fn is_fn(args: Vec<Object>) -> Object {
+    is_type(args, |value| matches!(value, Object::Function{..} | Object::Builtin{..} | Object::ReadLine{..} | Object::Rand{..} | Object::Composed{..} | Object::Partial{..}))
+}
+
+fn is_null(args: Vec<Object>) -> Object {
+    is_type(args, |value| matches!(value, Object::Null))
+}
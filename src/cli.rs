@@ -0,0 +1,199 @@
+use std::fs;
+use std::io::{self, Read};
+
+use super::parser;
+use super::ast::{Program};
+use super::ast_json;
+use super::format;
+use super::evaluator::{Environment};
+use super::object::{Object};
+use super::errors::{Errors};
+
+// handle command line arguments and return the exit status.
+pub fn run_cli(args: &[String]) -> i32 {
+    run_cli_with_input(args, io::stdin())
+}
+
+// the same as run_cli, but the program given by `-` is read from input instead of stdin.
+pub fn run_cli_with_input<R: Read>(args: &[String], mut input: R) -> i32 {
+    match args {
+        [path] if path == "-" => {
+            let mut source = String::new();
+            let result = match input.read_to_string(&mut source) {
+                Ok(_) => run_source(&source),
+                Err(error) => Err(vec![Errors::InputNotReadable(error.to_string())])
+            };
+            match result {
+                Ok(()) => 0,
+                Err(errors) => report_errors("<stdin>", &errors)
+            }
+        },
+        [option, path] if option == "--check" => {
+            match check_file(path) {
+                Ok(()) => 0,
+                Err(errors) => report_errors(path, &errors)
+            }
+        },
+        [option, path] if option == "--ast-json" => {
+            match parse_file(path) {
+                Ok(program) => {
+                    println!("{}", ast_json::ast_to_json(&program));
+                    0
+                },
+                Err(errors) => report_errors(path, &errors)
+            }
+        },
+        [option, path] if option == "--format" => {
+            match format_file(path) {
+                Ok(source) => {
+                    print!("{}", source);
+                    0
+                },
+                Err(errors) => report_errors(path, &errors)
+            }
+        },
+        [path] if !path.starts_with("--") => {
+            match run_file(path) {
+                Ok(()) => 0,
+                Err(errors) => report_errors(path, &errors)
+            }
+        },
+        _ => {
+            eprintln!("usage: monkey [<file> | - | --check <file> | --ast-json <file> | --format <file>]");
+            2
+        }
+    }
+}
+
+fn report_errors(path: &str, errors: &[Errors]) -> i32 {
+    for error in errors.iter() {
+        eprintln!("{}: {}", path, error);
+    }
+    1
+}
+
+// parse the file without evaluating it, and return all syntax errors in it.
+pub fn check_file(path: &str) -> Result<(), Vec<Errors>> {
+    parse_file(path)?;
+    Ok(())
+}
+
+// evaluate the file, which can start with a shebang line to be executed directly.
+pub fn run_file(path: &str) -> Result<(), Vec<Errors>> {
+    let program = parse_file(path)?;
+    evaluate_program(&program, Some(path))
+}
+
+// evaluate a program which is given as a string, like one read from a pipe.
+pub fn run_source(input: &str) -> Result<(), Vec<Errors>> {
+    let mut parser = parser::Parser::new_from_source(input);
+    let program = parser.parse_program_with_errors()?;
+    evaluate_program(&program, None)
+}
+
+// imports in the program are relative to the directory of the file at the path if it is given.
+fn evaluate_program(program: &Program, path: Option<&str>) -> Result<(), Vec<Errors>> {
+    let mut env = Environment::new();
+    let result = match path {
+        Some(path) => env.evaluate_file(program, path),
+        None => env.evaluate(program)
+    };
+    match result {
+        Ok(Object::Error(error)) => Err(vec![error]),
+        Err(error) => Err(vec![error]),
+        Ok(_) => Ok(())
+    }
+}
+
+// the source of the file in the style of format::format, keeping its comments.
+pub fn format_file(path: &str) -> Result<String, Vec<Errors>> {
+    let input = match fs::read_to_string(path) {
+        Ok(input) => input,
+        Err(_) => return Err(vec![Errors::FileNotReadable(path.to_string())])
+    };
+    let mut parser = parser::Parser::new_with_comments(&input);
+    let source = format::format(&parser.parse_program_with_errors()?);
+    // the lexer skips a shebang line instead of making a comment of it, so it is written back as it is.
+    match input.lines().next() {
+        Some(shebang) if shebang.starts_with("#!") => Ok(format!("{}\n{}", shebang, source)),
+        _ => Ok(source)
+    }
+}
+
+fn parse_file(path: &str) -> Result<Program, Vec<Errors>> {
+    let input = match fs::read_to_string(path) {
+        Ok(input) => input,
+        Err(_) => return Err(vec![Errors::FileNotReadable(path.to_string())])
+    };
+    let mut parser = parser::Parser::new_from_source(&input);
+    parser.parse_program_with_errors()
+}
+
+#[cfg(test)]// test runs only when execute cargo run
+mod testing {
+    use std::fs;
+    use std::env;
+    use std::io::Cursor;
+    use crate::cli::{check_file, run_file, format_file, run_cli, run_cli_with_input};
+
+    fn write_script(name: &str, input: &str) -> String {
+        let path = env::temp_dir().join(name);
+        fs::write(&path, input).unwrap();
+        path.to_str().unwrap().to_string()
+    }
+
+    #[test]
+    fn test_check_file() {
+        let path = write_script("monkey_check_two_errors.mk", "let = 5;\nlet x = 1;\nlet y 2;\n");
+        let errors = check_file(&path).unwrap_err();
+        assert_eq!(errors.len(), 2);
+        assert_eq!(run_cli(&["--check".to_string(), path]), 1);
+
+        let path = write_script("monkey_check_no_errors.mk", "let x = 1;\nlet y = x + 2;\n");
+        assert_eq!(check_file(&path), Ok(()));
+        assert_eq!(run_cli(&["--check".to_string(), path]), 0);
+    }
+
+    #[test]
+    fn test_ast_json_option() {
+        let path = write_script("monkey_ast_json.mk", "1 + 2;\n");
+        assert_eq!(run_cli(&["--ast-json".to_string(), path]), 0);
+        let path = write_script("monkey_ast_json_error.mk", "let = 1;\n");
+        assert_eq!(run_cli(&["--ast-json".to_string(), path]), 1);
+        assert_eq!(run_cli(&["--ast-json".to_string()]), 2);
+    }
+
+    #[test]
+    fn test_format_option() {
+        let path = write_script("monkey_format.mk", "#!/usr/bin/env monkey\n# note\nlet x=1 # one\nprint( x+1 )");
+        assert_eq!(format_file(&path), Ok(String::from("#!/usr/bin/env monkey\n# note\nlet x = 1; # one\nprint(x + 1);\n")));
+        assert_eq!(run_cli(&["--format".to_string(), path]), 0);
+        let path = write_script("monkey_format_error.mk", "let = 1;\n");
+        assert_eq!(run_cli(&["--format".to_string(), path]), 1);
+    }
+
+    #[test]
+    fn test_run_file() {
+        let path = write_script("monkey_run_shebang.mk", "#!/usr/bin/env monkey\nlet x = 1;\nassert_eq(x + 1, 2);\n");
+        assert_eq!(run_file(&path), Ok(()));
+        assert_eq!(run_cli(&[path]), 0);
+
+        let path = write_script("monkey_run_error.mk", "#!/usr/bin/env monkey\n1 + true;\n");
+        assert_eq!(run_cli(&[path.clone()]), 1);
+        assert_eq!(check_file(&path), Ok(()));
+
+        // a file imported by the script is found next to it, not in the current directory.
+        write_script("monkey_run_imported.mk", "let two = 2;\n");
+        let path = write_script("monkey_run_import.mk", "import \"monkey_run_imported.mk\";\nassert_eq(two, 2);\n");
+        assert_eq!(run_file(&path), Ok(()));
+    }
+
+    #[test]
+    fn test_run_stdin() {
+        let args = vec!["-".to_string()];
+        assert_eq!(run_cli_with_input(&args, Cursor::new("let x = 1 + 1;\nassert_eq(x, 2);\n")), 0);
+        assert_eq!(run_cli_with_input(&args, Cursor::new("1 + true;")), 1);
+        assert_eq!(run_cli_with_input(&args, Cursor::new("let = 1;")), 1);
+        assert_eq!(run_cli_with_input(&args, Cursor::new("")), 0);
+    }
+}
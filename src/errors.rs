@@ -1,41 +1,198 @@
 use std::fmt;
 
-use super::token::{Token};
+use super::token::{Token, TokenKind};
 use super::object::{Object};
 
 #[derive(Debug, PartialEq, Clone, Eq, Ord, PartialOrd)]
 pub enum Errors {
     TokenInvalid(Token),
+    // a token which can't start a statement, like a stray ')' after an expression.
+    UnexpectedToken(Token),
+    // a feature which is turned off in ParserConfig, like "arrays".
+    FeatureDisabled(String),
     InvalidOperator(String),
+    // a prefix operator which the type of the operand doesn't support, like -"x".
+    UnknownOperator{operator: String,
+                    right: String
+                   },
     InvalidInteger(Box<Object>),
     InvalidIdentifier(Box<Object>),
-    InvalidInfix,
     NodeError,
     InvalidNumberOfArguments{got: usize,
                              want: usize
                             },
+    // a builtin which takes from min to max arguments like slice.
+    ArgumentCountOutOfRange{got: usize,
+                            min: usize,
+                            max: usize
+                           },
+    // a user function called with arguments which don't match its parameters.
+    WrongArgCount{expected: usize,
+                  got: usize,
+                  function: String
+                 },
     LenInvalidTypeError(Box<Object>),
+    ByteLenTypeError(Box<Object>),
     FirstTypeError(Box<Object>),
     LastTypeError(Box<Object>),
     RestTypeError(Box<Object>),
     PushTypeError(Box<Object>),
+    BetweenTypeError(Box<Object>),
+    RepeatTypeError(Box<Object>),
+    RandRangeTypeError(Box<Object>),
+    // rand_range(low, high) where no integer is in low..high.
+    EmptyRange{low: i32,
+               high: i32
+              },
+    StringIndexTypeError(Box<Object>),
+    OrdTypeError(Box<Object>),
+    SortTypeError(Box<Object>),
+    ReverseTypeError(Box<Object>),
+    SliceTypeError(Box<Object>),
+    PairsTypeError(Box<Object>),
+    HasTypeError(Box<Object>),
+    FormatTypeError(Box<Object>),
+    EnvTypeError(Box<Object>),
+    AbsTypeError(Box<Object>),
+    PowTypeError(Box<Object>),
+    SignTypeError(Box<Object>),
+    EnvNotAllowed,
+    SandboxViolation(String),
+    ComposeTypeError(Box<Object>),
+    CurryTypeError(Box<Object>),
+    ArityTypeError(Box<Object>),
+    CharTypeError(Box<Object>),
+    AlreadyDeclared(String),
+    NotDeclared(String),
+    AssignToConstant(String),
+    InvalidAssignTarget(String),
+    DuplicateParameter(String),
+    IndexOutOfRange{index: i32,
+                    length: usize
+                   },
+    ArrayIndexTypeError(Box<Object>),
+    IndexAssignTypeError(Box<Object>),
+    HashKeyTypeError(Box<Object>),
+    UnusedVariable(String),
+    // a type annotation which isn't a known type name.
+    UnknownType(String),
+    AnnotationMismatch{name: String,
+                       annotation: String,
+                       got: String
+                      },
+    Located{error: Box<Errors>,
+            line: usize,
+            column: usize
+           },
+    FileNotReadable(String),
+    // a file which is imported again while it is being imported.
+    CircularImport(String),
+    InputNotReadable(String),
+    StringTooLarge{length: usize,
+                   max: usize
+                  },
+    ArrayTooLarge{length: usize,
+                  max: usize
+                 },
+    UnexpectedEof{expected: Option<TokenKind>},
+    TypeMismatch{left: String,
+                 operator: String,
+                 right: String
+                },
+    AssertionFailed,
+    AssertEqFailed{left: Box<Object>,
+                   right: Box<Object>
+                  },
+    BreakOutsideLoop,
+    DivisionByZero,
+    IntegerOverflow,
+    Aborted,
+    StepLimitExceeded{max: usize},
+    // an error which happened in function calls, frames are ordered from the innermost call.
+    Backtrace{error: Box<Errors>,
+              frames: Vec<String>
+             },
 }
 
 impl fmt::Display for Errors {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
-            Errors::TokenInvalid(value) => write!(f, "invalid token: {:?}", value),
+            Errors::TokenInvalid(value) => write!(f, "invalid token: {}", value),
+            Errors::UnexpectedToken(value) => write!(f, "unexpected token: {}", value),
+            Errors::FeatureDisabled(value) => write!(f, "{} are disabled", value),
             Errors::InvalidOperator(value) => write!(f, "invalid operator: {}", value),
+            Errors::UnknownOperator{operator, right} => write!(f, "unknown operator: {}{}", operator, right),
             Errors::InvalidInteger(value) => write!(f, "invalid integer: {}", value),
             Errors::InvalidIdentifier(value) => write!(f, "invalid identifier: {}", value),
-            Errors::InvalidInfix => write!(f, "invalid_infix"),
             Errors::NodeError => write!(f, "node_error"),
             Errors::InvalidNumberOfArguments{got, want} => write!(f, "wrong number of arguments. got={}, want={}",got, want),
+            Errors::ArgumentCountOutOfRange{got, min, max} => write!(f, "wrong number of arguments. got={}, want={}..{}", got, min, max),
+            Errors::WrongArgCount{expected, got, function} => write!(f, "wrong number of arguments to {}: expected={}, got={}", function, expected, got),
             Errors::LenInvalidTypeError(value) => write!(f, "argument to len not supported got {}", value),
+            Errors::ByteLenTypeError(value) => write!(f, "argument to 'byte_len' must be string, got {}", value),
             Errors::FirstTypeError(value) => write!(f, "argument to 'first' must be array, got {}", value),
             Errors::LastTypeError(value) => write!(f, "argument to 'last' must be array, got {}", value),
             Errors::RestTypeError(value) => write!(f, "argument to 'rest' must be array, got {}", value),
-            Errors::PushTypeError(value) => write!(f, "argument to 'push' must be array, got {}", value)
+            Errors::PushTypeError(value) => write!(f, "argument to 'push' must be array, got {}", value),
+            Errors::BetweenTypeError(value) => write!(f, "arguments to 'between' must be integer, got {}", value),
+            Errors::StringIndexTypeError(value) => write!(f, "index of string must be integer, got {}", value),
+            Errors::ComposeTypeError(value) => write!(f, "arguments to 'compose' must be function, got {}", value),
+            Errors::CurryTypeError(value) => write!(f, "arguments to 'curry' must be function and the number of arguments, got {}", value),
+            Errors::ArityTypeError(value) => write!(f, "argument to 'arity' must be function, got {}", value),
+            Errors::SortTypeError(value) => write!(f, "argument to 'sort' must be array, got {}", value),
+            Errors::ReverseTypeError(value) => write!(f, "argument to 'reverse' must be array or string, got {}", value),
+            Errors::SliceTypeError(value) => write!(f, "arguments to 'slice' must be array or string and integers, got {}", value),
+            Errors::AbsTypeError(value) => write!(f, "argument to 'abs' must be integer, got {}", value),
+            Errors::PowTypeError(value) => write!(f, "arguments to 'pow' must be integer and non-negative integer, got {}", value),
+            Errors::SignTypeError(value) => write!(f, "argument to 'sign' must be integer, got {}", value),
+            Errors::EnvTypeError(value) => write!(f, "argument to 'env' must be string, got {}", value),
+            Errors::EnvNotAllowed => write!(f, "'env' is disabled, set allow_env in EvalConfig to enable it"),
+            Errors::SandboxViolation(value) => write!(f, "'{}' is not allowed in sandbox", value),
+            Errors::FormatTypeError(value) => write!(f, "first argument to 'format' must be string, got {}", value),
+            Errors::PairsTypeError(value) => write!(f, "argument to 'pairs' must be hash, got {}", value),
+            Errors::HasTypeError(value) => write!(f, "first argument to 'has' must be hash, got {}", value),
+            Errors::OrdTypeError(value) => write!(f, "argument to 'ord' must be string of one character, got {}", value),
+            Errors::CharTypeError(value) => write!(f, "argument to 'char' must be valid code point, got {}", value),
+            Errors::RepeatTypeError(value) => write!(f, "argument to 'repeat' must be integer and function with zero or one parameter, got {}", value),
+            Errors::RandRangeTypeError(value) => write!(f, "arguments to 'rand_range' must be integer, got {}", value),
+            Errors::EmptyRange{low, high} => write!(f, "empty range: low={}, high={}", low, high),
+            Errors::AlreadyDeclared(value) => write!(f, "identifier '{}' has already been declared", value),
+            Errors::NotDeclared(value) => write!(f, "identifier '{}' has not been declared", value),
+            Errors::AssignToConstant(value) => write!(f, "can't assign to constant '{}'", value),
+            Errors::InvalidAssignTarget(value) => write!(f, "invalid assignment target: {}", value),
+            Errors::DuplicateParameter(value) => write!(f, "duplicate parameter '{}'", value),
+            Errors::IndexOutOfRange{index, length} => write!(f, "index out of range: index={}, length={}", index, length),
+            Errors::ArrayIndexTypeError(value) => write!(f, "index of array must be integer, got {}", value),
+            Errors::IndexAssignTypeError(value) => write!(f, "index assignment not supported: {}", value),
+            Errors::HashKeyTypeError(value) => write!(f, "unusable as hash key: {}", value),
+            Errors::UnusedVariable(value) => write!(f, "'{}' is declared but never used", value),
+            Errors::UnknownType(value) => write!(f, "unknown type '{}'", value),
+            Errors::AnnotationMismatch{name, annotation, got} => write!(f, "'{}' is annotated as {}, got {}", name, annotation, got),
+            Errors::Located{error, line, column} => write!(f, "line {}, column {}: {}", line, column, error),
+            Errors::FileNotReadable(value) => write!(f, "could not read file: {}", value),
+            Errors::CircularImport(value) => write!(f, "circular import of '{}'", value),
+            Errors::InputNotReadable(value) => write!(f, "could not read input: {}", value),
+            Errors::StringTooLarge{length, max} => write!(f, "string too large: length={}, max={}", length, max),
+            Errors::ArrayTooLarge{length, max} => write!(f, "array too large: length={}, max={}", length, max),
+            Errors::UnexpectedEof{expected} => match expected {
+                Some(expected) => write!(f, "unexpected end of input, expected {:?}", expected),
+                None => write!(f, "unexpected end of input"),
+            },
+            Errors::TypeMismatch{left, operator, right} => write!(f, "type mismatch: {} {} {}", left, operator, right),
+            Errors::AssertionFailed => write!(f, "assertion failed"),
+            Errors::AssertEqFailed{left, right} => write!(f, "assertion failed: left: {}, right: {}", left, right),
+            Errors::BreakOutsideLoop => write!(f, "break outside loop"),
+            Errors::DivisionByZero => write!(f, "division by zero"),
+            Errors::IntegerOverflow => write!(f, "integer overflow"),
+            Errors::Aborted => write!(f, "evaluation aborted"),
+            Errors::StepLimitExceeded{max} => write!(f, "step limit exceeded: max={}", max),
+            Errors::Backtrace{error, frames} => {
+                write!(f, "{}", error)?;
+                for frame in frames.iter() {
+                    write!(f, "\n  at {}", frame)?;
+                }
+                Ok(())
+            }
         }
     }
 }
\ No newline at end of file
@@ -1,47 +1,571 @@
 use std::fmt;
-use std::collections::BTreeMap;
+use std::cmp::Ordering;
+use std::cell::{Cell, RefCell};
+use std::rc::{Rc, Weak};
+use std::io::{self, BufRead, BufReader};
+use std::fs;
+use std::path::Path;
+use std::collections::{BTreeMap, BTreeSet};
+use std::collections::btree_map::Entry;
 
 use crate::ast;
 use super::object::{Object, HashKey, HashPair};
 use super::errors::{Errors};
 use super::ast::{Expression};
 use super::builtins;
+use super::parser;
+use super::invariant;
+use super::resolver;
+
+// settings which change how a program is evaluated.
+#[derive(Debug,PartialEq, Clone, Eq, Ord, PartialOrd)]
+pub struct EvalConfig {
+    // the upper limit of the length of a string or an array made by repetition like "ab" * 3,
+    // and of an array made by concatenation like a + a, which doubles it each time.
+    pub max_repeat_length: usize,
+    // if a function calls itself in tail position like `return f(n - 1)`,
+    // the call is evaluated by a loop instead of recursion, so that deep recursion doesn't overflow the stack.
+    pub tail_calls: bool,
+    // `env` reads environment variables of the host only if this is true.
+    pub allow_env: bool,
+    // `now` reads the clock of the host only if this is true.
+    pub allow_time: bool,
+    // builtins which have side effects outside of the program like print and env
+    // return an error, so that untrusted code can be evaluated safely.
+    pub sandbox: bool,
+    // the stream which read_line reads, stdin by default.
+    pub input: Input,
+    // a callback which is called before each statement and expression is evaluated.
+    pub trace: Option<Tracer>,
+    // the upper limit of the number of statements and expressions evaluated by one call of evaluate().
+    pub max_steps: Option<usize>,
+    // functions bound by let or const at the top level of a program can call each other
+    // even if the callee is bound after the caller, like mutually recursive functions.
+    pub hoist_functions: bool,
+    // true and false are used as 1 and 0 in arithmetic like true + true == 2.
+    // arithmetic on booleans is a type mismatch if this is false.
+    pub bool_arithmetic: bool,
+    // the seed of the generator which rand and rand_range use.
+    // environments made from configs with the same seed produce the same numbers.
+    pub seed: u64,
+    // a call of a pure builtin in a loop like len(arr), whose arguments the loop doesn't change,
+    // is evaluated once and its value is reused in the other iterations.
+    pub memoize_invariants: bool,
+    // a value bound by let with an annotation like `let x: Int = 5;` must have the type,
+    // otherwise the annotations are ignored.
+    pub check_types: bool,
+    // a variable is looked up in the scope where it is bound, which is found before the program is evaluated,
+    // instead of searching every scope on the way.
+    pub resolve_names: bool,
+}
+
+impl Default for EvalConfig {
+    fn default() -> EvalConfig {
+        EvalConfig{max_repeat_length: 1_000_000, tail_calls: true, allow_env: false, allow_time: true, sandbox: false, input: Input::stdin(), trace: None, max_steps: None, hoist_functions: true, bool_arithmetic: false, seed: 0, memoize_invariants: false, check_types: false, resolve_names: false}
+    }
+}
+
+// an input stream shared by the environments and functions made from the same config.
+// inputs are equal only if they are the same stream.
+#[derive(Clone)]
+pub struct Input(Rc<RefCell<Box<dyn BufRead>>>);
+
+impl Input {
+    pub fn new<R: BufRead + 'static>(reader: R) -> Input {
+        Input(Rc::new(RefCell::new(Box::new(reader))))
+    }
+
+    pub fn stdin() -> Input {
+        Input::new(BufReader::new(io::stdin()))
+    }
+
+    // read a line including the trailing newline, or an empty string at the end of the stream.
+    pub fn read_line(&self) -> io::Result<String> {
+        let mut line = String::new();
+        self.0.borrow_mut().read_line(&mut line)?;
+        Ok(line)
+    }
+
+    fn address(&self) -> usize {
+        &*self.0 as *const RefCell<Box<dyn BufRead>> as usize
+    }
+}
+
+impl fmt::Debug for Input {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Input({:#x})", self.address())
+    }
+}
+
+impl PartialEq for Input {
+    fn eq(&self, other: &Input) -> bool {
+        Rc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl Eq for Input {}
+
+impl Ord for Input {
+    fn cmp(&self, other: &Input) -> Ordering {
+        self.address().cmp(&other.address())
+    }
+}
+
+impl PartialOrd for Input {
+    fn partial_cmp(&self, other: &Input) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+// a xorshift pseudo-random generator shared by the rand builtins made from the same config.
+// generators are equal only if they are the same generator.
+#[derive(Clone)]
+pub struct Rng(Rc<Cell<u64>>);
+
+impl Rng {
+    pub fn new(seed: u64) -> Rng {
+        // xorshift gets stuck at zero, so zero is replaced with another seed.
+        Rng(Rc::new(Cell::new(if seed == 0 { 0x9E37_79B9_7F4A_7C15 } else { seed })))
+    }
+
+    pub fn next_u64(&self) -> u64 {
+        let mut x = self.0.get();
+        x ^= x << 13;
+        x ^= x >> 7;
+        x ^= x << 17;
+        self.0.set(x);
+        x
+    }
+
+    fn address(&self) -> usize {
+        &*self.0 as *const Cell<u64> as usize
+    }
+}
+
+impl fmt::Debug for Rng {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Rng({:#x})", self.address())
+    }
+}
+
+impl PartialEq for Rng {
+    fn eq(&self, other: &Rng) -> bool {
+        Rc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl Eq for Rng {}
+
+impl Ord for Rng {
+    fn cmp(&self, other: &Rng) -> Ordering {
+        self.address().cmp(&other.address())
+    }
+}
+
+impl PartialOrd for Rng {
+    fn partial_cmp(&self, other: &Rng) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+// a node which is going to be evaluated, given to the trace callback.
+#[derive(Debug, Clone, Copy)]
+pub enum TraceNode<'a> {
+    Statement(&'a ast::Statement),
+    Expression(&'a ast::Expression),
+}
+
+// what the evaluator does after the trace callback returns.
+// to pause at a breakpoint, the callback waits until the debugger resumes it.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum TraceSignal {
+    Continue,
+    // stop the evaluation with an error.
+    Abort,
+}
+
+// the callback which receives the node and the depth of function calls.
+type TraceCallback = dyn FnMut(TraceNode<'_>, usize) -> TraceSignal;
+
+// the trace callback and the depth of function calls, which are shared by
+// the environments and functions made from the same config.
+#[derive(Clone)]
+pub struct Tracer {
+    callback: Rc<RefCell<TraceCallback>>,
+    depth: Rc<Cell<usize>>,
+}
+
+impl Tracer {
+    // the callback receives the node and the depth of function calls, which is 0 at top level.
+    pub fn new<F: FnMut(TraceNode<'_>, usize) -> TraceSignal + 'static>(callback: F) -> Tracer {
+        Tracer{callback: Rc::new(RefCell::new(callback)), depth: Rc::new(Cell::new(0))}
+    }
+
+    fn signal(&self, node: TraceNode<'_>) -> TraceSignal {
+        (self.callback.borrow_mut())(node, self.depth.get())
+    }
+
+    // the depth is increased until the returned guard is dropped.
+    fn enter(&self) -> CallDepth {
+        self.depth.set(self.depth.get() + 1);
+        CallDepth(self.depth.clone())
+    }
+
+    fn address(&self) -> usize {
+        &*self.callback as *const RefCell<TraceCallback> as *const u8 as usize
+    }
+}
+
+struct CallDepth(Rc<Cell<usize>>);
+
+impl Drop for CallDepth {
+    fn drop(&mut self) {
+        self.0.set(self.0.get() - 1);
+    }
+}
+
+impl fmt::Debug for Tracer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Tracer({:#x})", self.address())
+    }
+}
+
+impl PartialEq for Tracer {
+    fn eq(&self, other: &Tracer) -> bool {
+        self.address() == other.address()
+    }
+}
+
+impl Eq for Tracer {}
+
+impl Ord for Tracer {
+    fn cmp(&self, other: &Tracer) -> Ordering {
+        self.address().cmp(&other.address())
+    }
+}
+
+impl PartialOrd for Tracer {
+    fn partial_cmp(&self, other: &Tracer) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+// the result of evaluating the tail position of a function body.
+enum Tail {
+    Value(Object),
+    // the function calls itself with the arguments.
+    SelfCall(Vec<Object>),
+}
 
 #[derive(Debug,PartialEq, Clone, Eq, Ord, PartialOrd)]
 pub struct Environment {
     store: BTreeMap<String, Object>,
+    // names bound by const in this scope, which can't be bound again.
+    constants: BTreeSet<String>,
+    outer: Option<Box<Environment>>,
+    // the builtins are the same in every scope, so they are shared instead of copied.
+    builtin: Rc<BTreeMap<String, Object>>,
+    config: EvalConfig,
+    steps: Steps,
+    // functions which are bound in the scope which a function keeps,
+    // so that a function bound by let can call itself by the name.
+    recursive: Definitions,
+    // the functions at the top level of the program which is being evaluated.
+    hoisted: Hoisted,
+    // the tables of the hoisted functions of the programs which this has evaluated.
+    // the scopes made from this don't have them, so that only the first scope owns them.
+    hoisted_tables: Shared<Vec<Rc<HoistedTable>>>,
+    imports: Imports,
+    strings: Strings,
+    memo: Memo,
+    // the function whose scope this is, which is none for the top level and the scope of a call.
+    identity: Identity,
+}
+
+// the parameters and the body of functions by their names.
+// the body is shared with the functions made from it, so that copying a function doesn't copy its body.
+type Definitions = BTreeMap<String, (Vec<Expression>, Rc<ast::Statement>)>;
+
+// the variables of an environment at a time, made by Environment::snapshot().
+#[derive(Debug,PartialEq, Clone, Eq)]
+pub struct Snapshot {
+    store: BTreeMap<String, Object>,
+    constants: BTreeSet<String>,
     outer: Option<Box<Environment>>,
-    builtin: BTreeMap<String, Object>
+}
+
+// the names of function literals bound by let or const at the top level.
+// a name bound more than once isn't hoisted, because it isn't known which value is called.
+pub fn hoisted_functions(statements: &[ast::Statement]) -> BTreeSet<String> {
+    let mut definitions = BTreeSet::new();
+    let mut bound = BTreeSet::new();
+    let mut rebound = BTreeSet::new();
+    for statement in statements.iter() {
+        if let ast::Statement::LetStatement{identifier: Expression::Identifier(name), value, ..} |
+               ast::Statement::ConstStatement{identifier: Expression::Identifier(name), value} = statement.without_comments() {
+            if !bound.insert(name.clone()) {
+                rebound.insert(name.clone());
+            }
+            if let Expression::FunctionLiteral{..} = value {
+                definitions.insert(name.clone());
+            }
+        }
+    }
+    definitions.into_iter().filter(|name| !rebound.contains(name)).collect()
+}
+
+// a value which an environment keeps but which isn't a part of its value, like the state of the evaluation
+// which is shared by the environments made from the same one. any two of them are equal,
+// so that comparing environments, and the functions which keep them, compares only the variables.
+#[derive(Debug, Clone, Default)]
+struct Shared<T>(T);
+
+impl<T> PartialEq for Shared<T> {
+    fn eq(&self, _: &Shared<T>) -> bool {
+        true
+    }
+}
+
+impl<T> Eq for Shared<T> {}
+
+impl<T> Ord for Shared<T> {
+    fn cmp(&self, _: &Shared<T>) -> Ordering {
+        Ordering::Equal
+    }
+}
+
+impl<T> PartialOrd for Shared<T> {
+    fn partial_cmp(&self, other: &Shared<T>) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+// the hoisted functions of a program by their names, which are shared by the functions made in it,
+// so that a function can call one which is bound after it. the callee is the function made at its own let,
+// so it runs in the scope where it is defined. a name has no function until its let is evaluated.
+type HoistedTable = RefCell<BTreeMap<String, Option<Object>>>;
+
+// the table keeps the functions, so they refer to it only weakly, and the environment which evaluates
+// the program owns it. otherwise the table and the functions would keep each other and never be freed.
+type Hoisted = Shared<Weak<HoistedTable>>;
+
+impl Hoisted {
+    fn get(&self, name: &str) -> Option<Object> {
+        self.0.upgrade()?.borrow().get(name).cloned().flatten()
+    }
+
+    // keep the function bound to the name, if the name is hoisted.
+    fn bind(&self, name: &str, function: &Object) {
+        if let Some(table) = self.0.upgrade() {
+            if let Some(bound) = table.borrow_mut().get_mut(name) {
+                *bound = Some(function.clone());
+            }
+        }
+    }
+}
+
+// the number of evaluated nodes.
+type Steps = Shared<Rc<Cell<usize>>>;
+
+// the files which are being imported, from the outermost one, to find circular imports.
+type Imports = Shared<Rc<RefCell<Vec<String>>>>;
+
+// the string literals which have been evaluated, so that evaluating the same literal again
+// shares the storage instead of allocating.
+type Strings = Shared<Rc<RefCell<BTreeSet<Rc<str>>>>>;
+
+impl Strings {
+    fn intern(&self, value: &str) -> Rc<str> {
+        if let Some(interned) = self.0.borrow().get(value) {
+            return interned.clone()
+        }
+        let interned: Rc<str> = Rc::from(value);
+        self.0.borrow_mut().insert(interned.clone());
+        interned
+    }
+}
+
+// the values of the invariant calls in the loops which are being evaluated, by the address of the call in the program.
+// nothing is known about a call until it is evaluated for the first time.
+type Memo = Shared<BTreeMap<usize, Option<Object>>>;
+
+fn address(expression: &ast::Expression) -> usize {
+    expression as *const ast::Expression as usize
+}
+
+// the identity of a function, which is made each time a function literal is evaluated
+// and is kept by the copies of the function, so that a self call is found without comparing the functions.
+type Identity = Shared<Option<Rc<()>>>;
+
+impl Identity {
+    fn new() -> Identity {
+        Shared(Some(Rc::new(())))
+    }
+}
+
+// whether both are copies of the function made by the same evaluation of a function literal.
+fn is_same_function(left: &Object, right: &Object) -> bool {
+    match (left, right) {
+        (Object::Function{env: left, ..}, Object::Function{env: right, ..}) => match (&left.identity.0, &right.identity.0) {
+            (Some(left), Some(right)) => Rc::ptr_eq(left, right),
+            _ => false
+        },
+        _ => false
+    }
 }
 
 impl Environment {
     pub fn new() -> Environment{
+        Environment::with_config(EvalConfig::default())
+    }
+
+    pub fn with_config(config: EvalConfig) -> Environment{
         let env = BTreeMap::new();
-        let builtins = builtins::new();
-        return Environment{store: env, outer: None, builtin: builtins}
+        let builtins = builtins::with_config(&config);
+        return Environment{store: env, constants: BTreeSet::new(), outer: None, builtin: Rc::new(builtins), config: config, steps: Steps::default(), recursive: Definitions::new(), hoisted: Hoisted::default(), hoisted_tables: Shared::default(), imports: Imports::default(), strings: Strings::default(), memo: Memo::default(), identity: Identity::default()}
+    }
+
+    // an environment where the host has bound the values, like a configuration for scripts.
+    pub fn from_pairs(pairs: impl IntoIterator<Item=(String, Object)>) -> Environment {
+        let mut env = Environment::new();
+        env.store.extend(pairs);
+        env
+    }
+
+    // save the variables, so that they can be restored after evaluating code which may change them.
+    pub fn snapshot(&self) -> Snapshot {
+        Snapshot{store: self.store.clone(), constants: self.constants.clone(), outer: self.outer.clone()}
+    }
+
+    // bring the variables back to the snapshot, and forget the ones bound after it.
+    pub fn restore(&mut self, snapshot: Snapshot) {
+        self.store = snapshot.store;
+        self.constants = snapshot.constants;
+        self.outer = snapshot.outer;
     }
 
     pub fn new_outer(self) -> Environment {
-        return Environment{store: BTreeMap::new(), outer: Some(Box::new(self.clone())), builtin: builtins::new()}
+        let mut scope = self.scope();
+        scope.outer = Some(Box::new(self));
+        scope
+    }
+
+    // an empty scope without outer scopes, which shares the builtins, the config and the state of the evaluation with this one.
+    // every scope except the first one is made from this.
+    fn scope(&self) -> Environment {
+        Environment{store: BTreeMap::new(), constants: BTreeSet::new(), outer: None, builtin: self.builtin.clone(), config: self.config.clone(),
+                    steps: self.steps.clone(), recursive: Definitions::new(), hoisted: Hoisted::default(), hoisted_tables: Shared::default(), imports: self.imports.clone(),
+                    strings: self.strings.clone(), memo: Memo::default(), identity: Identity::default()}
     }
 
     pub fn get(& self, name: &str) -> Option<Object> {
+        // a recursive function is made when it is referred, because it keeps this scope.
+        if let Some((params, body)) = self.recursive.get(name) {
+            return Some(Object::Function{params: params.clone(), body: body.clone(), env: self.clone()})
+        }
         match self.store.get(name) {
             Some(value) => Some(value.clone()),
             None => match &self.outer {
                 Some(outer) => outer.get(name),
                 None => None
             }
+        }.or_else(|| self.hoisted.get(name))
+    }
+
+    // look up the name from the scope which is depth scopes outer than this one.
+    pub fn get_at(&self, depth: usize, name: &str) -> Option<Object> {
+        let mut environment = self;
+        for _ in 0..depth {
+            match &environment.outer {
+                Some(outer) => environment = outer,
+                None => break
+            }
         }
+        environment.get(name)
     }
 
+    // let can bind the same name again in a scope, and the latest value shadows
+    // the previous one. only a name declared by const can't be bound again.
     pub fn set(&mut self, name: String, value: Object) -> Object {
+        if self.constants.contains(&name) {
+            return Object::Error(Errors::AlreadyDeclared(name))
+        }
+        self.store.insert(name, value.clone());
+        return value;
+    }
+
+    // rebind a name in the nearest scope which has it.
+    // a function has a copy of scopes where it is defined,
+    // so assigning inside a function doesn't change variables outside of it.
+    pub fn assign(&mut self, name: String, value: Object) -> Object {
+        if self.store.contains_key(&name) {
+            if self.constants.contains(&name) {
+                return Object::Error(Errors::AssignToConstant(name))
+            }
+            self.store.insert(name, value.clone());
+            return value;
+        }
+        match &mut self.outer {
+            Some(outer) => outer.assign(name, value),
+            None => Object::Error(Errors::NotDeclared(name))
+        }
+    }
+
+    // const binds a name only once per scope,
+    // so it fails if the name has already been bound in the same scope.
+    pub fn declare(&mut self, name: String, value: Object) -> Object {
+        if self.store.contains_key(&name) {
+            return Object::Error(Errors::AlreadyDeclared(name))
+        }
+        self.constants.insert(name.clone());
         self.store.insert(name, value.clone());
         return value;
     }
 
 
     pub fn evaluate(&mut self, program: &ast::Program) -> Result<Object, Errors> {
+        let resolved;
+        let program = if self.config.resolve_names {
+            resolved = resolver::resolve(program);
+            &resolved
+        } else {
+            program
+        };
+        self.steps.0.set(0);
+        if self.config.hoist_functions {
+            self.hoist(&program.statements);
+        }
+        let result = self.evaluate_statements(program);
+        self.hoisted = Hoisted::default();
+        result
+    }
+
+    // evaluate the program of the file at the path, so that a relative path imported by it
+    // is relative to the directory of the file, and importing the file from itself is circular.
+    pub fn evaluate_file(&mut self, program: &ast::Program, path: &str) -> Result<Object, Errors> {
+        let file = match fs::canonicalize(path) {
+            Ok(file) => file.to_string_lossy().into_owned(),
+            Err(_) => path.to_string()
+        };
+        self.imports.0.borrow_mut().push(file);
+        let result = self.evaluate(program);
+        self.imports.0.borrow_mut().pop();
+        result
+    }
+
+    // make the table of the functions of the statements which are hoisted, which this environment owns.
+    fn hoist(&mut self, statements: &[ast::Statement]) {
+        let names = hoisted_functions(statements);
+        if names.is_empty() {
+            self.hoisted = Hoisted::default();
+            return;
+        }
+        let table: Rc<HoistedTable> = Rc::new(RefCell::new(names.into_iter().map(|name| (name, None)).collect()));
+        self.hoisted = Shared(Rc::downgrade(&table));
+        self.hoisted_tables.0.push(table);
+    }
+
+    fn evaluate_statements(&mut self, program: &ast::Program) -> Result<Object, Errors> {
         let mut result = Object::Default;
         // evaluate sentence per semicolon.
         for statement in program.statements.iter() {
@@ -50,42 +574,241 @@ impl Environment {
             if let Object::Return(value) = result {
                 return Ok(*value)
             }
-            // if the result of evaluation, process should be broken.
-            if result == Object::Error(Errors::InvalidInfix){
+            if let Object::Break(_) = result {
+                return Ok(Object::Error(Errors::BreakOutsideLoop))
+            }
+            // if the result of evaluation is an error, process should be broken.
+            if let Object::Error(_) = result {
                 return Ok(result)
             }
         }
         Ok(result)
     }
 
+    // count the node and call the trace callback before evaluating it,
+    // and return the error if the evaluation should stop.
+    fn step(&self, node: TraceNode<'_>) -> Option<Object> {
+        if let Some(max) = self.config.max_steps {
+            let steps = self.steps.0.get() + 1;
+            self.steps.0.set(steps);
+            if steps > max {
+                return Some(Object::Error(Errors::StepLimitExceeded{max: max}))
+            }
+        }
+        match &self.config.trace {
+            Some(tracer) if tracer.signal(node) == TraceSignal::Abort => Some(Object::Error(Errors::Aborted)),
+            _ => None
+        }
+    }
+
     fn evaluate_statement(&mut self, statement: &ast::Statement) -> Result<Object, Errors> {
+        if let Some(error) = self.step(TraceNode::Statement(statement)) {
+            return Ok(error)
+        }
         match statement {
             ast::Statement::ExpressionStatement(expression) => self.evaluate_expression(expression),
             ast::Statement::Block(stmt) => self.evaluate_block_statements(stmt),
+            ast::Statement::Import{path, alias} => self.evaluate_import(path, alias),
+            ast::Statement::Commented{statement, ..} => self.evaluate_statement(statement),
             ast::Statement::Return(expression) => {
                                     let return_expression = self.evaluate_expression(expression)?;
+                                    if is_error(&return_expression) {
+                                        return Ok(return_expression)
+                                    }
                                     Ok(Object::Return(Box::new(return_expression)))
                                     },
-            ast::Statement::LetStatement{identifier ,value} => {
+            ast::Statement::Break(expression) => {
+                                    let break_value = match expression {
+                                        Some(expression) => self.evaluate_expression(expression)?,
+                                        None => Object::Null
+                                    };
+                                    if is_error(&break_value) {
+                                        return Ok(break_value)
+                                    }
+                                    Ok(Object::Break(Box::new(break_value)))
+                                    },
+            ast::Statement::LetStatement{identifier, annotation, value} => {
                                                  if let Expression::Identifier(identifier) = identifier {
                                                     // if expression is identifier, evaluate value, and 
                                                     // append identifier as variable.
-                                                    let evaluated_value = self.evaluate_expression(&value)?;
+                                                    let evaluated_value = self.evaluate_binding(identifier, &value)?;
+                                                    if let (Some(annotation), true) = (annotation, self.config.check_types) {
+                                                        if let Some(error) = check_annotation(identifier, annotation, &evaluated_value) {
+                                                            return Ok(Object::Error(error))
+                                                        }
+                                                    }
                                                     let value = self.set(identifier.to_owned(), evaluated_value);
                                                     return Ok(value)
                                                  }
                                                  Ok(Object::Null)
                                                 },
+            ast::Statement::Assign{target, value} => {
+                                                 let value = self.evaluate_expression(&value)?;
+                                                 if is_error(&value) {
+                                                     return Ok(value)
+                                                 }
+                                                 self.assign_target(target, value)
+                                                },
+            ast::Statement::ConstStatement{identifier ,value} => {
+                                                 if let Expression::Identifier(identifier) = identifier {
+                                                    let evaluated_value = self.evaluate_binding(identifier, &value)?;
+                                                    let value = self.declare(identifier.to_owned(), evaluated_value);
+                                                    return Ok(value)
+                                                 }
+                                                 Ok(Object::Null)
+                                                },
             _ => Err(Errors::NodeError),
             }
         }
 
+    // evaluate the file in this scope, so that the names bound at the top level of it can be used here.
+    // with an alias, the file is evaluated in a new scope, and the names are bound in a hash of the alias.
+    // the path is relative to the current directory, and the value of import is null.
+    fn evaluate_import(&mut self, path: &str, alias: &Option<String>) -> Result<Object, Errors> {
+        if self.config.sandbox {
+            return Ok(Object::Error(Errors::SandboxViolation(String::from("import"))))
+        }
+        // a relative path is relative to the directory of the file which imports it,
+        // or to the current directory if the program isn't a file, like in the REPL.
+        let relative = match self.imports.0.borrow().last() {
+            Some(importer) => Path::new(importer).parent().unwrap_or(Path::new("")).join(path),
+            None => Path::new(path).to_path_buf()
+        };
+        // the same file can be reached by different paths, so it is compared by the canonical path.
+        let file = match fs::canonicalize(relative) {
+            Ok(file) => file.to_string_lossy().into_owned(),
+            Err(_) => return Ok(Object::Error(Errors::FileNotReadable(path.to_string())))
+        };
+        if self.imports.0.borrow().contains(&file) {
+            return Ok(Object::Error(Errors::CircularImport(path.to_string())))
+        }
+        let input = match fs::read_to_string(&file) {
+            Ok(input) => input,
+            Err(_) => return Ok(Object::Error(Errors::FileNotReadable(path.to_string())))
+        };
+        let program = match parser::Parser::new_from_source(&input).parse_program() {
+            Ok(program) if self.config.resolve_names => resolver::resolve(&program),
+            Ok(program) => program,
+            Err(error) => return Ok(Object::Error(error))
+        };
+        self.imports.0.borrow_mut().push(file);
+        let result = match alias {
+            Some(alias) => self.evaluate_module(&program).map(|module| match module {
+                error @ Object::Error(_) => error,
+                module => self.set(alias.clone(), module)
+            }),
+            None => self.evaluate_statements(&program)
+        };
+        self.imports.0.borrow_mut().pop();
+        match result? {
+            error @ Object::Error(_) => Ok(error),
+            _ => Ok(Object::Null)
+        }
+    }
+
+    // evaluate the program in a new top level scope, and make a hash of the names bound in it.
+    fn evaluate_module(&mut self, program: &ast::Program) -> Result<Object, Errors> {
+        let mut module = self.scope();
+        if module.config.hoist_functions {
+            module.hoist(&program.statements);
+        }
+        let result = module.evaluate_statements(program)?;
+        // the functions of the file call the hoisted ones after the scope of the file is dropped.
+        self.hoisted_tables.0.append(&mut module.hoisted_tables.0);
+        if is_error(&result) {
+            return Ok(result)
+        }
+        let mut pairs = BTreeMap::new();
+        for (name, value) in module.store.into_iter() {
+            let key = Object::String(name.into());
+            pairs.insert(Box::new(HashKey::get_hashkey(&key)), Box::new(HashPair{key: key, value: value}));
+        }
+        Ok(Object::Hash(pairs))
+    }
+
+    // a value inside an array or a hash is replaced by making a new one,
+    // and the new array is assigned to its target again, like a[0][1] = 2 assigns a new a[0] into a.
+    fn assign_target(&mut self, target: &Expression, value: Object) -> Result<Object, Errors> {
+        match target {
+            Expression::Identifier(name) | Expression::ResolvedIdentifier{name, ..} => Ok(self.assign(name.to_string(), value)),
+            Expression::IndexExpression{array, subscript} => {
+                // an unknown identifier is evaluated into null, so it is checked here.
+                if let Expression::Identifier(name) | Expression::ResolvedIdentifier{name, ..} = &**array {
+                    if self.get(name).is_none() {
+                        return Ok(Object::Error(Errors::NotDeclared(name.to_string())))
+                    }
+                }
+                let container = self.evaluate_expression(array)?;
+                if is_error(&container) {
+                    return Ok(container)
+                }
+                let index = self.evaluate_expression(subscript)?;
+                if is_error(&index) {
+                    return Ok(index)
+                }
+                let container = match replace_index(container, index, value.clone()) {
+                    error @ Object::Error(_) => return Ok(error),
+                    container => container
+                };
+                match self.assign_target(array, container)? {
+                    error @ Object::Error(_) => Ok(error),
+                    _ => Ok(value)
+                }
+            },
+            _ => Err(Errors::NodeError)
+        }
+    }
+
+    // a function which keeps a copy of this scope.
+    fn function_literal(&self, parameters: &[Expression], body: Rc<ast::Statement>, recursive: Definitions) -> Object {
+        let mut env = self.scope();
+        env.store = self.store.clone();
+        env.constants = self.constants.clone();
+        env.outer = self.outer.clone();
+        env.recursive = recursive;
+        env.hoisted = self.hoisted.clone();
+        env.identity = Identity::new();
+        Object::Function{params: parameters.to_vec(), body: body, env: env}
+    }
+
+    // the value of let or const. a function literal bound to a name can refer to itself by the name,
+    // even though the scope which it keeps is copied before the name is bound.
+    fn evaluate_binding(&mut self, name: &str, value: &Expression) -> Result<Object, Errors> {
+        match value {
+            Expression::FunctionLiteral{parameters, body} => {
+                if let Some(error) = self.step(TraceNode::Expression(value)) {
+                    return Ok(error)
+                }
+                let body = Rc::new((**body).clone());
+                let mut recursive = Definitions::new();
+                recursive.insert(name.to_string(), (parameters.clone(), body.clone()));
+                let function = self.function_literal(parameters, body, recursive);
+                self.hoisted.bind(name, &function);
+                Ok(function)
+            },
+            _ => self.evaluate_expression(value)
+        }
+    }
+
+    fn bound_or_builtin(&self, name: &str, bound: Option<Object>) -> Object {
+        match bound {
+            Some(value) => value,
+            None => match self.builtin.get(name) {
+                Some(value) => value.to_owned(),
+                None => Object::Null
+            }
+        }
+    }
+
+    // a block has the value of its last statement, and an empty block is null.
+    // the bodies of if, do and functions are evaluated as blocks in the same way.
     fn evaluate_block_statements(&mut self, statements: &Vec<ast::Statement>) -> Result<Object, Errors> {
-        let mut result = Object::Default;
+        let mut result = Object::Null;
         for statement in statements.iter() {
             result = self.evaluate_statement(statement)?;
-            // if 'return' is in nested block, the value should be returned.
-            if let Object::Return(_) = result {
+            // if 'return' or 'break' is in nested block, the value should be returned.
+            // an error also stops the rest of the block.
+            if let Object::Return(_) | Object::Break(_) | Object::Error(_) = result {
                 return Ok(result);
             }
         }
@@ -93,137 +816,387 @@ impl Environment {
     }
 
     fn evaluate_expression(&mut self, expression: &ast::Expression) -> Result<Object, Errors> {
+        if let Some(error) = self.step(TraceNode::Expression(expression)) {
+            return Ok(error)
+        }
+        if let Some(Some(value)) = self.memo.0.get(&address(expression)) {
+            return Ok(value.clone())
+        }
         match expression {
             ast::Expression::Identifier(value) => {
                 // if a key exists in Environment map,
                 // get value which is equivalent to it.
-                match self.get(value) {
-                    Some(value) => {
-                        Ok(value)},
-                    _ => {
-                        match self.builtin.get(value) {
-                            Some(value) => {
-                                Ok(value.to_owned())
-                            },
-                            None => {
-                                Ok(Object::Null)}
-                        }
-                        }
-                    }
+                let bound = self.get(value);
+                Ok(self.bound_or_builtin(value, bound))
+                },
+            ast::Expression::ResolvedIdentifier{name, depth} => {
+                let bound = self.get_at(*depth, name);
+                Ok(self.bound_or_builtin(name, bound))
                 },
-            ast::Expression::String(value) => Ok(Object::String(value.to_owned())),
+            ast::Expression::String(value) => Ok(Object::String(self.strings.intern(value))),
             ast::Expression::Integer(value) => Ok(Object::Integer(*value)),
+            ast::Expression::Float(value) => Ok(Object::Float(*value)),
             ast::Expression::Bool(bool) => Ok(Object::Boolean(*bool)),
             ast::Expression::Array(value) =>{
                 let array = self.evaluate_arguments(value.to_vec())?;
+                if let Some(error) = array.iter().find(|element| is_error(element)) {
+                    return Ok(error.clone())
+                }
                 Ok(Object::Array(array))
             },
             ast::Expression::IndexExpression{array, subscript} => {
                                                         let array = self.evaluate_expression(array)?;
+                                                        if is_error(&array) {
+                                                            return Ok(array)
+                                                        }
                                                         let index = self.evaluate_expression(subscript)?;
+                                                        if is_error(&index) {
+                                                            return Ok(index)
+                                                        }
                                                         Ok(evaluate_index_expression(array, index))
                                                         },
+            ast::Expression::OptionalIndexExpression{array, subscript} => {
+                let array = self.evaluate_expression(array)?;
+                if is_error(&array) || array == Object::Null {
+                    return Ok(array)
+                }
+                let index = self.evaluate_expression(subscript)?;
+                if is_error(&index) {
+                    return Ok(index)
+                }
+                Ok(evaluate_index_expression(array, index))
+            },
             ast::Expression::Hashmap(value) => {
                 let mut pairs = BTreeMap::new();
                 for (key, value) in value {
                     let mut key = self.evaluate_expression(key)?;
+                    if is_error(&key) {
+                        return Ok(key)
+                    }
                     let hash_key = match HashKey::get_hashkey(&key) {
                         key => key,
                         _ => HashKey::Null
                     };
                     let mut value = self.evaluate_expression(value)?;
+                    if is_error(&value) {
+                        return Ok(value)
+                    }
                     pairs.insert(Box::new(hash_key), Box::new(HashPair{key: key.to_owned(), value: value}));
                 }               
                 Ok(Object::Hash(pairs))
             }
             ast::Expression::PrefixExpression{operator, right_expression} => {
-                let right = self.evaluate_expression(&right_expression);
-                evaluate_prefix_expression(operator, right.unwrap())
+                let right = self.evaluate_expression(&right_expression)?;
+                if is_error(&right) {
+                    return Ok(right)
+                }
+                evaluate_prefix_expression(operator, right)
             },
             ast::Expression::InfixExpression{left_expression, operator, right_expression} => {
                 // if there are more than two calculations, left expression should be a calculation.
                 // it is firstly evaluated, and then the result and right_expression are calculated.
                 // for example, the whole sentence is 1 + 2 + 5. firstly, 1 + 2 is evaluated and
                 // the result is 3. After that the result and 5 is evaluated.
-                let left = self.evaluate_expression(&left_expression);
-                let right = self.evaluate_expression(&right_expression);
-                evaluate_infix_expression(left.unwrap(), operator, right.unwrap())
+                let left = self.evaluate_expression(&left_expression)?;
+                if is_error(&left) {
+                    return Ok(left)
+                }
+                // the right of ?? is evaluated only when the left is null.
+                if operator == "??" && left != Object::Null {
+                    return Ok(left)
+                }
+                let right = self.evaluate_expression(&right_expression)?;
+                if is_error(&right) {
+                    return Ok(right)
+                }
+                evaluate_infix_expression(left, operator, right, &self.config)
             },
             ast::Expression::IfExpression{condition, consequence, alternative} => {
-                let condition = self.evaluate_expression(&condition);
-                if is_truthy(condition?) {
+                let condition = self.evaluate_expression(&condition)?;
+                if is_error(&condition) {
+                    return Ok(condition)
+                }
+                if is_truthy(condition) {
                     self.evaluate_statement(consequence)
                 } else {
                     match alternative {
                         Some(alternative) => self.evaluate_statement(alternative),
+                        // an if used only for side effects has no value when it isn't taken.
                         None => Ok(Object::Null)
                     }
                 }
             },
-            ast::Expression::FunctionLiteral{parameters, body} => {
-                let obj = Object::Function{params: parameters.clone(),
-                                           body: *body.clone(),
-                                           env: Environment{store: self.store.clone(), outer:None, builtin: builtins::new()}
-                                          };
-                Ok(obj)
+            // a let in the block binds the name in the current scope, like in if.
+            ast::Expression::DoExpression{body} => self.evaluate_statement(body),
+            ast::Expression::WhileExpression{condition, body} => {
+                let memoized = if self.config.memoize_invariants {
+                    self.memoize(condition, body)
+                } else {
+                    vec![]
+                };
+                let result = self.evaluate_while(condition, body);
+                for key in memoized.iter() {
+                    self.memo.0.remove(key);
+                }
+                result
             },
+            ast::Expression::FunctionLiteral{parameters, body} => Ok(self.function_literal(parameters, Rc::new((**body).clone()), Definitions::new())),
             ast::Expression::CallExpression{function, body} => {
-                match self.evaluate_expression(function) {
-                    Ok(value) =>{
-                        let func = self.evaluate_expression(function)?;
-                        let args = self.evaluate_arguments(body.to_vec())?;
-                        let res = apply_function(func, args);
-                        return res
-                    },
-                    Err(_) => return Ok(Object::Null)
+                let func = self.evaluate_expression(function)?;
+                if is_error(&func) {
+                    return Ok(func)
                 }
-
-                Ok(Object::Null)
+                let args = self.evaluate_arguments(body.to_vec())?;
+                if let Some(error) = args.iter().find(|arg| is_error(arg)) {
+                    return Ok(error.clone())
+                }
+                let value = call(func, args, function, expression)?;
+                if let Some(memoized) = self.memo.0.get_mut(&address(expression)) {
+                    if !is_error(&value) {
+                        *memoized = Some(value.clone());
+                    }
+                }
+                Ok(value)
             },
             _ =>  Err(Errors::NodeError)
         }
     }
 
-    fn evaluate_arguments(&mut self, expressions: Vec<Expression>) -> Result<Vec<Object>, Errors> {
-        let mut results: Vec<Object> = Vec::new();
-        for expression in expressions.iter() {
-            match self.evaluate_expression(expression) {
-                Ok(value) => {
-                    results.push(value)
-                             },
-                Err(_) => return Ok(results)
+    fn evaluate_while(&mut self, condition: &ast::Expression, body: &ast::Statement) -> Result<Object, Errors> {
+        // a loop is an expression, whose value is the value of the body
+        // in the last iteration, or a value given by break.
+        let mut result = Object::Null;
+        loop {
+            let condition = self.evaluate_expression(condition)?;
+            if is_error(&condition) {
+                return Ok(condition)
+            }
+            if !is_truthy(condition) {
+                return Ok(result)
+            }
+            match self.evaluate_statement(body)? {
+                Object::Break(value) => return Ok(*value),
+                value @ Object::Return(_) | value @ Object::Error(_) => return Ok(value),
+                value => result = value
             }
         }
-        Ok(results)    
     }
-}
 
-fn apply_function(func: Object, args: Vec<Object>) -> Result<Object, Errors> {
-    match func {
-        Object::Function{params, body, env} => {
-            // the value of parameter is inserted in outer when function is called.
-            let mut outer = env.new_outer();
-            for (i, param) in params.iter().enumerate() {
-                if let Expression::Identifier(param) = param {
-                    outer.set(param.to_string(), args[i].clone());
-                }
-            }
-            match outer.evaluate_statement(&body)? {
-                Object::Return(expression) => {
-                    return Ok(*expression)
-                },
-                other_expression => return Ok(other_expression)
+    // start remembering the invariant calls of a loop, and return the ones which a loop outside it doesn't remember,
+    // so that they are forgotten when the loop ends.
+    fn memoize(&mut self, condition: &ast::Expression, body: &ast::Statement) -> Vec<usize> {
+        let calls = invariant::invariant_calls(condition, body, &|name| self.is_pure_builtin(name));
+        let mut memoized = vec![];
+        for call in calls.into_iter() {
+            let key = address(call);
+            if let Entry::Vacant(entry) = self.memo.0.entry(key) {
+                entry.insert(None);
+                memoized.push(key);
             }
-            Ok(Object::Null)
-        }
-        Object::Builtin{func} => {
-            Ok(func(args))
+        }
+        memoized
+    }
+
+    // a name bound to a builtin which neither acts on the outside nor calls functions.
+    // builtins which a host binds by its own name are assumed to be pure.
+    fn is_pure_builtin(&self, name: &str) -> bool {
+        let value = self.get(name);
+        match self.bound_or_builtin(name, value) {
+            Object::Builtin{..} => !builtins::SIDE_EFFECTS.contains(&name) && !builtins::CALLS_FUNCTIONS.contains(&name),
+            _ => false
+        }
+    }
+
+    // evaluate a statement in tail position of the body of current_function.
+    // only the last statement of a block, branches of if, and return are in tail position.
+    fn evaluate_tail_statement(&mut self, statement: &ast::Statement, current_function: &Object) -> Result<Tail, Errors> {
+        // the other statements are traced in evaluate_statement.
+        if let ast::Statement::ExpressionStatement(_) | ast::Statement::Return(_) | ast::Statement::Block(_) = statement {
+            if let Some(error) = self.step(TraceNode::Statement(statement)) {
+                return Ok(Tail::Value(error))
+            }
+        }
+        match statement {
+            ast::Statement::ExpressionStatement(expression) => self.evaluate_tail_expression(expression, current_function),
+            ast::Statement::Return(expression) => {
+                match self.evaluate_tail_expression(expression, current_function)? {
+                    Tail::Value(value) if is_error(&value) => Ok(Tail::Value(value)),
+                    Tail::Value(value) => Ok(Tail::Value(Object::Return(Box::new(value)))),
+                    self_call => Ok(self_call)
+                }
+            },
+            ast::Statement::Block(statements) => {
+                let mut result = Object::Null;
+                for (i, statement) in statements.iter().enumerate() {
+                    if i == statements.len() - 1 {
+                        return self.evaluate_tail_statement(statement, current_function)
+                    }
+                    result = self.evaluate_statement(statement)?;
+                    if let Object::Return(_) | Object::Break(_) | Object::Error(_) = result {
+                        return Ok(Tail::Value(result));
+                    }
+                }
+                Ok(Tail::Value(result))
+            },
+            _ => Ok(Tail::Value(self.evaluate_statement(statement)?))
+        }
+    }
+
+    fn evaluate_tail_expression(&mut self, expression: &ast::Expression, current_function: &Object) -> Result<Tail, Errors> {
+        // the other expressions are traced in evaluate_expression.
+        if let ast::Expression::IfExpression{..} | ast::Expression::CallExpression{..} | ast::Expression::DoExpression{..} = expression {
+            if let Some(error) = self.step(TraceNode::Expression(expression)) {
+                return Ok(Tail::Value(error))
+            }
+        }
+        match expression {
+            ast::Expression::IfExpression{condition, consequence, alternative} => {
+                let condition = self.evaluate_expression(&condition)?;
+                if is_error(&condition) {
+                    return Ok(Tail::Value(condition))
+                }
+                if is_truthy(condition) {
+                    self.evaluate_tail_statement(consequence, current_function)
+                } else {
+                    match alternative {
+                        Some(alternative) => self.evaluate_tail_statement(alternative, current_function),
+                        None => Ok(Tail::Value(Object::Null))
+                    }
+                }
+            },
+            ast::Expression::DoExpression{body} => self.evaluate_tail_statement(body, current_function),
+            ast::Expression::CallExpression{function, body} => {
+                let func = self.evaluate_expression(function)?;
+                if is_error(&func) {
+                    return Ok(Tail::Value(func))
+                }
+                let args = self.evaluate_arguments(body.to_vec())?;
+                if let Some(error) = args.iter().find(|arg| is_error(arg)) {
+                    return Ok(Tail::Value(error.clone()))
+                }
+                if is_same_function(&func, current_function) {
+                    return Ok(Tail::SelfCall(args))
+                }
+                Ok(Tail::Value(call(func, args, function, expression)?))
+            },
+            _ => Ok(Tail::Value(self.evaluate_expression(expression)?))
+        }
+    }
+
+    fn evaluate_arguments(&mut self, expressions: Vec<Expression>) -> Result<Vec<Object>, Errors> {
+        let mut results: Vec<Object> = Vec::new();
+        for expression in expressions.iter() {
+            match self.evaluate_expression(expression) {
+                Ok(value) => {
+                    results.push(value)
+                             },
+                Err(_) => return Ok(results)
+            }
+        }
+        Ok(results)    
+    }
+}
+
+// call a function at the call site, which is shown in the backtrace if an error happens.
+fn call(func: Object, args: Vec<Object>, function: &Expression, call: &Expression) -> Result<Object, Errors> {
+    let is_user_function = matches!(func, Object::Function{..});
+    match apply_function(func, args)? {
+        // when an error happens inside a function, the call site is appended,
+        // so that the error shows the chain of calls which reached it.
+        Object::Error(error) if is_user_function => Ok(Object::Error(push_frame(error, function, call))),
+        result => Ok(result)
+    }
+}
+
+pub fn apply_function(func: Object, args: Vec<Object>) -> Result<Object, Errors> {
+    match func {
+        Object::Function{..} => apply_user_function(&func, args),
+        Object::Builtin{func} => {
+            Ok(func(args))
+        }
+        Object::ReadLine{input} => Ok(builtins::read_line(&input, args)),
+        Object::Rand{rng, ranged} => Ok(builtins::rand(&rng, ranged, args)),
+        Object::Composed{outer, inner} => {
+            let value = apply_function(*inner, args)?;
+            if is_error(&value) {
+                return Ok(value)
+            }
+            apply_function(*outer, vec![value])
+        }
+        Object::Partial{function, arity, args: mut applied} => {
+            applied.extend(args);
+            if applied.len() < arity {
+                return Ok(Object::Partial{function, arity, args: applied})
+            }
+            apply_function(*function, applied)
         }
         _ => {
             Ok(Object::Null)}
     }
 }
 
+// self calls in tail position are repeated in the loop instead of recursion.
+// they are shown only once in the backtrace because no call site is kept for them.
+fn apply_user_function(function: &Object, mut args: Vec<Object>) -> Result<Object, Errors> {
+    let (params, body, env) = match function {
+        Object::Function{params, body, env} => (params, body, env),
+        _ => unreachable!()
+    };
+    let _depth = env.config.trace.as_ref().map(|tracer| tracer.enter());
+    loop {
+        // self calls in tail position are checked again, since they come back with new arguments.
+        if args.len() != params.len() {
+            return Ok(Object::Error(Errors::WrongArgCount{expected: params.len(),
+                                                          got: args.len(),
+                                                          function: signature(params)
+                                                         }))
+        }
+        // the value of parameter is inserted in outer when function is called.
+        let mut outer = env.clone().new_outer();
+        for (i, param) in params.iter().enumerate() {
+            if let Expression::Identifier(param) = param {
+                outer.set(param.to_string(), args[i].clone());
+            }
+        }
+        let result = if outer.config.tail_calls {
+            outer.evaluate_tail_statement(body, function)?
+        } else {
+            Tail::Value(outer.evaluate_statement(body)?)
+        };
+        match result {
+            Tail::SelfCall(next_args) => args = next_args,
+            Tail::Value(Object::Return(expression)) => return Ok(*expression),
+            Tail::Value(Object::Break(_)) => return Ok(Object::Error(Errors::BreakOutsideLoop)),
+            Tail::Value(other_expression) => return Ok(other_expression)
+        }
+    }
+}
+
+// the signature like "fn(a, b)" which is shown in the error of the number of arguments.
+fn signature(params: &[Expression]) -> String {
+    format!("fn({})", params.iter().map(|param| format!("{}", param)).collect::<Vec<_>>().join(", "))
+}
+
+fn is_error(object: &Object) -> bool {
+    matches!(object, Object::Error(_))
+}
+
+// append a frame like "add: add(1, 2)" into the backtrace of the error.
+fn push_frame(error: Errors, function: &Expression, call: &Expression) -> Errors {
+    let name = match function {
+        Expression::Identifier(name) | Expression::ResolvedIdentifier{name, ..} => name.to_string(),
+        _ => String::from("<anonymous>")
+    };
+    let frame = format!("{}: {}", name, format!("{}", call).trim_end_matches(';'));
+    match error {
+        Errors::Backtrace{error, mut frames} => {
+            frames.push(frame);
+            Errors::Backtrace{error, frames}
+        },
+        error => Errors::Backtrace{error: Box::new(error), frames: vec![frame]}
+    }
+}
+
 fn evaluate_index_expression(left: Object, index: Object) -> Object {
     match left {
         Object::Array(left) => {
@@ -246,80 +1219,295 @@ fn evaluate_index_expression(left: Object, index: Object) -> Object {
             }
             left[&hash_key].value.clone()
         }
+        Object::String(left) => {
+            if let Object::Integer(index) = index {
+                evaluate_string_index_expression(&left, index)
+            } else {
+                Object::Error(Errors::StringIndexTypeError(Box::new(index)))
+            }
+        }
         _ => Object::Null
     }
 }
 
 
+// a new array or hash where the value at the index is replaced.
+fn replace_index(container: Object, index: Object, value: Object) -> Object {
+    match (container, index) {
+        (Object::Array(mut array), Object::Integer(index)) => {
+            if index < 0 || index as usize >= array.len() {
+                return Object::Error(Errors::IndexOutOfRange{index: index, length: array.len()})
+            }
+            array[index as usize] = value;
+            Object::Array(array)
+        },
+        (Object::Array(_), index) => Object::Error(Errors::ArrayIndexTypeError(Box::new(index))),
+        // a key which doesn't exist is inserted.
+        (Object::Hash(mut tree), key) => {
+            match HashKey::get_hashkey(&key) {
+                HashKey::Null => Object::Error(Errors::HashKeyTypeError(Box::new(key))),
+                hash_key => {
+                    tree.insert(Box::new(hash_key), Box::new(HashPair{key: key, value: value}));
+                    Object::Hash(tree)
+                }
+            }
+        },
+        (container, _) => Object::Error(Errors::IndexAssignTypeError(Box::new(container)))
+    }
+}
+
 fn evaluate_array_index_expression(array: Vec<Object>, index: i32) -> Object {
     let max = array.len() as i32;
-    if index < 0 || index > max {
+    if index < 0 || index >= max {
         return Object::Null
     } else {
         array[index as usize].clone()
     }
 }
 
+// a string is indexed per character, so the result is a string of one character.
+fn evaluate_string_index_expression(string: &str, index: i32) -> Object {
+    if index < 0 {
+        return Object::Null
+    }
+    match string.chars().nth(index as usize) {
+        Some(ch) => Object::String(ch.to_string().into()),
+        None => Object::Null
+    }
+}
+
 fn evaluate_prefix_expression(operator: &str, right: Object) -> Result<Object, Errors> {
     match operator {
         "!" => evaluate_bang_operation_expression(right),
         "-" => evaluate_minus_prefix_operator_expression(right),
+        "+" => evaluate_plus_prefix_operator_expression(right),
         _ => Ok(Object::Error(Errors::InvalidOperator(operator.to_string())))
     }
 }
 
+// !x is the negation of the truthiness of x, so !null is true and !5 is false.
 fn evaluate_bang_operation_expression(right: Object) -> Result<Object, Errors> {
+    Ok(Object::Boolean(!is_truthy(right)))
+}
+
+fn evaluate_minus_prefix_operator_expression(right: Object) -> Result<Object, Errors> {
     match right {
-        Object::Boolean(true) => Ok(Object::Boolean(false)),
-        Object::Boolean(false) => Ok(Object::Boolean(true)),
-        Object::Boolean(Null) => Ok(Object::Boolean(true)),
-        _ => Ok(Object::Boolean(false))
+        Object::Integer(value) => match value.checked_neg() {
+            Some(value) => Ok(Object::Integer(value)),
+            None => Ok(Object::Error(Errors::IntegerOverflow))
+        },
+        Object::Float(value) => Ok(Object::Float(ast::Float(-value.0))),
+        _ => Ok(unknown_prefix_operator("-", &right))
     }
 }
 
-fn evaluate_minus_prefix_operator_expression(right: Object) -> Result<Object, Errors> {
+// +x is x itself, but only for a number like -x.
+fn evaluate_plus_prefix_operator_expression(right: Object) -> Result<Object, Errors> {
     match right {
-        Object::Integer(value) => Ok(Object::Integer(-value)),
-        _ =>Ok(Object::Error(Errors::InvalidInteger(Box::new(right))))
+        Object::Integer(_) | Object::Float(_) => Ok(right),
+        _ => Ok(unknown_prefix_operator("+", &right))
     }
 }
 
-fn evaluate_infix_expression(left: Object, operator: &str, right: Object) -> Result<Object, Errors> {
+fn unknown_prefix_operator(operator: &str, right: &Object) -> Object {
+    Object::Error(Errors::UnknownOperator{operator: operator.to_string(), right: right.type_name().to_string()})
+}
+
+fn evaluate_infix_expression(left: Object, operator: &str, right: Object, config: &EvalConfig) -> Result<Object, Errors> {
+    let (left, right) = if config.bool_arithmetic && is_arithmetic(operator) && is_number_or_boolean(&left) && is_number_or_boolean(&right) {
+        (boolean_to_integer(left), boolean_to_integer(right))
+    } else {
+        (left, right)
+    };
     match (left, right) {
-        (Object::Integer(left),Object::Integer(right)) => {
-            match operator {
-                "+" => Ok(Object::Integer(left + right)),
-                "-" => Ok(Object::Integer(left - right)),
-                "*" => Ok(Object::Integer(left * right)),
-                "/" => Ok(Object::Integer(left / right)),
-                "<" => Ok(Object::Boolean(left < right)),
-                ">" => Ok(Object::Boolean(left > right)),
-                "==" => Ok(Object::Boolean(left == right)),
-                "!=" => Ok(Object::Boolean(left != right)),
-                _ => Ok(Object::Error(Errors::InvalidOperator(operator.to_string())))
-            }
-        },
+        // the left is null here, because it is returned without evaluating the right otherwise.
+        (_, right) if operator == "??" => Ok(right),
+        (left, right) if is_number(&left) && is_number(&right) => numeric_infix(left, operator, right),
         (Object::Boolean(left), Object::Boolean(right)) => {
             match operator {
                 "==" => Ok(Object::Boolean(left == right)),
                 "!=" => Ok(Object::Boolean(left != right)),
+                operator if is_arithmetic(operator) => Ok(Object::Error(Errors::TypeMismatch{left: String::from("BOOLEAN"),
+                                                                                           operator: operator.to_string(),
+                                                                                           right: String::from("BOOLEAN")})),
                 _ => Ok(Object::Error(Errors::InvalidOperator(operator.to_string())))
             }
         },
+        // values of different types are never equal like 5 == "5" is false,
+        // while they still can't be ordered by < or >.
+        (left, right) if operator == "==" => Ok(Object::Boolean(left == right)),
+        (left, right) if operator == "!=" => Ok(Object::Boolean(left != right)),
         (Object::String(left), Object::String(right)) => {
             if operator != "+" {
                 Ok(Object::Null)
             } else {
                 let concatenated = format!("{}{}", left, right);
-                Ok(Object::String(concatenated))                
+                Ok(Object::String(concatenated.into()))
             }
         },
-        _ => {
-            Ok(Object::Error(Errors::InvalidInfix))}
+        (Object::String(left), Object::Integer(right)) if operator == "*" => repeat_string(&left, right, config),
+        (Object::Integer(left), Object::String(right)) if operator == "*" => repeat_string(&right, left, config),
+        (Object::Array(left), Object::Array(right)) if operator == "+" => {
+            let length = left.len().saturating_add(right.len());
+            if length > config.max_repeat_length {
+                return Ok(Object::Error(Errors::ArrayTooLarge{length: length, max: config.max_repeat_length}))
+            }
+            let mut concatenated = left.clone();
+            concatenated.extend(right);
+            Ok(Object::Array(concatenated))
+        },
+        (Object::Array(left), Object::Integer(right)) if operator == "*" => repeat_array(&left, right, config),
+        (Object::Integer(left), Object::Array(right)) if operator == "*" => repeat_array(&right, left, config),
+        (left, right) => {
+            Ok(Object::Error(Errors::TypeMismatch{left: left.type_name().to_string(),
+                                                  operator: operator.to_string(),
+                                                  right: right.type_name().to_string()}))}
     }
 }
 
-fn is_truthy(object: Object) -> bool {
+fn is_number(object: &Object) -> bool {
+    matches!(object, Object::Integer(_) | Object::Float(_))
+}
+
+fn is_number_or_boolean(object: &Object) -> bool {
+    match object {
+        Object::Boolean(_) => true,
+        object => is_number(object)
+    }
+}
+
+pub fn is_arithmetic(operator: &str) -> bool {
+    matches!(operator, "+" | "-" | "*" | "/" | "//")
+}
+
+fn boolean_to_integer(object: Object) -> Object {
+    match object {
+        Object::Boolean(value) => Object::Integer(value as i32),
+        object => object
+    }
+}
+
+// arithmetic and comparison between numbers.
+// integer op integer stays integer, so `/` truncates toward zero like 5 / 2 == 2.
+// if either of them is a float, the other is promoted to a float like 2 + 3.0 == 5.0,
+// and comparisons promote in the same way like 1 == 1.0.
+// `//` floors toward negative infinity and always makes an integer like -7 // 2 == -4.
+// dividing by zero is an error for both integers and floats,
+// and an integer result which doesn't fit in 32 bits is an overflow error.
+fn numeric_infix(left: Object, operator: &str, right: Object) -> Result<Object, Errors> {
+    if (operator == "/" || operator == "//") && to_float(&right) == 0.0 {
+        return Ok(Object::Error(Errors::DivisionByZero))
+    }
+    match (left, right) {
+        (Object::Integer(left), Object::Integer(right)) => {
+            match operator {
+                "+" => Ok(checked_integer(left.checked_add(right))),
+                "-" => Ok(checked_integer(left.checked_sub(right))),
+                "*" => Ok(checked_integer(left.checked_mul(right))),
+                "/" => Ok(checked_integer(left.checked_div(right))),
+                "//" => Ok(checked_integer(left.checked_div(right).map(|quotient| {
+                    if left % right != 0 && (left < 0) != (right < 0) {
+                        quotient - 1
+                    } else {
+                        quotient
+                    }
+                }))),
+                "<" => Ok(Object::Boolean(left < right)),
+                ">" => Ok(Object::Boolean(left > right)),
+                "==" => Ok(Object::Boolean(left == right)),
+                "!=" => Ok(Object::Boolean(left != right)),
+                _ => Ok(Object::Error(Errors::InvalidOperator(operator.to_string())))
+            }
+        },
+        (left, right) => {
+            let (left, right) = (to_float(&left), to_float(&right));
+            match operator {
+                "+" => Ok(Object::Float(ast::Float(left + right))),
+                "-" => Ok(Object::Float(ast::Float(left - right))),
+                "*" => Ok(Object::Float(ast::Float(left * right))),
+                "/" => Ok(Object::Float(ast::Float(left / right))),
+                "//" => {
+                    let quotient = (left / right).floor();
+                    if quotient.is_finite() && quotient >= i32::MIN as f64 && quotient <= i32::MAX as f64 {
+                        Ok(Object::Integer(quotient as i32))
+                    } else {
+                        Ok(Object::Error(Errors::IntegerOverflow))
+                    }
+                },
+                "<" => Ok(Object::Boolean(left < right)),
+                ">" => Ok(Object::Boolean(left > right)),
+                "==" => Ok(Object::Boolean(left == right)),
+                "!=" => Ok(Object::Boolean(left != right)),
+                _ => Ok(Object::Error(Errors::InvalidOperator(operator.to_string())))
+            }
+        }
+    }
+}
+
+fn checked_integer(value: Option<i32>) -> Object {
+    match value {
+        Some(value) => Object::Integer(value),
+        None => Object::Error(Errors::IntegerOverflow)
+    }
+}
+
+fn to_float(object: &Object) -> f64 {
+    match object {
+        Object::Integer(value) => *value as f64,
+        Object::Float(value) => value.0,
+        _ => unreachable!()
+    }
+}
+
+// the error if a value bound by let doesn't have the type of the annotation.
+// an error value is left to be returned as it is.
+fn check_annotation(name: &str, annotation: &str, value: &Object) -> Option<Errors> {
+    let types: &[&str] = match annotation {
+        "Int" => &["INTEGER"],
+        "Float" => &["FLOAT"],
+        "String" => &["STRING"],
+        "Bool" => &["BOOLEAN"],
+        "Array" => &["ARRAY"],
+        "Hash" => &["HASH"],
+        "Fn" => &["FUNCTION", "BUILTIN"],
+        "Null" => &["NULL"],
+        _ => return Some(Errors::UnknownType(annotation.to_string()))
+    };
+    if is_error(value) || types.contains(&value.type_name()) {
+        return None
+    }
+    Some(Errors::AnnotationMismatch{name: name.to_string(), annotation: annotation.to_string(), got: value.type_name().to_string()})
+}
+
+// repeat a string count times. zero or negative count makes an empty string.
+fn repeat_string(value: &str, count: i32, config: &EvalConfig) -> Result<Object, Errors> {
+    if count <= 0 {
+        return Ok(Object::String("".into()))
+    }
+    let length = value.len().saturating_mul(count as usize);
+    if length > config.max_repeat_length {
+        return Ok(Object::Error(Errors::StringTooLarge{length: length, max: config.max_repeat_length}))
+    }
+    Ok(Object::String(value.repeat(count as usize).into()))
+}
+
+// repeat elements of an array count times in the same way as a string.
+fn repeat_array(value: &Vec<Object>, count: i32, config: &EvalConfig) -> Result<Object, Errors> {
+    if count <= 0 {
+        return Ok(Object::Array(Vec::new()))
+    }
+    let length = value.len().saturating_mul(count as usize);
+    if length > config.max_repeat_length {
+        return Ok(Object::Error(Errors::ArrayTooLarge{length: length, max: config.max_repeat_length}))
+    }
+    let mut array = Vec::with_capacity(length);
+    for _ in 0..count {
+        array.extend(value.iter().cloned());
+    }
+    Ok(Object::Array(array))
+}
+
+pub fn is_truthy(object: Object) -> bool {
     match object {
         Object::Null => false,
         Object::Boolean(true) => true,
@@ -331,24 +1519,37 @@ fn is_truthy(object: Object) -> bool {
 #[cfg(test)]// test runs only when execute cargo run
 mod testing {
     use crate::lexer::Lexer;
-    use crate::evaluator::Environment;
+    use crate::evaluator::{Environment, EvalConfig, Input, Tracer, TraceNode, TraceSignal};
     use crate::token::TokenKind;
     use crate::ast::Statement::Block;
     use crate::ast::Statement;
     use crate::ast::Expression;
     use crate::parser::Parser;
     use crate::evaluator;
+    use crate::resolver;
     use crate::object::Object;
+    use crate::errors::Errors;
     use std::str::FromStr;
+    use std::io::Cursor;
+    use std::rc::Rc;
+    use std::env;
+    use std::fs;
+    use std::cell::{Cell, RefCell};
 
     fn test_evaluate(input: &str) -> Object {
-        let l = Lexer::new(input);
-        let mut p = Parser::new(l);
+        let mut p = Parser::new_from_source(input);
         let program = p.parse_program();
         let mut environment = Environment::new();
         environment.evaluate(&program.unwrap()).unwrap()
     }
 
+    fn test_evaluate_with_config(input: &str, config: EvalConfig) -> Object {
+        let mut p = Parser::new_from_source(input);
+        let program = p.parse_program();
+        let mut environment = Environment::with_config(config);
+        environment.evaluate(&program.unwrap()).unwrap()
+    }
+
     #[test]
     fn test_eval_integer_expression() {
         let tests = vec![
@@ -356,6 +1557,9 @@ mod testing {
                         ("10", 10),
                         ("-5", -5),
                         ("-10", -10),
+                        ("+5", 5),
+                        ("-+5", -5),
+                        ("5 + +5", 10),
                         ("5 + 5 + 5 + 5 - 10", 10),
                         ("2 * 2 * 2 * 2 * 2", 32),
                         ("-50 + 100 + -50", 0),
@@ -375,6 +1579,68 @@ mod testing {
         }
     }
 
+    #[test]
+    fn test_eval_float_expression() {
+        let tests = vec![
+                        ("1.5", "1.5"),
+                        ("1e3", "1000.0"),
+                        ("1e3 == 1000.0", "true"),
+                        ("2.5e-2", "0.025"),
+                        ("-1E6", "-1000000.0"),
+                        ("0.5 + 0.25", "0.75"),
+                        ("1.5 * 2.0 - 1e0", "2.0"),
+                        ("1.0 / 4.0", "0.25"),
+                        ("0.1 < 0.2", "true"),
+                        ];
+        for (input, expected) in tests.into_iter() {
+            assert_eq!(format!("{}", test_evaluate(input)), expected);
+        }
+    }
+
+    #[test]
+    fn test_numeric_promotion() {
+        let tests = vec![
+                        ("5 / 2", "2"),
+                        ("-5 / 2", "-2"),
+                        ("5.0 / 2", "2.5"),
+                        ("5 / 2.0", "2.5"),
+                        ("2 + 3.0", "5.0"),
+                        ("2.5 * 2", "5.0"),
+                        ("10 - 0.5", "9.5"),
+                        ("1 < 1.5", "true"),
+                        ("2 > 1.5", "true"),
+                        ("1 == 1.0", "true"),
+                        ("1 != 1.0", "false"),
+                        ("7 // 2", "3"),
+                        ("-7 // 2", "-4"),
+                        ("7 // -2", "-4"),
+                        ("-7 // -2", "3"),
+                        ("6 // 3", "2"),
+                        ("-6 // 3", "-2"),
+                        ("7.5 // 2", "3"),
+                        ("-7.5 // 2", "-4"),
+                        ("1 + 7 // 2 * 2", "7"),
+                        ("7 // 0", "division by zero"),
+                        ("7.0 // 0", "division by zero"),
+                        ("5 / 0", "division by zero"),
+                        ("5.0 / 0", "division by zero"),
+                        ("5 / 0.0", "division by zero"),
+                        ("1.5 + true", "type mismatch: FLOAT + BOOLEAN"),
+                        ("2147483647 + 1", "integer overflow"),
+                        ("-2147483647 - 2", "integer overflow"),
+                        ("65536 * 65536", "integer overflow"),
+                        ("(-2147483647 - 1) / -1", "integer overflow"),
+                        ("2147483646 + 1", "2147483647"),
+                        ("1e300 // 1.0", "integer overflow"),
+                        ("-1e300 // 1.0", "integer overflow"),
+                        ("(-2147483647 - 1) // -1", "integer overflow"),
+                        ("-2147483648.0 // 1", "-2147483648"),
+                        ];
+        for (input, expected) in tests.into_iter() {
+            assert_eq!(format!("{}", test_evaluate(input)), expected, "{}", input);
+        }
+    }
+
     #[test]
     fn test_eval_boolean_expression() {
         let tests = vec![
@@ -397,6 +1663,14 @@ mod testing {
                         ("(1 < 2) == false", false),
                         ("(1 > 2) == true", false),
                         ("(1 > 2) == false", true),
+                        ("5 == \"5\"", false),
+                        ("5 != \"5\"", true),
+                        ("[1] == 1", false),
+                        // null is made by if without else.
+                        ("let n = if (false) {1}; n == n", true),
+                        ("let n = if (false) {1}; n == false", false),
+                        ("\"a\" == \"a\"", true),
+                        ("[1, 2] != [1, 2]", false),
                         ];
         for test in tests.iter() {
             let evaluated = test_evaluate(test.0);
@@ -414,6 +1688,10 @@ mod testing {
                         ("!!true", true),
                         ("!!false", false),
                         ("!!5", true),
+                        ("!0", false),
+                        ("!\"\"", false),
+                        ("!if (false) {1}", true),
+                        ("!!if (false) {1}", false),
                         ];
         for test in tests.iter() {
             let evaluated = test_evaluate(test.0);
@@ -440,6 +1718,22 @@ mod testing {
         }
     }
 
+    #[test]
+    fn test_if_without_else() {
+        let tests = vec![
+                        ("if (false) {10}", Object::Null),
+                        ("let x = 1; if (x > 1) {x}", Object::Null),
+                        ("if (true) {10}", Object::Integer(10)),
+                        ("if (true) {print(\"\")}", Object::Null),
+                        // the value of if isn't used when it is followed by other statements.
+                        ("if (true) {10}; 20", Object::Integer(20)),
+                        ("if (false) {10}; 20", Object::Integer(20)),
+                        ];
+        for (input, expected) in tests.into_iter() {
+            assert_eq!(test_evaluate(input), expected, "{}", input);
+        }
+    }
+
     #[test]
     fn test_return_statement() {
         let tests = vec![
@@ -461,21 +1755,142 @@ mod testing {
         }
     }
 
+    #[test]
+    fn test_do_expressions() {
+        let tests = vec![
+                        ("let x = do { let y = 2; y * 3 }; x", "6"),
+                        ("do { 1; 2 } + 1", "3"),
+                        ("let f = fn() { let x = do { return 5; 1 }; x + 1 }; f()", "5"),
+                        ("do { 1 + true; 2 }", "type mismatch: INTEGER + BOOLEAN"),
+                        ("do { let y = 1; y + 1; y * 5 }", "5"),
+                        ];
+        for test in tests.iter() {
+            let evaluated = test_evaluate(test.0);
+            let return_value = format!("{}", evaluated);
+            assert_eq!(return_value, test.1);
+        }
+    }
+
+    #[test]
+    fn test_empty_blocks() {
+        let tests = vec![
+                        "do {}",
+                        "if (true) {}",
+                        "if (false) {1} else {}",
+                        "fn() {}()",
+                        "let f = fn() {}; f()",
+                        ];
+        for input in tests.into_iter() {
+            assert_eq!(test_evaluate(input), Object::Null, "{}", input);
+        }
+        let config = EvalConfig{tail_calls: false, ..EvalConfig::default()};
+        assert_eq!(test_evaluate_with_config("let f = fn() {}; f()", config), Object::Null);
+    }
+
+    #[test]
+    fn test_optional_chain() {
+        let tests = vec![
+                        // null is made by if without else, and is shown as empty.
+                        ("let n = if (false) {1}; n?.[0]", ""),
+                        ("[1, 2]?.[0]", "1"),
+                        ("{\"a\": {\"b\": 1}}?.a?.b", "1"),
+                        ("{\"a\": 1}?.b?.c", ""),
+                        // the index isn't evaluated when the left is null.
+                        ("let n = if (false) {1}; n?.[1 + true]", ""),
+                        ("[1]?.[1 + true]", "type mismatch: INTEGER + BOOLEAN"),
+                        ];
+        for test in tests.iter() {
+            let evaluated = test_evaluate(test.0);
+            let return_value = format!("{}", evaluated);
+            assert_eq!(return_value, test.1, "{}", test.0);
+        }
+    }
+
+    #[test]
+    fn test_null_coalesce() {
+        let tests = vec![
+                        // null is made by if without else.
+                        ("let n = if (false) {1}; n ?? 5", "5"),
+                        ("3 ?? 5", "3"),
+                        ("false ?? 5", "false"),
+                        ("let n = if (false) {1}; n ?? n ?? 7", "7"),
+                        ("{\"a\": 1}?.b ?? 0", "0"),
+                        // the right isn't evaluated when the left isn't null.
+                        ("3 ?? 1 + true", "3"),
+                        ("let n = if (false) {1}; n ?? 1 + true", "type mismatch: INTEGER + BOOLEAN"),
+                        ];
+        for test in tests.iter() {
+            let evaluated = test_evaluate(test.0);
+            let return_value = format!("{}", evaluated);
+            assert_eq!(return_value, test.1, "{}", test.0);
+        }
+    }
+
+    #[test]
+    fn test_bool_arithmetic() {
+        let tests = vec![
+                        ("true + true", "type mismatch: BOOLEAN + BOOLEAN", "2"),
+                        ("true * 3", "type mismatch: BOOLEAN * INTEGER", "3"),
+                        ("10 - false", "type mismatch: INTEGER - BOOLEAN", "10"),
+                        ("true / 2.0", "type mismatch: BOOLEAN / FLOAT", "0.5"),
+                        // comparison and other types aren't changed.
+                        ("true == 1", "false", "false"),
+                        ("true > false", "invalid operator: >", "invalid operator: >"),
+                        ("true + \"a\"", "type mismatch: BOOLEAN + STRING", "type mismatch: BOOLEAN + STRING"),
+                        ];
+        for (input, disabled, enabled) in tests.into_iter() {
+            assert_eq!(format!("{}", test_evaluate(input)), disabled, "{}", input);
+            let config = EvalConfig{bool_arithmetic: true, ..EvalConfig::default()};
+            assert_eq!(format!("{}", test_evaluate_with_config(input, config)), enabled, "{}", input);
+        }
+    }
+
     #[test]
     fn test_error_handling() {
         let tests = vec![
-                        ("5 + true", "invalid_infix"),
-                        ("5 + true; 5;", "invalid_infix"),
-                        ("-true", "invalid integer: true"),
-                        ("true + false;", "invalid operator: +"),
-                        ("5; true + false;", "invalid operator: +"),
-                        ("if (10 > 1) {true + false;}", "invalid operator: +"),
+                        ("5 + true", "type mismatch: INTEGER + BOOLEAN"),
+                        ("5 + true; 5;", "type mismatch: INTEGER + BOOLEAN"),
+                        ("5 < \"x\"", "type mismatch: INTEGER < STRING"),
+                        ("5 < \"5\"", "type mismatch: INTEGER < STRING"),
+                        ("[1] > 1", "type mismatch: ARRAY > INTEGER"),
+                        ("true * 2", "type mismatch: BOOLEAN * INTEGER"),
+                        ("-true", "unknown operator: -BOOLEAN"),
+                        ("-\"x\"", "unknown operator: -STRING"),
+                        ("-[1]", "unknown operator: -ARRAY"),
+                        ("+\"x\"", "unknown operator: +STRING"),
+                        ("+true", "unknown operator: +BOOLEAN"),
+                        ("-(-2147483647 - 1)", "integer overflow"),
+                        ("true + false;", "type mismatch: BOOLEAN + BOOLEAN"),
+                        ("5; true + false;", "type mismatch: BOOLEAN + BOOLEAN"),
+                        ("if (10 > 1) {true + false;}", "type mismatch: BOOLEAN + BOOLEAN"),
+                        ("true > false", "invalid operator: >"),
                         ("if (10 > 1){
                             if (10 > 1) {
                                return true + false;
                                         }
                                 return 1;
-                                     }", "invalid operator: +")
+                                     }", "type mismatch: BOOLEAN + BOOLEAN")
+                        ];
+        for test in tests.iter() {
+            let evaluated = test_evaluate(test.0);
+            let return_value = format!("{}", evaluated);
+            assert_eq!(return_value, test.1);
+        }
+    }
+
+    #[test]
+    fn test_while_expressions() {
+        let tests = vec![
+                        ("let i = 0; while (i < 3) {let i = i + 1; i * 10}", "30"),
+                        ("let i = 0; while (i < 3) {let i = i + 1;}; i", "3"),
+                        ("let x = while (true) {break 42;}; x", "42"),
+                        ("let i = 0; while (true) {let i = i + 1; if (i == 5) {break i * 10;}}", "50"),
+                        ("while (true) {break;}", ""),
+                        ("while (false) {1}", ""),
+                        ("let f = fn() {while (true) {return 7;}; 8}; f()", "7"),
+                        ("while (true) {1 + true}", "type mismatch: INTEGER + BOOLEAN"),
+                        ("break 1;", "break outside loop"),
+                        ("let f = fn() {break;}; f()", "break outside loop\n  at f: f()"),
                         ];
         for test in tests.iter() {
             let evaluated = test_evaluate(test.0);
@@ -491,6 +1906,50 @@ mod testing {
                         ("let a = 5 * 5; a;", "25"),
                         ("let a = 5 ; let b = a; b;", "5"),
                         ("let a = 5 ; let b = a; let c = a + b + 5; c;", "15"), 
+                        ("let x = 1; let x = 2; x", "2"),
+                        ("let x = 1; let x = x + 1; x", "2"),
+                        ];
+        for test in tests.iter() {
+            let evaluated = test_evaluate(test.0);
+            let return_value = format!("{}", evaluated);
+            assert_eq!(return_value, test.1);
+        }
+    }
+
+    #[test]
+    fn test_check_types() {
+        let config = EvalConfig{check_types: true, ..EvalConfig::default()};
+        let tests = vec![
+            ("let x: Int = 5; x", "5"),
+            ("let s: String = \"a\" + \"b\"; s", "ab"),
+            ("let f: Fn = fn(x) { x }; f(1)", "1"),
+            ("let g: Fn = len; g([1])", "1"),
+            ("let a: Array = [1]; let h: Hash = {}; let b: Bool = true; let n: Null = if (false) { 1 }; b", "true"),
+            ("let x: Int = \"s\"", "'x' is annotated as Int, got STRING"),
+            ("let x: Float = 1", "'x' is annotated as Float, got INTEGER"),
+            ("let x: Number = 1", "unknown type 'Number'"),
+            // an error of the value is returned as it is.
+            ("let x: Int = 1 + true", "type mismatch: INTEGER + BOOLEAN"),
+            ("let f = fn() { let y: Bool = 1; y }; f()", "'y' is annotated as Bool, got INTEGER\n  at f: f()"),
+            ];
+        for (input, expected) in tests.into_iter() {
+            let evaluated = test_evaluate_with_config(input, config.clone());
+            assert_eq!(format!("{}", evaluated), expected, "{}", input);
+        }
+        // annotations are ignored unless they are checked.
+        assert_eq!(format!("{}", test_evaluate("let x: Int = \"s\"; x")), "s");
+        assert_eq!(format!("{}", test_evaluate("let x: Number = 1; x")), "1");
+    }
+
+    #[test]
+    fn test_const_statements() {
+        let tests = vec![
+                        ("const a = 5; a;", "5"),
+                        ("const a = 5; let b = a * 2; b;", "10"),
+                        ("const x = 1; const x = 2; x", "identifier 'x' has already been declared"),
+                        ("const x = 1; let x = 2; x", "identifier 'x' has already been declared"),
+                        ("let x = 1; const x = 2; x", "identifier 'x' has already been declared"),
+                        ("const x = 1; let f = fn() {let x = 2; x}; f()", "2"),
                         ];
         for test in tests.iter() {
             let evaluated = test_evaluate(test.0);
@@ -508,6 +1967,28 @@ mod testing {
                 ("let add = fn(x, y) {x + y;}; add(5, 5);", "10"),
                 ("let add = fn(x, y) {x + y;}; add(5 + 5, add(5, 5));", "20"),
                 ("fn(x) {x;}(5)", "5"),
+                ("let f = fn() {1}; f()", "1"),
+                ("let f = fn(a, b) {}; f(1)", "wrong number of arguments to fn(a, b): expected=2, got=1\n  at f: f(1)"),
+                ("let f = fn(a, b) {}; f(1, 2, 3)", "wrong number of arguments to fn(a, b): expected=2, got=3\n  at f: f(1, 2, 3)"),
+                ("let f = fn() {}; f(1)", "wrong number of arguments to fn(): expected=0, got=1\n  at f: f(1)"),
+                    ];
+        for test in tests.iter() {
+            let evaluated = test_evaluate(test.0);
+            let return_value = format!("{}", evaluated);
+            assert_eq!(return_value, test.1);
+        }
+    }
+
+    #[test]
+    fn test_backtrace() {
+        let tests = vec![
+                ("let inner = fn(x) {x + true}; let outer = fn(y) {inner(y * 2)}; outer(1)",
+                 "type mismatch: INTEGER + BOOLEAN\n  at inner: inner(y * 2)\n  at outer: outer(1)"),
+                ("let inner = fn(x) {return -true; x}; let outer = fn(y) {let z = inner(y); z + 1}; outer(1) + 2",
+                 "unknown operator: -BOOLEAN\n  at inner: inner(y)\n  at outer: outer(1)"),
+                ("fn(x) {assert(x)}(false)",
+                 "assertion failed\n  at <anonymous>: fn (x) {assert(x);}(false)"),
+                ("let f = fn(x) {x}; len(f(1))", "argument to len not supported got 1"),
                     ];
         for test in tests.iter() {
             let evaluated = test_evaluate(test.0);
@@ -516,6 +1997,79 @@ mod testing {
         }
     }
 
+    #[test]
+    fn test_tail_calls() {
+        // the function is given as an argument, which is the same as calling it by the name.
+        let countdown = "let countdown = fn(self, n) { if (n == 0) { return 0; } self(self, n - 1) };";
+        let sum = "let sum = fn(self, n, total) { if (n == 0) { total } else { return self(self, n - 1, total + n); } };";
+        let tests = vec![
+                (format!("{} countdown(countdown, 100000)", countdown), "0"),
+                (format!("{} sum(sum, 10000, 0)", sum), "50005000"),
+                (format!("{} sum(sum, 10, 0)", sum), "55"),
+                // a call which isn't in tail position is evaluated as usual.
+                (String::from("let f = fn(self, n) { if (n == 0) { 0 } else { 1 + self(self, n - 1) } }; f(f, 10)"), "10"),
+                (String::from("let f = fn(self, n) { if (n == 0) { 1 + true } else { self(self, n - 1) } }; f(f, 3)"),
+                 "type mismatch: INTEGER + BOOLEAN\n  at f: f(f, 3)"),
+                // functions made by the same literal are different functions, even if their scopes are the same.
+                (String::from("let make = fn() { fn(n, other) { if (n == 0) { 1 + true } else { other(n - 1, other) } } }; let a = make(); let b = make(); a(1, b)"),
+                 "type mismatch: INTEGER + BOOLEAN\n  at other: other(n - 1, other)\n  at a: a(1, b)"),
+                    ];
+        for (input, expected) in tests.iter() {
+            assert_eq!(format!("{}", test_evaluate(input)), *expected, "{}", input);
+        }
+
+        let config = EvalConfig{tail_calls: false, ..EvalConfig::default()};
+        let evaluated = test_evaluate_with_config(&format!("{} sum(sum, 10, 0)", sum), config);
+        assert_eq!(format!("{}", evaluated), "55");
+    }
+
+    #[test]
+    fn test_pipe_expressions() {
+        let tests = vec![
+                        ("let inc = fn(x) { x + 1 }; let double = fn(x) { x * 2 }; 1 |> inc |> double", "4"),
+                        ("let sub = fn(x, y) { x - y }; 10 |> sub(3)", "7"),
+                        ("[3, 1, 2] |> sort |> first", "1"),
+                        ("\"abc\" |> len", "3"),
+                        ];
+        for (input, expected) in tests.into_iter() {
+            assert_eq!(format!("{}", test_evaluate(input)), expected, "{}", input);
+        }
+    }
+
+    #[test]
+    fn test_hoisted_functions() {
+        let even_odd = "let is_even = fn(n) { if (n == 0) { true } else { is_odd(n - 1) } };
+                        let is_odd = fn(n) { if (n == 0) { false } else { is_even(n - 1) } };";
+        let tests = vec![
+                (format!("{} is_even(10)", even_odd), "true"),
+                (format!("{} is_even(7)", even_odd), "false"),
+                (format!("{} is_odd(7)", even_odd), "true"),
+                (String::from("let f = fn() { g() }; let g = fn() { 2 }; f()"), "2"),
+                // the callee sees the variables bound before its own let, not before the caller.
+                (String::from("let f = fn() { g() }; let x = 5; let g = fn() { x }; f()"), "5"),
+                (String::from("let f = fn() { g() }; let x = 5; let g = fn() { x }; g()"), "5"),
+                // a name bound twice isn't hoisted.
+                (String::from("let f = fn() { g() }; let g = fn() { 2 }; let g = 3; f()"), ""),
+                    ];
+        for (input, expected) in tests.iter() {
+            assert_eq!(format!("{}", test_evaluate(input)), *expected, "{}", input);
+        }
+        let config = EvalConfig{hoist_functions: false, ..EvalConfig::default()};
+        assert_eq!(test_evaluate_with_config(&format!("{} is_even(10)", even_odd), config), Object::Null);
+
+        // only the environment keeps the table of the functions, so they are freed with the environment.
+        let mut env = Environment::new();
+        let program = Parser::new_from_source(&format!("{} is_even(10)", even_odd)).parse_program().unwrap();
+        assert_eq!(env.evaluate(&program), Ok(Object::Boolean(true)));
+        assert_eq!(env.hoisted_tables.0.len(), 1);
+        assert_eq!(Rc::strong_count(&env.hoisted_tables.0[0]), 1);
+        let table = Rc::downgrade(&env.hoisted_tables.0[0]);
+        let is_even = env.get("is_even").unwrap();
+        drop(env);
+        assert!(table.upgrade().is_none());
+        drop(is_even);
+    }
+
     #[test]
     fn test_closures() {
         let input = "let new_adder = fn(x) {fn(y) {x + y};}; 
@@ -526,6 +2080,29 @@ mod testing {
         assert_eq!(return_value.parse::<i32>().unwrap(), 4);
         }
 
+    #[test]
+    fn test_recursive_functions() {
+        let fact = "let fact = fn(n) { if (n < 2) { 1 } else { n * fact(n - 1) } };";
+        let tests = vec![
+                (format!("{} fact(5)", fact), "120"),
+                (String::from("const fact = fn(n) { if (n < 2) { 1 } else { n * fact(n - 1) } }; fact(5)"), "120"),
+                // the name refers to the function itself even if it was bound to another value before.
+                (String::from("let f = 1; let f = fn(n) { if (n == 0) { 0 } else { f(n - 1) } }; f(3)"), "0"),
+                // a self call by the name is a tail call.
+                (String::from("let countdown = fn(n) { if (n == 0) { 0 } else { countdown(n - 1) } }; countdown(1000)"), "0"),
+                // a closure made inside the function can call it.
+                (String::from("let f = fn(n) { let g = fn() { f(n - 1) }; if (n == 0) { 0 } else { g() } }; f(3)"), "0"),
+                (String::from("let f = fn(f) { f }; f(2)"), "2"),
+                (String::from("fn fact(n) { if (n < 2) { 1 } else { n * fact(n - 1) } } fact(5)"), "120"),
+                (String::from("fn is_even(n) { if (n == 0) { true } else { is_odd(n - 1) } } fn is_odd(n) { if (n == 0) { false } else { is_even(n - 1) } } is_odd(5)"), "true"),
+                    ];
+        for (input, expected) in tests.iter() {
+            assert_eq!(format!("{}", test_evaluate(input)), *expected, "{}", input);
+            let resolved = resolver::resolve(&Parser::new_from_source(input).parse_program().unwrap());
+            assert_eq!(format!("{}", Environment::new().evaluate(&resolved).unwrap()), *expected, "{}", input);
+        }
+    }
+
     #[test]
     fn test_string() {
         let input = r#""Hello world;""#;
@@ -541,12 +2118,430 @@ mod testing {
         assert_eq!(return_value, "Hello world;");
         }
 
+    #[test]
+    fn test_interned_strings() {
+        let shared = |left: &Object, right: &Object| match (left, right) {
+            (Object::String(left), Object::String(right)) => std::rc::Rc::ptr_eq(left, right),
+            _ => panic!("expected strings, got {} and {}", left, right),
+        };
+        // the same literal evaluated again shares the storage, even in another function.
+        let evaluated = test_evaluate("let tag = fn() { \"tag\" }; [\"tag\", tag(), \"ta\" + \"g\", \"t\" * 1 + \"ag\"]");
+        let values = match evaluated {
+            Object::Array(values) => values,
+            evaluated => panic!("expected an array, got {}", evaluated),
+        };
+        assert!(shared(&values[0], &values[1]));
+        assert!(!shared(&values[0], &values[2]));
+        // interned and made strings are equal by their contents.
+        assert_eq!(values[0], values[2]);
+        assert_eq!(values[2], values[3]);
+        assert_eq!(values[0], Object::String("tag".into()));
+        assert_ne!(values[0], Object::String("tags".into()));
+        let tests = vec![
+            ("\"tag\" == \"ta\" + \"g\"", "true"),
+            ("let h = {\"tag\": 1}; h[\"ta\" + \"g\"]", "1"),
+            ("\"tag\" != \"tag\"", "false"),
+            ];
+        for (input, expected) in tests.into_iter() {
+            assert_eq!(format!("{}", test_evaluate(input)), expected, "{}", input);
+        }
+
+        // a literal evaluated in every iteration of a loop is allocated only once.
+        let evaluated = test_evaluate("let i = 0; let tags = []; while (i < 100) { let tags = push(tags, \"tag\"); let i = i + 1; } tags");
+        let values = match evaluated {
+            Object::Array(values) => values,
+            evaluated => panic!("expected an array, got {}", evaluated),
+        };
+        assert_eq!(values.len(), 100);
+        assert!(values.iter().all(|value| shared(&values[0], value)));
+    }
+
+    // run with `cargo test -- --ignored --nocapture` to compare interning a literal with allocating it every time.
+    #[test]
+    #[ignore]
+    fn bench_interned_strings() {
+        let literal = "a literal which a loop evaluates in every iteration";
+        let strings = evaluator::Strings::default();
+        // the fastest of some runs, so that a pause of the machine doesn't decide the result.
+        let time = |make: &dyn Fn() -> Rc<str>| (0..5).map(|_| {
+            let start = std::time::Instant::now();
+            let values: Vec<Rc<str>> = (0..100000).map(|_| make()).collect();
+            let elapsed = start.elapsed();
+            assert_eq!(values.len(), 100000);
+            elapsed
+        }).min().unwrap();
+        let allocated = time(&|| Rc::from(literal));
+        let interned = time(&|| strings.intern(literal));
+        println!("allocated: {:?}, interned: {:?}", allocated, interned);
+        assert!(interned < allocated, "interned: {:?}, allocated: {:?}", interned, allocated);
+    }
+
+    #[test]
+    fn test_string_repetition() {
+        let tests = vec![
+            ("\"ab\" * 3", "ababab"),
+            ("3 * \"ab\"", "ababab"),
+            ("\"x\" * 0", ""),
+            ("\"x\" * -2", ""),
+            ("len(\"ab\" * 2 + \"c\")", "5"),
+            ("\"x\" * 2000000", "string too large: length=2000000, max=1000000"),
+            ];
+        for test in tests.iter() {
+            let evaluated = test_evaluate(test.0);
+            let return_value = format!("{}", evaluated);
+            assert_eq!(return_value, test.1);
+        }
+
+        let config = EvalConfig{max_repeat_length: 4, ..EvalConfig::default()};
+        let evaluated = test_evaluate_with_config("\"ab\" * 2", config.clone());
+        assert_eq!(format!("{}", evaluated), "abab");
+        let evaluated = test_evaluate_with_config("\"ab\" * 3", config);
+        assert_eq!(format!("{}", evaluated), "string too large: length=6, max=4");
+    }
+
+    #[test]
+    fn test_env() {
+        std::env::set_var("MONKEY_TEST_ENV", "monkey");
+        let config = EvalConfig{allow_env: true, ..EvalConfig::default()};
+        let tests = vec![
+            ("env(\"MONKEY_TEST_ENV\")", "monkey"),
+            ("env(\"MONKEY_TEST_ENV\") + \"!\"", "monkey!"),
+            ("env(\"MONKEY_TEST_UNSET_ENV\")", ""),
+            ("let f = fn() { env(\"MONKEY_TEST_ENV\") }; f()", "monkey"),
+            ("env(1)", "argument to 'env' must be string, got 1"),
+            ];
+        for (input, expected) in tests.into_iter() {
+            let evaluated = test_evaluate_with_config(input, config.clone());
+            assert_eq!(format!("{}", evaluated), expected, "{}", input);
+        }
+        assert_eq!(test_evaluate_with_config("env(\"MONKEY_TEST_UNSET_ENV\")", config), Object::Null);
+
+        // env is disabled by default.
+        let tests = vec!["env(\"MONKEY_TEST_ENV\")", "let f = fn() { env(\"MONKEY_TEST_ENV\") }; f()"];
+        for input in tests.into_iter() {
+            let evaluated = test_evaluate(input);
+            assert!(format!("{}", evaluated).starts_with("'env' is disabled, set allow_env in EvalConfig to enable it"), "{}", input);
+        }
+    }
+
+    #[test]
+    fn test_now() {
+        match test_evaluate("now()") {
+            Object::Float(crate::ast::Float(millis)) => assert!(millis > 0.0, "{}", millis),
+            evaluated => panic!("now() returned {}", evaluated),
+        }
+        assert_eq!(format!("{}", test_evaluate("now(1)")), "wrong number of arguments. got=1, want=0");
+
+        let config = EvalConfig{allow_time: false, ..EvalConfig::default()};
+        let evaluated = test_evaluate_with_config("now()", config);
+        assert!(format!("{}", evaluated).starts_with("'now' is not allowed in sandbox"), "{}", evaluated);
+    }
+
+    #[test]
+    fn test_rand() {
+        let input = "[rand(), rand(), rand_range(1, 7), rand_range(-3, 0), rand()]";
+        let config = EvalConfig{seed: 42, ..EvalConfig::default()};
+        let first = test_evaluate_with_config(input, config.clone());
+        assert_eq!(first, test_evaluate_with_config(input, config));
+        assert_ne!(first, test_evaluate_with_config(input, EvalConfig{seed: 43, ..EvalConfig::default()}));
+        match first {
+            Object::Array(values) => {
+                assert_ne!(values[0], values[1]);
+                assert!(matches!(values[2], Object::Integer(1..=6)), "{}", values[2]);
+                assert!(matches!(values[3], Object::Integer(-3..=-1)), "{}", values[3]);
+                assert!(matches!(values[4], Object::Integer(0..=i32::MAX)), "{}", values[4]);
+            },
+            evaluated => panic!("expected an array, got {}", evaluated),
+        }
+
+        let tests = vec![
+            ("rand_range(5, 6)", "5"),
+            ("rand_range(-2147483647 - 1, 2147483647) < 2147483647", "true"),
+            ("rand_range(2, 2)", "empty range: low=2, high=2"),
+            ("rand_range(1, \"a\")", "arguments to 'rand_range' must be integer, got a"),
+            ("rand(1)", "wrong number of arguments. got=1, want=0"),
+            ("[arity(rand), arity(rand_range)]", "[0, 2]"),
+            ];
+        for (input, expected) in tests.into_iter() {
+            let evaluated = test_evaluate(input);
+            assert_eq!(format!("{}", evaluated), expected, "{}", input);
+        }
+    }
+
+    #[test]
+    fn test_read_line() {
+        let input = Input::new(Cursor::new("first\nsecond\r\nlast"));
+        let config = EvalConfig{input: input, ..EvalConfig::default()};
+        let tests = vec![
+            ("read_line()", "first"),
+            ("let f = fn() { read_line() }; f() + \"!\"", "second!"),
+            ("read_line()", "last"),
+            ("read_line()", ""),
+            ("read_line(1)", "wrong number of arguments. got=1, want=0"),
+            ];
+        for (input, expected) in tests.into_iter() {
+            let evaluated = test_evaluate_with_config(input, config.clone());
+            assert_eq!(format!("{}", evaluated), expected, "{}", input);
+        }
+        assert_eq!(test_evaluate_with_config("read_line()", config.clone()), Object::Null);
+
+        let config = EvalConfig{sandbox: true, ..config};
+        assert_eq!(format!("{}", test_evaluate_with_config("read_line()", config)), "'read_line' is not allowed in sandbox");
+    }
+
+    #[test]
+    fn test_trace() {
+        let count = |input: &str, tail_calls: bool| {
+            let nodes = Rc::new(RefCell::new(Vec::new()));
+            let recorded = nodes.clone();
+            let trace = Tracer::new(move |node, depth| {
+                let kind = match node {
+                    TraceNode::Statement(_) => "statement",
+                    TraceNode::Expression(_) => "expression",
+                };
+                recorded.borrow_mut().push((kind, depth));
+                TraceSignal::Continue
+            });
+            let config = EvalConfig{trace: Some(trace), tail_calls: tail_calls, ..EvalConfig::default()};
+            test_evaluate_with_config(input, config);
+            let nodes = nodes.borrow().clone();
+            nodes
+        };
+        // let, 1 + 2, 1, 2, the expression statement and x.
+        let nodes = count("let x = 1 + 2; x", true);
+        assert_eq!(nodes.len(), 6);
+        assert_eq!(nodes.iter().filter(|(kind, _)| *kind == "statement").count(), 2);
+        // the body of f is evaluated in depth 1.
+        for tail_calls in vec![true, false].into_iter() {
+            let nodes = count("let f = fn(x) { x }; f(1)", tail_calls);
+            assert_eq!(nodes.len(), 9);
+            assert_eq!(nodes.iter().filter(|(_, depth)| *depth == 1).count(), 3);
+            assert_eq!(nodes.last(), Some(&("expression", 1)));
+        }
+
+        let steps = Rc::new(Cell::new(0));
+        let counted = steps.clone();
+        let trace = Tracer::new(move |_, _| {
+            counted.set(counted.get() + 1);
+            if counted.get() >= 3 { TraceSignal::Abort } else { TraceSignal::Continue }
+        });
+        let config = EvalConfig{trace: Some(trace), ..EvalConfig::default()};
+        assert_eq!(test_evaluate_with_config("let x = 1; let y = 2; x + y", config), Object::Error(Errors::Aborted));
+        assert_eq!(steps.get(), 3);
+    }
+
+    #[test]
+    fn test_from_pairs_and_snapshot() {
+        let evaluate = |env: &mut Environment, input: &str| {
+            let program = Parser::new_from_source(input).parse_program().unwrap();
+            env.evaluate(&program).unwrap()
+        };
+        let mut env = Environment::from_pairs(vec![(String::from("host_version"), Object::String("1.2.0".into()))]);
+        assert_eq!(evaluate(&mut env, "\"version \" + host_version"), Object::String("version 1.2.0".into()));
+
+        let snapshot = env.snapshot();
+        evaluate(&mut env, "let host_version = \"hacked\"; const added = 1;");
+        assert_eq!(evaluate(&mut env, "[host_version, added]"), Object::Array(vec![Object::String("hacked".into()), Object::Integer(1)]));
+        env.restore(snapshot);
+        assert_eq!(evaluate(&mut env, "host_version"), Object::String("1.2.0".into()));
+        assert_eq!(evaluate(&mut env, "added"), Object::Null);
+        // a name which was a constant after the snapshot can be bound again.
+        assert_eq!(evaluate(&mut env, "let added = 2; added"), Object::Integer(2));
+    }
+
+    thread_local! {
+        static LEN_CALLS: std::cell::Cell<usize> = std::cell::Cell::new(0);
+    }
+
+    // len of an array, which counts how many times it is called.
+    fn counted_len(args: Vec<Object>) -> Object {
+        LEN_CALLS.with(|calls| calls.set(calls.get() + 1));
+        match args.as_slice() {
+            [Object::Array(values)] => Object::Integer(values.len() as i32),
+            _ => Object::Null
+        }
+    }
+
+    #[test]
+    fn test_memoize_invariants() {
+        let count_calls = |input: &str, memoize_invariants: bool| {
+            LEN_CALLS.with(|calls| calls.set(0));
+            let mut env = Environment::with_config(EvalConfig{memoize_invariants: memoize_invariants, ..EvalConfig::default()});
+            env.store.insert(String::from("len"), Object::Builtin{func: counted_len});
+            let program = Parser::new_from_source(input).parse_program().unwrap();
+            let value = env.evaluate(&program).unwrap();
+            (format!("{}", value), LEN_CALLS.with(|calls| calls.get()))
+        };
+        let tests = vec![
+            // len(arr) is the same in every iteration.
+            ("let arr = [1, 2, 3]; let i = 0; let total = 0; while (i < len(arr)) { let total = total + arr[i]; let i = i + 1; } total", "6", 1, 4),
+            // each call in the loop is evaluated once.
+            ("let arr = [1, 2, 3]; let i = 0; let total = 0; while (i < len(arr)) { let total = total + arr[i] * len(arr); let i = i + 1; } total", "18", 2, 7),
+            // arr is bound in the loop.
+            ("let arr = []; while (len(arr) < 3) { let arr = push(arr, 1); }; len(arr)", "3", 5, 5),
+            // a function called in the loop could rebind arr.
+            ("let arr = [1, 2]; let i = 0; let inc = fn(x) { x + 1 }; while (i < len(arr)) { let i = inc(i); } i", "2", 3, 3),
+            // the value is forgotten when the loop ends, so each run of the loop evaluates it once.
+            ("let arr = [1, 2]; let f = fn() { let i = 0; while (i < len(arr)) { let i = i + 1; } i }; f() + f()", "4", 2, 6),
+            // the loop doesn't run, so len(arr) is never evaluated.
+            ("let arr = [1]; while (false) { len(arr) }", "", 0, 0),
+            ];
+        for (input, expected, memoized_calls, calls) in tests.into_iter() {
+            assert_eq!(count_calls(input, true), (expected.to_string(), memoized_calls), "{}", input);
+            assert_eq!(count_calls(input, false), (expected.to_string(), calls), "{}", input);
+        }
+    }
+
+    #[test]
+    fn test_max_steps() {
+        let config = EvalConfig{max_steps: Some(100), ..EvalConfig::default()};
+        let tests = vec![
+            ("while (true) {}", "step limit exceeded: max=100"),
+            ("let f = fn(self, n) { self(self, n + 1) }; f(f, 0)", "step limit exceeded: max=100\n  at f: f(f, 0)"),
+            ("let i = 0; while (i < 3) { let i = i + 1; } i", "3"),
+            ];
+        for (input, expected) in tests.into_iter() {
+            let evaluated = test_evaluate_with_config(input, config.clone());
+            assert_eq!(format!("{}", evaluated), expected, "{}", input);
+        }
+
+        // the steps are counted for each program.
+        let mut env = Environment::with_config(config);
+        let program = Parser::new_from_source("let f = fn() { 1 + 2 + 3 + 4 }; f()").parse_program().unwrap();
+        for _ in 0..20 {
+            assert_eq!(env.evaluate(&program), Ok(Object::Integer(10)));
+        }
+    }
+
+    #[test]
+    fn test_sandbox() {
+        let config = EvalConfig{sandbox: true, allow_env: true, ..EvalConfig::default()};
+        let tests = vec![
+            ("print(\"x\")", "'print' is not allowed in sandbox"),
+            ("env(\"HOME\")", "'env' is not allowed in sandbox"),
+            ("now()", "'now' is not allowed in sandbox"),
+            ("let p = fn(x) { print(x) }; 1", "1"),
+            ("len(\"x\")", "1"),
+            ("sort([2, 1])", "[1, 2]"),
+            ("format(\"{}!\", 1)", "1!"),
+            ("import \"lib.mk\"", "'import' is not allowed in sandbox"),
+            ];
+        for (input, expected) in tests.into_iter() {
+            let evaluated = test_evaluate_with_config(input, config.clone());
+            assert_eq!(format!("{}", evaluated), expected, "{}", input);
+        }
+        let evaluated = test_evaluate_with_config("let p = fn(x) { print(x) }; p(1)", config);
+        assert_eq!(evaluated, Object::Error(Errors::Backtrace{error: Box::new(Errors::SandboxViolation(String::from("print"))),
+                                                              frames: vec![String::from("p: p(1)")]}));
+    }
+
+    fn write_script(name: &str, input: &str) -> String {
+        let path = env::temp_dir().join(name);
+        fs::write(&path, input).unwrap();
+        path.to_str().unwrap().to_string()
+    }
+
+    #[test]
+    fn test_import() {
+        let lib = write_script("monkey_import_lib.mk", "let add = fn(a, b) { a + b };\nlet one = 1;\n");
+        let broken = write_script("monkey_import_broken.mk", "let x = 1 + true;\n");
+        let invalid = write_script("monkey_import_invalid.mk", "let x = );\n");
+        let tests = vec![
+            (format!("import \"{}\"; add(one, 2)", lib), "3"),
+            (format!("import \"{}\";", lib), ""),
+            // the names are bound in the scope where import is evaluated.
+            (format!("let f = fn() {{ import \"{}\"; add(1, 1) }}; f()", lib), "2"),
+            (format!("let f = fn() {{ import \"{}\"; 0 }}; f(); one", lib), ""),
+            (format!("import \"{}\"; 1", broken), "type mismatch: INTEGER + BOOLEAN"),
+            (format!("import \"{}\"; 1", invalid), "invalid token: ')'"),
+            (String::from("import \"monkey_import_missing.mk\""), "could not read file: monkey_import_missing.mk"),
+            ];
+        for (input, expected) in tests.iter() {
+            assert_eq!(format!("{}", test_evaluate(input)), *expected, "{}", input);
+        }
+    }
+
+    #[test]
+    fn test_nested_import() {
+        // the library imports its neighbour by a relative path, and the program runs in another directory.
+        let dir = env::temp_dir().join("monkey_nested_import");
+        fs::create_dir_all(dir.join("util")).unwrap();
+        fs::write(dir.join("util").join("math.mk"), "let square = fn(x) { x * x };\n").unwrap();
+        fs::write(dir.join("lib.mk"), "import \"util/math.mk\";\nlet area = fn(side) { square(side) };\n").unwrap();
+        fs::write(dir.join("main.mk"), "import \"lib.mk\";\narea(3)\n").unwrap();
+        let lib = dir.join("lib.mk");
+        assert_eq!(test_evaluate(&format!("import \"{}\"; area(4)", lib.to_str().unwrap())), Object::Integer(16));
+
+        let main = dir.join("main.mk");
+        let program = Parser::new_from_source(&fs::read_to_string(&main).unwrap()).parse_program().unwrap();
+        assert_eq!(Environment::new().evaluate_file(&program, main.to_str().unwrap()), Ok(Object::Integer(9)));
+        // without the file, the path is relative to the current directory.
+        assert_eq!(Environment::new().evaluate(&program), Ok(Object::Error(Errors::FileNotReadable(String::from("lib.mk")))));
+    }
+
+    #[test]
+    fn test_import_as() {
+        let lib = write_script("monkey_import_as_lib.mk", "let pi = 3;\nlet add = fn(a, b) { a + b };\nlet twice = fn(x) { add(x, x) };\n");
+        let hoisting = write_script("monkey_import_as_hoisting.mk", "let first = fn() { second() };\nlet second = fn() { 2 };\n");
+        let tests = vec![
+            // a function of the file can call one bound after it.
+            (format!("import \"{}\" as h; h.first()", hoisting), "2"),
+            (format!("import \"{}\" as l; l.add(1, 2)", lib), "3"),
+            (format!("import \"{}\" as l; l[\"pi\"]", lib), "3"),
+            (format!("import \"{}\" as l; l.twice(l.pi)", lib), "6"),
+            // the names of the file don't leak into the scope which imports it.
+            (format!("import \"{}\" as l; add", lib), ""),
+            (format!("let pi = 1; import \"{}\" as l; pi", lib), "1"),
+            (format!("let f = fn() {{ import \"{}\" as l; l.pi }}; f()", lib), "3"),
+            (format!("const l = 1; import \"{}\" as l;", lib), "identifier 'l' has already been declared"),
+            ];
+        for (input, expected) in tests.iter() {
+            assert_eq!(format!("{}", test_evaluate(input)), *expected, "{}", input);
+        }
+        // the namespace is a hash of the names bound at the top level of the file.
+        let evaluated = test_evaluate(&format!("import \"{}\" as l; l", lib));
+        match evaluated {
+            Object::Hash(pairs) => assert_eq!(pairs.len(), 3),
+            other => panic!("the namespace should be a hash, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_circular_import() {
+        let first = env::temp_dir().join("monkey_import_first.mk");
+        let second = write_script("monkey_import_second.mk", &format!("import \"{}\";\n", first.to_str().unwrap()));
+        let first = write_script("monkey_import_first.mk", &format!("let x = 1;\nimport \"{}\";\n", second));
+        let evaluated = test_evaluate(&format!("import \"{}\"", first));
+        assert_eq!(evaluated, Object::Error(Errors::CircularImport(first.clone())));
+
+        let itself = env::temp_dir().join("monkey_import_itself.mk");
+        let itself = write_script("monkey_import_itself.mk", &format!("import \"{}\";\n", itself.to_str().unwrap()));
+        assert_eq!(test_evaluate(&format!("import \"{}\"", itself)), Object::Error(Errors::CircularImport(itself.clone())));
+
+        // a file run by evaluate_file can't be imported from the files which it imports.
+        let back = write_script("monkey_import_back.mk", "import \"monkey_import_main.mk\";\n");
+        let main = write_script("monkey_import_main.mk", &format!("import \"{}\";\n", back));
+        let program = Parser::new_from_source(&fs::read_to_string(&main).unwrap()).parse_program().unwrap();
+        let evaluated = Environment::new().evaluate_file(&program, &main).unwrap();
+        assert_eq!(evaluated, Object::Error(Errors::CircularImport(String::from("monkey_import_main.mk"))));
+
+        // a file can be imported again after the first import has finished.
+        let lib = write_script("monkey_import_twice.mk", "let n = 2;\n");
+        assert_eq!(test_evaluate(&format!("import \"{}\"; import \"{}\"; n", lib, lib)), Object::Integer(2));
+    }
+
     #[test]
     fn test_builtin_functions() {
         let tests = vec![
             ("len(\"\");", "0"),
             ("len(\"four\");", "4"),
             ("len(\"hello world\");", "11"),
+            ("len(\"héllo\");", "5"),
+            ("len(\"こんにちは\");", "5"),
+            ("byte_len(\"hello\");", "5"),
+            ("byte_len(\"héllo\");", "6"),
+            ("byte_len(\"こんにちは\");", "15"),
+            ("byte_len([1]);", "argument to 'byte_len' must be string, got [1]"),
             ("len(1);", "argument to len not supported got 1"),
             ("len(\"one\", \"two\");", "wrong number of arguments. got=2, want=1"),
             ("first([\"a\",\"b\"]);", "a"),
@@ -557,6 +2552,222 @@ mod testing {
             ("rest(\"ab\");", "argument to 'rest' must be array, got ab"),
             ("push([\"a\",\"b\",\"c\",\"d\"], \"e\");", "[a, b, c, d, e]"),
             ("push(\"ab\");", "argument to 'push' must be array, got ab"),
+            ("between(5, 1, 10);", "true"),
+            ("between(1, 1, 10);", "true"),
+            ("between(10, 1, 10);", "true"),
+            ("between(0, 1, 10);", "false"),
+            ("between(11, 1, 10);", "false"),
+            ("between(5, \"1\", 10);", "arguments to 'between' must be integer, got 1"),
+            ("between(5, 1);", "wrong number of arguments. got=2, want=3"),
+            ("let double = fn(x) {x * 2}; let inc = fn(x) {x + 1}; let fg = compose(double, inc); fg(3);", "8"),
+            ("let double = fn(x) {x * 2}; let inc = fn(x) {x + 1}; compose(inc, double)(3);", "7"),
+            ("let double = fn(x) {x * 2}; compose(double, len)(\"abc\");", "6"),
+            ("compose(first, sort)([3, 1, 2]);", "1"),
+            ("let inc = fn(x) {x + 1}; compose(inc, compose(inc, inc))(1);", "4"),
+            ("let add = fn(x, y) {x + y}; let inc = fn(x) {x + 1}; compose(inc, add)(1, 2);", "4"),
+            ("compose(first, len)([1]);", "argument to 'first' must be array, got 1"),
+            ("compose(1, len);", "arguments to 'compose' must be function, got 1"),
+            ("compose(len);", "wrong number of arguments. got=1, want=2"),
+            ("let add = curry(fn(a, b) {a + b}, 2); let inc = add(1); inc(4);", "5"),
+            ("let add = curry(fn(a, b) {a + b}, 2); add(1, 4);", "5"),
+            ("let add = curry(fn(a, b) {a + b}, 2); add(1)(4);", "5"),
+            ("let add = curry(fn(a, b) {a + b}, 2); add()(1)()(4);", "5"),
+            ("let sum_three = curry(fn(a, b, c) {a + b + c}); sum_three(1)(2)(3);", "6"),
+            ("let sum_three = curry(fn(a, b, c) {a + b + c}); sum_three(1, 2)(3);", "6"),
+            ("let add = curry(fn(a, b) {a - b}); [add(10)(1), add(20)(1)];", "[9, 19]"),
+            ("let at_least = curry(between, 3)(5); at_least(1, 9);", "true"),
+            ("curry(fn(a, b) {a + b})(1);", "curry(fn (a, b) {a + b}, 2)(1)"),
+            ("curry(1, 2);", "arguments to 'curry' must be function and the number of arguments, got 1"),
+            ("curry(len);", "arguments to 'curry' must be function and the number of arguments, got builtin functions"),
+            ("curry(len, -1);", "arguments to 'curry' must be function and the number of arguments, got -1"),
+            ("arity(fn(a, b) {});", "2"),
+            ("arity(fn() {});", "0"),
+            ("fn f(x) { x } arity(f);", "1"),
+            ("arity(len);", "-1"),
+            ("arity(read_line);", "0"),
+            ("arity(compose(fn(x) { x }, fn(a, b) { a + b }));", "2"),
+            ("arity(curry(fn(a, b, c) { a + b + c })(1));", "2"),
+            ("arity(1);", "argument to 'arity' must be function, got 1"),
+            ("arity();", "wrong number of arguments. got=0, want=1"),
+            ("clone([1, [2, 3], {\"a\": [4]}]);", "[1, [2, 3], {a: [4]}]"),
+            ("clone({\"a\": 1});", "{a: 1}"),
+            ("clone(1);", "1"),
+            ("clone(\"a\");", "a"),
+            ("let a = [1, [2]]; let b = push(clone(a), 3); a;", "[1, [2]]"),
+            ("let a = [1, [2]]; let b = clone(a); let a = push(a, 3); b;", "[1, [2]]"),
+            ("clone();", "wrong number of arguments. got=0, want=1"),
+            ("sort([3, 1, 2]);", "[1, 2, 3]"),
+            ("sort([\"b\", \"c\", \"a\"]);", "[a, b, c]"),
+            ("sort([]);", "[]"),
+            ("sort([\"a\", 2, true, 1]);", "[true, 1, 2, a]"),
+            ("let a = [2, 1]; sort(a); a;", "[2, 1]"),
+            ("sort(1);", "argument to 'sort' must be array, got 1"),
+            ("reverse([1, 2, 3]);", "[3, 2, 1]"),
+            ("reverse([]);", "[]"),
+            ("let a = [1, 2]; reverse(a); a;", "[1, 2]"),
+            ("reverse(\"abc\");", "cba"),
+            ("reverse(\"héllo\");", "olléh"),
+            ("reverse(\"\");", ""),
+            ("reverse(\"abc\") == \"cba\";", "true"),
+            ("reverse(1);", "argument to 'reverse' must be array or string, got 1"),
+            ("reverse([1], [2]);", "wrong number of arguments. got=2, want=1"),
+            ("slice([1, 2, 3, 4], 1, 3);", "[2, 3]"),
+            ("slice([1, 2, 3, 4], 1);", "[2, 3, 4]"),
+            ("slice([1, 2, 3, 4], -2);", "[3, 4]"),
+            ("slice([1, 2, 3, 4], 0, -1);", "[1, 2, 3]"),
+            ("slice([1, 2, 3, 4], -10, 10);", "[1, 2, 3, 4]"),
+            ("slice([1, 2, 3, 4], 3, 1);", "[]"),
+            ("slice([], 0, 1);", "[]"),
+            ("slice(\"hello\", 1, 4);", "ell"),
+            ("slice(\"héllo\", 1, 2);", "é"),
+            ("slice(\"hello\", -3);", "llo"),
+            ("slice(\"hello\", 2, -5);", ""),
+            ("slice(1, 0);", "arguments to 'slice' must be array or string and integers, got 1"),
+            ("slice([1], \"a\");", "arguments to 'slice' must be array or string and integers, got a"),
+            ("slice([1], 0, true);", "arguments to 'slice' must be array or string and integers, got true"),
+            ("slice([1]);", "wrong number of arguments. got=1, want=2..3"),
+            ("slice([1], 0, 1, 2);", "wrong number of arguments. got=4, want=2..3"),
+            ("pairs({\"b\": 2, \"a\": 1});", "[[a, 1], [b, 2]]"),
+            ("pairs({});", "[]"),
+            ("pairs([1]);", "argument to 'pairs' must be hash, got [1]"),
+            ("has({\"a\": 1}, \"a\");", "true"),
+            ("has({\"a\": 1}, \"b\");", "false"),
+            ("has({1: 2, true: 3}, true);", "true"),
+            ("has({1: 2}, \"1\");", "false"),
+            ("has({\"a\": if (false) {1}}, \"a\");", "true"),
+            ("has({}, [1]);", "unusable as hash key: [1]"),
+            ("has([1], 0);", "first argument to 'has' must be hash, got [1]"),
+            ("has({});", "wrong number of arguments. got=1, want=2"),
+            ("format(\"{} + {} = {}\", 1, 2, 3);", "1 + 2 = 3"),
+            ("format(\"[{}] {}\", [1, \"a\"], \"b\");", "[[1, a]] b"),
+            ("format(\"no placeholder\");", "no placeholder"),
+            ("format(\"{{}} {}}}\", 1);", "{} 1}"),
+            ("format(\"{ } {\");", "{ } {"),
+            ("format(\"{} {}\", 1);", "wrong number of arguments. got=2, want=3"),
+            ("format(\"{}\", 1, 2);", "wrong number of arguments. got=3, want=2"),
+            ("format();", "wrong number of arguments. got=0, want=1"),
+            ("format(1);", "first argument to 'format' must be string, got 1"),
+            ("is_array([1]);", "true"),
+            ("is_array({});", "false"),
+            ("is_hash({});", "true"),
+            ("is_hash([]);", "false"),
+            ("is_int(1);", "true"),
+            ("is_int(1.5);", "false"),
+            ("is_int(\"1\");", "false"),
+            ("is_string(\"1\");", "true"),
+            ("is_string(1);", "false"),
+            ("is_fn(fn(x) {x});", "true"),
+            ("is_fn(len);", "true"),
+            ("is_fn(compose(len, rest));", "true"),
+            ("is_fn([]);", "false"),
+            ("is_null(if (false) {1});", "true"),
+            ("is_null(0);", "false"),
+            ("is_int();", "wrong number of arguments. got=0, want=1"),
+            ("abs(-5);", "5"),
+            ("abs(5);", "5"),
+            ("abs(-2147483647 - 1);", "integer overflow"),
+            ("abs(-1.5);", "argument to 'abs' must be integer, got -1.5"),
+            ("pow(2, 10);", "1024"),
+            ("pow(-3, 3);", "-27"),
+            ("pow(5, 0);", "1"),
+            ("pow(2, 31);", "integer overflow"),
+            ("pow(2, -1);", "arguments to 'pow' must be integer and non-negative integer, got -1"),
+            ("pow(\"2\", 1);", "arguments to 'pow' must be integer and non-negative integer, got 2"),
+            ("pow(2);", "wrong number of arguments. got=1, want=2"),
+            ("sign(-3);", "-1"),
+            ("sign(0);", "0"),
+            ("sign(7);", "1"),
+            ("sign(true);", "argument to 'sign' must be integer, got true"),
+            ("is_fn(1, 2);", "wrong number of arguments. got=2, want=1"),
+            ("ord(\"A\");", "65"),
+            ("ord(\"é\");", "233"),
+            ("ord(\"😀\");", "128512"),
+            ("ord(\"AB\");", "argument to 'ord' must be string of one character, got AB"),
+            ("ord(\"\");", "argument to 'ord' must be string of one character, got "),
+            ("ord(65);", "argument to 'ord' must be string of one character, got 65"),
+            ("char(65);", "A"),
+            ("char(233);", "é"),
+            ("char(ord(\"A\"));", "A"),
+            ("char(ord(\"😀\"));", "😀"),
+            ("char(ord(\"A\") + 1);", "B"),
+            ("char(55296);", "argument to 'char' must be valid code point, got 55296"),
+            ("char(-1);", "argument to 'char' must be valid code point, got -1"),
+            ("char(\"A\");", "argument to 'char' must be valid code point, got A"),
+            ("repeat(3, fn(i) {i});", "2"),
+            ("repeat(3, fn() {5});", "5"),
+            ("repeat(1, fn(i) {i * 10});", "0"),
+            ("repeat(0, fn(i) {i});", ""),
+            ("repeat(-1, fn(i) {1 + true});", ""),
+            ("repeat(2, fn(i) {if (i == 1) {1 + true} else {i}});", "type mismatch: INTEGER + BOOLEAN"),
+            ("repeat(2, fn(a, b) {a});", "argument to 'repeat' must be integer and function with zero or one parameter, got fn (a, b) {a}"),
+            ("repeat(\"2\", fn() {1});", "argument to 'repeat' must be integer and function with zero or one parameter, got 2"),
+            ];
+        for test in tests.iter() {
+            let evaluated = test_evaluate(test.0);
+            let return_value = format!("{}", evaluated);
+            assert_eq!(return_value, test.1);
+        }
+    }
+
+    thread_local! {
+        static TICKS: std::cell::Cell<usize> = std::cell::Cell::new(0);
+    }
+
+    // a builtin which counts how many times it is called.
+    fn tick(_args: Vec<Object>) -> Object {
+        TICKS.with(|ticks| ticks.set(ticks.get() + 1));
+        Object::Null
+    }
+
+    #[test]
+    fn test_repeat_side_effects() {
+        let tests = vec![
+            ("repeat(3, fn() { tick() })", 3),
+            ("repeat(3, fn(i) { tick(i) })", 3),
+            ("repeat(0, fn() { tick() })", 0),
+            ("repeat(1, fn() { tick() }); repeat(2, fn() { tick() })", 3),
+            ];
+        for (input, expected) in tests.into_iter() {
+            TICKS.with(|ticks| ticks.set(0));
+            let mut env = Environment::new();
+            env.store.insert(String::from("tick"), Object::Builtin{func: tick});
+            let program = Parser::new_from_source(input).parse_program().unwrap();
+            env.evaluate(&program).unwrap();
+            assert_eq!(TICKS.with(|ticks| ticks.get()), expected, "{}", input);
+        }
+    }
+
+    #[test]
+    fn test_pairs() {
+        let pairs = |values: Vec<(Object, Object)>| Object::Array(values.into_iter().map(|(key, value)| Object::Array(vec![key, value])).collect());
+        let tests = vec![
+            ("pairs({\"b\": 2, \"a\": \"x\"})", pairs(vec![(Object::String("a".into()), Object::String("x".into())),
+                                                      (Object::String("b".into()), Object::Integer(2))])),
+            // keys of different types are ordered by their types.
+            ("pairs({true: 1, \"a\": 2, 3: 3})", pairs(vec![(Object::Integer(3), Object::Integer(3)),
+                                                          (Object::String("a".into()), Object::Integer(2)),
+                                                          (Object::Boolean(true), Object::Integer(1))])),
+            ("pairs({})", pairs(vec![])),
+            ];
+        for (input, expected) in tests.into_iter() {
+            assert_eq!(test_evaluate(input), expected, "{}", input);
+        }
+    }
+
+    #[test]
+    fn test_assert_functions() {
+        let tests = vec![
+            ("assert(true)", ""),
+            ("assert(1 < 2)", ""),
+            ("assert(false)", "assertion failed"),
+            ("assert(1 > 2); 5", "assertion failed"),
+            ("assert()", "wrong number of arguments. got=0, want=1"),
+            ("assert_eq([1], [1])", ""),
+            ("assert_eq({\"a\": [1, 2]}, {\"a\": [1, 2]})", ""),
+            ("assert_eq(1 + 1, 2)", ""),
+            ("assert_eq([1], [2])", "assertion failed: left: [1], right: [2]"),
+            ("assert_eq(1, \"1\")", "assertion failed: left: 1, right: 1"),
+            ("assert_eq(1)", "wrong number of arguments. got=1, want=2"),
             ];
         for test in tests.iter() {
             let evaluated = test_evaluate(test.0);
@@ -573,6 +2784,32 @@ mod testing {
         assert_eq!(return_value, "[1, 4, 6]");
         }
 
+    #[test]
+    fn test_array_operators() {
+        let tests = vec![
+            ("[1, 2] + [3]", "[1, 2, 3]"),
+            ("[] + [1]", "[1]"),
+            ("let a = [1]; let b = a + [2]; a", "[1]"),
+            ("[0] * 3", "[0, 0, 0]"),
+            ("2 * [1, \"a\"]", "[1, a, 1, a]"),
+            ("[1] * 0", "[]"),
+            ("[1] * 2000000", "array too large: length=2000000, max=1000000"),
+            ("[1, 2] + 3", "type mismatch: ARRAY + INTEGER"),
+            ("\"a\" + [1]", "type mismatch: STRING + ARRAY"),
+            ];
+        for test in tests.iter() {
+            let evaluated = test_evaluate(test.0);
+            let return_value = format!("{}", evaluated);
+            assert_eq!(return_value, test.1);
+        }
+
+        let config = EvalConfig{max_repeat_length: 4, ..EvalConfig::default()};
+        let evaluated = test_evaluate_with_config("[1, 2] + [3, 4]", config.clone());
+        assert_eq!(format!("{}", evaluated), "[1, 2, 3, 4]");
+        let evaluated = test_evaluate_with_config("let a = [1, 2, 3]; a + a", config);
+        assert_eq!(format!("{}", evaluated), "array too large: length=6, max=4");
+    }
+
     #[test]
     fn test_array_index_expressions() {
         let tests = vec![
@@ -584,6 +2821,86 @@ mod testing {
             ("let my_array = [1, 2, 3]; let i = my_array[2]", "3"),
             ("let my_array = [1, 2, 3];my_array[0] + my_array[1]", "3"),
             ("let my_array = [1, 2, 3]; let i = my_array[0]; my_array[i]", "2"),
+            ("[1, 2, 3][3]", ""),
+            ("[1, 2, 3][-1]", ""),
+            ];
+        for test in tests.iter() {
+            let evaluated = test_evaluate(test.0);
+            let return_value = format!("{}", evaluated);
+            assert_eq!(return_value, test.1);
+        }
+    }
+
+    #[test]
+    fn test_array_index_assignments() {
+        let tests = vec![
+                ("let a = [1, 2, 3]; a[0] = 9; a", "[9, 2, 3]"),
+                ("let a = [1, 2, 3]; a[2] = a[0] + a[1] * 10; a", "[1, 2, 21]"),
+                ("let a = [1, 2, 3]; let i = 1; a[i + 1] = \"x\"; a", "[1, 2, x]"),
+                ("let a = [[1, 2], [3]]; a[0][1] = 5; a", "[[1, 5], [3]]"),
+                ("let a = [1]; let b = a; a[0] = 2; b", "[1]"),
+                ("let a = [1]; if (true) { a[0] = 2; } a", "[2]"),
+                ("let a = [1]; let f = fn() { a[0] = 2; a }; [f(), a]", "[[2], [1]]"),
+                ("let x = 1; x = 2; x", "2"),
+                ("let a = [1, 2, 3]; a[3] = 9;", "index out of range: index=3, length=3"),
+                ("let a = [1, 2, 3]; a[-1] = 9;", "index out of range: index=-1, length=3"),
+                ("let a = [1, 2, 3]; a[\"0\"] = 9;", "index of array must be integer, got 0"),
+                ("let a = 1; a[0] = 9;", "index assignment not supported: 1"),
+                ("const a = [1]; a[0] = 9;", "can't assign to constant 'a'"),
+                ("b[0] = 9;", "identifier 'b' has not been declared"),
+                ];
+        for (input, expected) in tests.into_iter() {
+            assert_eq!(format!("{}", test_evaluate(input)), expected, "{}", input);
+        }
+    }
+
+    #[test]
+    fn test_hash_index_assignments() {
+        let tests = vec![
+                ("let h = {\"a\": 1}; h[\"a\"] = 2; h", "{a: 2}"),
+                ("let h = {\"a\": 1}; h[\"b\"] = 2; h", "{a: 1, b: 2}"),
+                ("let h = {}; h[1] = \"one\"; h[true] = \"yes\"; [h[1], h[true]]", "[one, yes]"),
+                ("let h = {\"a\": 1}; h[\"a\"] = h[\"a\"] + 1; h[\"a\"]", "2"),
+                ("let h = {\"xs\": [1, 2]}; h[\"xs\"][0] = 3; h", "{xs: [3, 2]}"),
+                ("let a = [{\"k\": 1}]; a[0][\"k\"] = 2; a", "[{k: 2}]"),
+                ("let h = {}; let g = h; h[\"a\"] = 1; g", "{}"),
+                ("let h = {}; h[[1]] = 1;", "unusable as hash key: [1]"),
+                ("let h = {}; h[fn(x) {x}] = 1;", "unusable as hash key: fn (x) {x}"),
+                ];
+        for (input, expected) in tests.into_iter() {
+            assert_eq!(format!("{}", test_evaluate(input)), expected, "{}", input);
+        }
+    }
+
+    #[test]
+    fn test_self_assignment() {
+        // the value is copied into itself, so it doesn't refer to itself.
+        let tests = vec![
+                ("let a = [1, 2]; a[0] = a; a", "[[1, 2], 2]"),
+                ("let a = [1, 2]; a[0] = a; a[0][0] = 3; a", "[[3, 2], 2]"),
+                ("let h = {\"a\": 1}; h[\"self\"] = h; h", "{a: 1, self: {a: 1}}"),
+                ];
+        for (input, expected) in tests.into_iter() {
+            assert_eq!(format!("{}", test_evaluate(input)), expected, "{}", input);
+        }
+        let nested = test_evaluate("let a = [0]; let i = 0; while (i < 20) { a[0] = a; let i = i + 1; } a");
+        let config = crate::object::InspectConfig::default();
+        assert_eq!(nested.inspect(&config), format!("{}[...]{}", "[".repeat(config.max_depth), "]".repeat(config.max_depth)));
+        assert!(format!("{}", nested).starts_with(&"[".repeat(21)));
+    }
+
+    #[test]
+    fn test_string_index_expressions() {
+        let tests = vec![
+            ("\"hello\"[1]", "e"),
+            ("\"hello\"[0] + \"hello\"[4]", "ho"),
+            ("len(\"hello\"[4])", "1"),
+            ("\"hello\"[5]", ""),
+            ("\"hello\"[-1]", ""),
+            ("\"héllo\"[1]", "é"),
+            ("\"こんにちは\"[2]", "に"),
+            ("\"hello\"[\"1\"]", "index of string must be integer, got 1"),
+            ("\"hello\"[true]", "index of string must be integer, got true"),
             ];
         for test in tests.iter() {
             let evaluated = test_evaluate(test.0);
@@ -0,0 +1,149 @@
+use super::ast::{Program, Statement, Expression, Precedence, operator_precedence};
+
+// write a program as source in one style, with a statement on each line and blocks indented by four spaces.
+// comments are written where they were if the program is parsed by Parser::new_with_comments.
+// the pairs of a hash are written in the order the AST keeps them, which may differ from the source.
+pub fn format(program: &Program) -> String {
+    let mut output = String::new();
+    for statement in program.statements.iter() {
+        write_statement(&mut output, statement, 0);
+    }
+    output
+}
+
+fn write_statement(output: &mut String, statement: &Statement, indent: usize) {
+    let (leading_comments, statement, trailing_comment) = match statement {
+        Statement::Commented{leading_comments, statement, trailing_comment} => (&leading_comments[..], statement.without_comments(), trailing_comment.as_ref()),
+        _ => (&[][..], statement, None)
+    };
+    for comment in leading_comments.iter() {
+        output.push_str(&format!("{}{}\n", "    ".repeat(indent), comment));
+    }
+    output.push_str(&"    ".repeat(indent));
+    output.push_str(&statement_source(statement, indent));
+    if let Some(comment) = trailing_comment {
+        output.push_str(&format!(" {}", comment));
+    }
+    output.push('\n');
+}
+
+// every statement ends with ';', so that the next line isn't read as a part of it.
+fn statement_source(statement: &Statement, indent: usize) -> String {
+    match statement {
+        Statement::LetStatement{identifier, annotation, value} => match annotation {
+            Some(annotation) => format!("let {}: {} = {};", expression_source(identifier, indent), annotation, expression_source(value, indent)),
+            None => format!("let {} = {};", expression_source(identifier, indent), expression_source(value, indent)),
+        },
+        Statement::ConstStatement{identifier, value} => format!("const {} = {};", expression_source(identifier, indent), expression_source(value, indent)),
+        Statement::Assign{target, value} => format!("{} = {};", expression_source(target, indent), expression_source(value, indent)),
+        Statement::Return(value) => format!("return {};", expression_source(value, indent)),
+        Statement::Break(Some(value)) => format!("break {};", expression_source(value, indent)),
+        Statement::Break(None) => String::from("break;"),
+        Statement::ExpressionStatement(value) => format!("{};", expression_source(value, indent)),
+        Statement::Import{path, alias: Some(alias)} => format!("import {} as {};", string_source(path), alias),
+        Statement::Import{path, alias: None} => format!("import {};", string_source(path)),
+        Statement::Block(_) => block_source(statement, indent),
+        Statement::Commented{statement, ..} => statement_source(statement, indent),
+    }
+}
+
+// a block starts at the end of the line of the expression which has it, and ends at its indent.
+fn block_source(block: &Statement, indent: usize) -> String {
+    let statements = match block {
+        Statement::Block(statements) => &statements[..],
+        statement => std::slice::from_ref(statement),
+    };
+    if statements.is_empty() {
+        return String::from("{}")
+    }
+    let mut output = String::from("{\n");
+    for statement in statements.iter() {
+        write_statement(&mut output, statement, indent + 1);
+    }
+    output.push_str(&format!("{}}}", "    ".repeat(indent)));
+    output
+}
+
+fn expression_source(expression: &Expression, indent: usize) -> String {
+    let list = |expressions: &[Expression]| expressions.iter().map(|expression| expression_source(expression, indent)).collect::<Vec<_>>().join(", ");
+    match expression {
+        Expression::Identifier(name) | Expression::ResolvedIdentifier{name, ..} | Expression::LParen(name) => name.clone(),
+        Expression::String(value) => string_source(value),
+        Expression::Integer(value) => value.to_string(),
+        Expression::Float(value) => value.to_string(),
+        Expression::Bool(value) => value.to_string(),
+        Expression::Null => String::from("null"),
+        Expression::Array(values) => format!("[{}]", list(values)),
+        Expression::Hashmap(tree) => format!("{{{}}}", tree.iter().map(|(key, value)| format!("{}: {}", expression_source(key, indent), expression_source(value, indent))).collect::<Vec<_>>().join(", ")),
+        Expression::IndexExpression{array, subscript} => format!("{}[{}]", operand(array, Precedence::PREFIX, true, indent), expression_source(subscript, indent)),
+        Expression::OptionalIndexExpression{array, subscript} => format!("{}?.[{}]", operand(array, Precedence::PREFIX, true, indent), expression_source(subscript, indent)),
+        Expression::PrefixExpression{operator, right_expression} => format!("{}{}", operator, operand(right_expression, Precedence::PREFIX, false, indent)),
+        Expression::InfixExpression{left_expression, operator, right_expression} => {
+            let precedence = operator_precedence(operator);
+            format!("{} {} {}", operand(left_expression, precedence, false, indent), operator, operand(right_expression, precedence, true, indent))
+        },
+        Expression::IfExpression{condition, consequence, alternative: Some(alternative)} =>
+            format!("if ({}) {} else {}", expression_source(condition, indent), block_source(consequence, indent), block_source(alternative, indent)),
+        Expression::IfExpression{condition, consequence, alternative: None} =>
+            format!("if ({}) {}", expression_source(condition, indent), block_source(consequence, indent)),
+        Expression::WhileExpression{condition, body} => format!("while ({}) {}", expression_source(condition, indent), block_source(body, indent)),
+        Expression::DoExpression{body} => format!("do {}", block_source(body, indent)),
+        Expression::FunctionLiteral{parameters, body} => format!("fn({}) {}", list(parameters), block_source(body, indent)),
+        Expression::CallExpression{function, body} => format!("{}({})", operand(function, Precedence::PREFIX, true, indent), list(body)),
+    }
+}
+
+// the same parentheses as the Display of Expression. the right side of an operator is also wrapped
+// at the same precedence, because operators are left associative.
+fn operand(expression: &Expression, precedence: Precedence, is_right: bool, indent: usize) -> String {
+    match expression.precedence() {
+        Some(own) if own < precedence || (is_right && own == precedence) => format!("({})", expression_source(expression, indent)),
+        _ => expression_source(expression, indent)
+    }
+}
+
+// a string literal which the lexer reads back into the same value.
+fn string_source(value: &str) -> String {
+    let mut source = String::from("\"");
+    for ch in value.chars() {
+        match ch {
+            '\\' => source.push_str("\\\\"),
+            '"' => source.push_str("\\\""),
+            '\n' => source.push_str("\\n"),
+            '\t' => source.push_str("\\t"),
+            _ => source.push(ch),
+        }
+    }
+    source.push('"');
+    source
+}
+
+#[cfg(test)]// test runs only when execute cargo run
+mod testing {
+    use crate::parser::{Parser};
+    use crate::format::{format};
+
+    fn format_source(input: &str) -> String {
+        format(&Parser::new_with_comments(input).parse_program().unwrap())
+    }
+
+    #[test]
+    fn test_format() {
+        let tests = vec![
+                ("# note\nlet x = 5;", "# note\nlet x = 5;\n"),
+                ("let x = 5; # five\n# the next\n# two lines\nx", "let x = 5; # five\n# the next\n# two lines\nx;\n"),
+                ("let f = fn(x) {\n# inside\nx * 2 # double\n};", "let f = fn(x) {\n    # inside\n    x * 2; # double\n};\n"),
+                ("if (a) { 1 } else { # none\n}", "# none\nif (a) {\n    1;\n} else {};\n"),
+                ("(1 + 2) * 3 - (4 - 5)", "(1 + 2) * 3 - (4 - 5);\n"),
+                ("let s = \"a \\\"b\\\"\\n\\\\\"; s[0]", "let s = \"a \\\"b\\\"\\n\\\\\";\ns[0];\n"),
+                ("fn f(x) { return x; }; f(1)", "let f = fn(x) {\n    return x;\n};\nf(1);\n"),
+                ];
+        for (input, expected) in tests.into_iter() {
+            assert_eq!(format_source(input), expected, "{}", input);
+            // the formatted source has the same statements, and formatting it again changes nothing.
+            let program = Parser::new_from_source(input).parse_program().unwrap();
+            assert_eq!(Parser::new_from_source(expected).parse_program().unwrap(), program, "{}", input);
+            assert_eq!(format_source(expected), expected, "{}", input);
+        }
+    }
+}
@@ -0,0 +1,216 @@
+use std::collections::BTreeSet;
+
+use super::ast::{Statement, Expression};
+
+// calls in a loop whose value is the same in every iteration, so that the evaluator can evaluate them once.
+// a call is invariant if it calls a pure builtin with arguments made of literals and names which nothing in the loop binds.
+// nothing is invariant if the loop calls anything else, because a function can rebind the names which the loop reads,
+// or if the loop imports a file, because the names which it binds aren't known.
+// calls inside a function defined in the loop aren't included, because they are evaluated when the function is called.
+pub fn invariant_calls<'a>(condition: &'a Expression, body: &'a Statement, is_pure: &dyn Fn(&str) -> bool) -> Vec<&'a Expression> {
+    let mut analysis = Analysis{bound: BTreeSet::new(), impure: false};
+    analysis.expression(condition, is_pure);
+    analysis.statement(body, is_pure);
+    if analysis.impure {
+        return vec![]
+    }
+    let mut calls = vec![];
+    analysis.collect_expression(condition, &mut calls);
+    analysis.collect_statement(body, &mut calls);
+    calls
+}
+
+struct Analysis {
+    // names bound by let, const or assignment anywhere in the loop.
+    bound: BTreeSet<String>,
+    impure: bool,
+}
+
+impl Analysis {
+    fn statement(&mut self, statement: &Statement, is_pure: &dyn Fn(&str) -> bool) {
+        match statement {
+            Statement::LetStatement{identifier, value, ..} | Statement::ConstStatement{identifier, value} => {
+                if let Expression::Identifier(name) = identifier {
+                    self.bound.insert(name.clone());
+                }
+                self.expression(value, is_pure);
+            },
+            Statement::Assign{target, value} => {
+                // a[0] = 9 changes a, so a is bound as well.
+                if let Some(name) = root_name(target) {
+                    self.bound.insert(name.to_string());
+                }
+                self.expression(target, is_pure);
+                self.expression(value, is_pure);
+            },
+            Statement::Return(value) | Statement::Break(Some(value)) | Statement::ExpressionStatement(value) => self.expression(value, is_pure),
+            Statement::Break(None) => {},
+            Statement::Import{..} => self.impure = true,
+            Statement::Commented{statement, ..} => self.statement(statement, is_pure),
+            Statement::Block(statements) => {
+                for statement in statements.iter() {
+                    self.statement(statement, is_pure);
+                }
+            },
+        }
+    }
+
+    fn expression(&mut self, expression: &Expression, is_pure: &dyn Fn(&str) -> bool) {
+        match expression {
+            Expression::CallExpression{function, body} => {
+                match callee_name(function) {
+                    Some(name) if is_pure(name) => {},
+                    _ => self.impure = true,
+                }
+                for argument in body.iter() {
+                    self.expression(argument, is_pure);
+                }
+            },
+            Expression::IfExpression{condition, consequence, alternative} => {
+                self.expression(condition, is_pure);
+                self.statement(consequence, is_pure);
+                if let Some(alternative) = alternative {
+                    self.statement(alternative, is_pure);
+                }
+            },
+            Expression::WhileExpression{condition, body} => {
+                self.expression(condition, is_pure);
+                self.statement(body, is_pure);
+            },
+            Expression::DoExpression{body} => self.statement(body, is_pure),
+            // a function has its own scope, and its body isn't evaluated until it is called.
+            Expression::FunctionLiteral{..} => {},
+            _ => for child in children(expression) {
+                self.expression(child, is_pure);
+            }
+        }
+    }
+
+    fn collect_statement<'a>(&self, statement: &'a Statement, calls: &mut Vec<&'a Expression>) {
+        match statement {
+            Statement::LetStatement{value, ..} | Statement::ConstStatement{value, ..} => self.collect_expression(value, calls),
+            Statement::Assign{target, value} => {
+                self.collect_expression(target, calls);
+                self.collect_expression(value, calls);
+            },
+            Statement::Return(value) | Statement::Break(Some(value)) | Statement::ExpressionStatement(value) => self.collect_expression(value, calls),
+            Statement::Break(None) | Statement::Import{..} => {},
+            Statement::Commented{statement, ..} => self.collect_statement(statement, calls),
+            Statement::Block(statements) => {
+                for statement in statements.iter() {
+                    self.collect_statement(statement, calls);
+                }
+            },
+        }
+    }
+
+    // the outermost invariant calls, so that a call inside another invariant call isn't included.
+    fn collect_expression<'a>(&self, expression: &'a Expression, calls: &mut Vec<&'a Expression>) {
+        match expression {
+            Expression::CallExpression{..} if self.is_invariant(expression) => calls.push(expression),
+            Expression::FunctionLiteral{..} => {},
+            Expression::IfExpression{condition, consequence, alternative} => {
+                self.collect_expression(condition, calls);
+                self.collect_statement(consequence, calls);
+                if let Some(alternative) = alternative {
+                    self.collect_statement(alternative, calls);
+                }
+            },
+            Expression::WhileExpression{condition, body} => {
+                self.collect_expression(condition, calls);
+                self.collect_statement(body, calls);
+            },
+            Expression::DoExpression{body} => self.collect_statement(body, calls),
+            _ => for child in children(expression) {
+                self.collect_expression(child, calls);
+            }
+        }
+    }
+
+    fn is_invariant(&self, expression: &Expression) -> bool {
+        match expression {
+            Expression::String(_) | Expression::Integer(_) | Expression::Float(_) | Expression::Bool(_) | Expression::Null => true,
+            Expression::Identifier(name) | Expression::ResolvedIdentifier{name, ..} => !self.bound.contains(name),
+            // the callee is a pure builtin, or the loop would have been impure.
+            Expression::CallExpression{function, body} => self.is_invariant(function) && body.iter().all(|argument| self.is_invariant(argument)),
+            Expression::Array(_) | Expression::Hashmap(_) | Expression::IndexExpression{..} | Expression::OptionalIndexExpression{..} |
+            Expression::PrefixExpression{..} | Expression::InfixExpression{..} => children(expression).into_iter().all(|child| self.is_invariant(child)),
+            _ => false
+        }
+    }
+}
+
+// the expressions directly inside an expression, except for the statements of if, while, do and functions.
+fn children(expression: &Expression) -> Vec<&Expression> {
+    match expression {
+        Expression::Array(values) => values.iter().collect(),
+        Expression::Hashmap(tree) => tree.iter().flat_map(|(key, value)| vec![&**key, &**value]).collect(),
+        Expression::IndexExpression{array, subscript} | Expression::OptionalIndexExpression{array, subscript} => vec![&**array, &**subscript],
+        Expression::PrefixExpression{right_expression, ..} => vec![&**right_expression],
+        Expression::InfixExpression{left_expression, right_expression, ..} => vec![&**left_expression, &**right_expression],
+        Expression::IfExpression{condition, ..} | Expression::WhileExpression{condition, ..} => vec![&**condition],
+        Expression::CallExpression{function, body} => std::iter::once(&**function).chain(body.iter()).collect(),
+        _ => vec![]
+    }
+}
+
+fn callee_name(function: &Expression) -> Option<&str> {
+    match function {
+        Expression::Identifier(name) | Expression::ResolvedIdentifier{name, ..} => Some(name.as_str()),
+        _ => None
+    }
+}
+
+fn root_name(target: &Expression) -> Option<&str> {
+    match target {
+        Expression::Identifier(name) | Expression::ResolvedIdentifier{name, ..} => Some(name.as_str()),
+        Expression::IndexExpression{array, ..} => root_name(array),
+        _ => None
+    }
+}
+
+#[cfg(test)]
+mod testing {
+    use crate::parser::Parser;
+    use crate::ast::{Statement, Expression};
+    use crate::invariant::invariant_calls;
+
+    fn parse_expression(input: &str) -> Expression {
+        match Parser::new_from_source(input).parse_program().unwrap().statements.remove(0) {
+            Statement::ExpressionStatement(expression) => expression,
+            statement => panic!("expected an expression, got {}", statement),
+        }
+    }
+
+    // the invariant calls of the loop in the input.
+    fn invariants(input: &str) -> Vec<Expression> {
+        let is_pure = |name: &str| ["len", "pow", "abs"].contains(&name);
+        match parse_expression(input) {
+            Expression::WhileExpression{condition, body} => invariant_calls(&condition, &body, &is_pure).into_iter().cloned().collect(),
+            expression => panic!("expected a loop, got {}", expression),
+        }
+    }
+
+    #[test]
+    fn test_invariant_calls() {
+        let tests: Vec<(&str, Vec<&str>)> = vec![
+            ("while (i < len(arr)) { let i = i + 1; }", vec!["len(arr)"]),
+            ("while (i < 3) { let n = len(arr) * 2; let i = i + 1; }", vec!["len(arr)"]),
+            // the outermost call is evaluated once, with the calls inside it.
+            ("while (i < 3) { let n = pow(len(arr), 2); let i = i + 1; }", vec!["pow(len(arr), 2)"]),
+            // i is bound in the loop.
+            ("while (i < 3) { let n = abs(i); let i = i + 1; }", vec![]),
+            ("while (i < 3) { arr[0] = i; let n = len(arr); let i = i + 1; }", vec![]),
+            // a call which isn't pure makes the whole loop impure.
+            ("while (i < len(arr)) { print(i); let i = i + 1; }", vec![]),
+            ("while (i < len(arr)) { let i = inc(i); }", vec![]),
+            ("while (i < len(arr)) { import \"lib.mk\"; let i = i + 1; }", vec![]),
+            // a function defined in the loop isn't called by defining it.
+            ("while (i < len(arr)) { let f = fn() { print(i) }; let i = i + 1; }", vec!["len(arr)"]),
+            ];
+        for (input, expected) in tests.into_iter() {
+            let expected: Vec<Expression> = expected.into_iter().map(parse_expression).collect();
+            assert_eq!(invariants(input), expected, "{}", input);
+        }
+    }
+}
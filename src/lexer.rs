@@ -6,20 +6,101 @@ pub struct Lexer<'a>  {
     position:     usize, // current input position
     read_position: usize, // next input position
     ch:           u8, // a letter which is currently read
+    token_position: usize, // input position where the last token starts
+    newline_tokens: bool, // emit NEWLINE instead of skipping '\n'
+    comment_tokens: bool, // emit COMMENT instead of skipping '#' comments
+    eof_returned: bool, // next_token_checked() has returned EOF
 }
 
 impl<'a>  Lexer<'a>  {
-    pub fn new(input: &'a str) -> Self {
+    // input is anything which can be borrowed as a str like &str or &String,
+    // and the lexer borrows it while it is used.
+    pub fn new<S: AsRef<str> + ?Sized>(input: &'a S) -> Self {
+        Self::new_at(input.as_ref(), 0)
+    }
+
+    // start reading input from the byte offset, so that a tool can lex again
+    // only the region after the offset which position() returned.
+    pub fn new_at(input: &'a str, offset: usize) -> Self {
+        // a shebang like `#!/usr/bin/env monkey` in the first line is skipped,
+        // but the newline is kept so that lines and columns stay the same.
+        let offset = if offset == 0 && input.starts_with("#!") {
+            input.find('\n').unwrap_or(input.len())
+        } else {
+            offset
+        };
         let mut l = Lexer{
                       input,
-                      position: 0,
-                      read_position: 0,
-                      ch: 0
+                      position: offset,
+                      read_position: offset,
+                      ch: 0,
+                      token_position: offset,
+                      newline_tokens: false,
+                      comment_tokens: false,
+                      eof_returned: false
                     };
         l.read_char();
         return l;
     }
 
+    // emit NEWLINE for '\n' instead of skipping it as whitespace,
+    // so that the parser can end a statement at the end of a line.
+    pub fn with_newline_tokens(mut self) -> Self {
+        self.newline_tokens = true;
+        self
+    }
+
+    // emit COMMENT for '#' comments instead of skipping them,
+    // so that a tool like a highlighter can see them. the parser keeps them around statements.
+    pub fn with_comment_tokens(mut self) -> Self {
+        self.comment_tokens = true;
+        self
+    }
+
+    pub fn newline_tokens(&self) -> bool {
+        self.newline_tokens
+    }
+
+    pub fn comment_tokens(&self) -> bool {
+        self.comment_tokens
+    }
+
+    // the byte offset of the letter which is currently read.
+    pub fn position(&self) -> usize {
+        self.position
+    }
+
+    // the number of bytes of the whole input.
+    pub fn input_length(&self) -> usize {
+        self.input.len()
+    }
+
+    // the part of input which hasn't been read yet.
+    pub fn remaining(&self) -> &'a str {
+        self.input.get(self.position..).unwrap_or("")
+    }
+
+    // the byte offset where the token returned by next_token() last time starts.
+    pub fn token_position(&self) -> usize {
+        self.token_position
+    }
+
+    // convert a byte offset into line and column, both of them start from 1.
+    pub fn line_column(&self, offset: usize) -> (usize, usize) {
+        let mut line = 1;
+        let mut column = 1;
+        // columns are counted by characters, not by bytes.
+        for ch in self.input[..offset].chars() {
+            if ch == '\n' {
+                line += 1;
+                column = 1;
+            } else {
+                column += 1;
+            }
+        }
+        (line, column)
+    }
+
     fn read_char(&mut self) {
     if self.read_position >= self.input.len() {
         self.ch = 0;
@@ -30,15 +111,113 @@ impl<'a>  Lexer<'a>  {
     self.read_position += 1;
     }
 
-    fn read_string(&mut self) -> String {
+    // none is returned if the input ends before the closing quote.
+    fn read_string(&mut self) -> Option<String> {
         let position = self.position + 1;
         loop {
             self.read_char();
-            if self.ch == b'"' || self.ch == 0{
+            // the letter after a backslash doesn't end the string like \".
+            if self.ch == b'\\' && self.peek_char() != 0 {
+                self.read_char();
+                continue;
+            }
+            if self.ch == b'"' {
             break;
             }
+            if self.ch == 0 {
+                return None;
+            }
+        }
+        Some(self.input[position..self.position].to_string())
+    }
+
+    // the number of '#' between r and '"' if a raw string like r"C:\path" or r#"say "hi""# starts here.
+    fn raw_string_hashes(&self) -> Option<usize> {
+        if self.ch != b'r' {
+            return None;
+        }
+        let rest = &self.input.as_bytes()[self.read_position.min(self.input.len())..];
+        let hashes = rest.iter().take_while(|ch| **ch == b'#').count();
+        if rest.get(hashes) == Some(&b'"') {
+            Some(hashes)
+        } else {
+            None
+        }
+    }
+
+    // a raw string ends at '"' followed by as many '#' as it starts with, and a backslash in it is a letter as it is.
+    // a raw string without the end is illegal until the end of input.
+    fn read_raw_string(&mut self, hashes: usize) -> Token {
+        let start = self.read_position + hashes + 1;
+        let closing = format!("\"{}", "#".repeat(hashes));
+        match self.input[start..].find(&closing) {
+            Some(length) => {
+                // stop at the last letter of the end, which next_token() reads past.
+                let end = start + length + closing.len() - 1;
+                while self.position < end {
+                    self.read_char();
+                }
+                Token {
+                    token_type: TokenKind::STRING,
+                    literal: self.input[start..start + length].to_string()
+                }
+            },
+            None => {
+                let literal = self.input[self.position..].to_string();
+                while self.ch != 0 {
+                    self.read_char();
+                }
+                Token {
+                    token_type: TokenKind::ILLEGAL,
+                    literal
+                }
+            }
+        }
+    }
+
+    // replace `\n`, `\t`, `\\` and `\"` with a newline, a tab, a backslash and a quote,
+    // and `\u{41}` with the character of the hex code point like `A`.
+    // none is returned if the braces or the code point are invalid.
+    // a backslash followed by another letter is kept as it is.
+    fn decode_escapes(literal: &str) -> Option<String> {
+        let mut decoded = String::new();
+        let mut chars = literal.chars();
+        while let Some(ch) = chars.next() {
+            if ch != '\\' {
+                decoded.push(ch);
+                continue;
+            }
+            match chars.next() {
+                Some('n') => decoded.push('\n'),
+                Some('t') => decoded.push('\t'),
+                Some('\\') => decoded.push('\\'),
+                Some('"') => decoded.push('"'),
+                Some('u') => decoded.push(Self::read_code_point(&mut chars)?),
+                Some(other) => {
+                    decoded.push(ch);
+                    decoded.push(other);
+                },
+                None => decoded.push(ch)
+            }
+        }
+        Some(decoded)
+    }
+
+    // read `{41}` after `\u` and return the character of the hex code point.
+    fn read_code_point(chars: &mut std::str::Chars<'_>) -> Option<char> {
+        if chars.next() != Some('{') {
+            return None;
+        }
+        let mut hex = String::new();
+        loop {
+            match chars.next() {
+                Some('}') => break,
+                Some(digit) if digit.is_ascii_hexdigit() && hex.len() < 6 => hex.push(digit),
+                _ => return None
+            }
         }
-        self.input[position..self.position].to_string()
+        let code_point = u32::from_str_radix(&hex, 16).ok()?;
+        std::char::from_u32(code_point)
     }
 
     fn peek_char(&mut self) -> u8 {
@@ -64,12 +243,38 @@ impl<'a>  Lexer<'a>  {
         self.input.get(position..self.position).unwrap().to_string()
     }
 
-    fn read_number(&mut self) -> String {
+    // read an integer, or a float which has a fraction or an exponent like 2.5e-2.
+    // an exponent without digits like 1e+ is ILLEGAL.
+    fn read_number(&mut self) -> Token {
         let position = self.position;
+        let mut token_type = TokenKind::INT;
+        self.read_digits();
+        if self.ch == b'.' && Self::is_digit(&self.peek_char()) {
+            token_type = TokenKind::FLOAT;
+            self.read_char();
+            self.read_digits();
+        }
+        if self.ch == b'e' || self.ch == b'E' {
+            token_type = TokenKind::FLOAT;
+            self.read_char();
+            if self.ch == b'+' || self.ch == b'-' {
+                self.read_char();
+            }
+            if !Self::is_digit(&self.ch) {
+                token_type = TokenKind::ILLEGAL;
+            }
+            self.read_digits();
+        }
+        Token {
+            token_type,
+            literal: self.input.get(position..self.position).unwrap().to_string()
+        }
+    }
+
+    fn read_digits(&mut self) {
         while Self::is_digit(&self.ch) {
             self.read_char();
         }
-        self.input.get(position..self.position).unwrap().to_string()
     }
 
     fn is_letter(ch: &u8) -> bool {
@@ -83,15 +288,49 @@ impl<'a>  Lexer<'a>  {
     }
 
     fn skip_whitespace(&mut self) {
-    while self.ch == b' ' || self.ch == b'\t' || self.ch == b'\n' || self.ch == b'\r' {
-        self.read_char();
+    loop {
+        while self.ch == b' ' || self.ch == b'\t' || (self.ch == b'\n' && !self.newline_tokens) || self.ch == b'\r' {
+            self.read_char();
+            }
+        if self.ch != b'#' || self.comment_tokens {
+            break;
+            }
+        self.skip_comment();
+        }
+    }
+
+    // '#' starts a comment until the end of the line.
+    // the newline isn't skipped here, so that it can still end a statement.
+    fn skip_comment(&mut self) {
+        while self.ch != b'\n' && self.ch != 0 {
+            self.read_char();
+        }
+    }
+
+    // the same as next_token(), but EOF is returned only once and none is returned after it,
+    // so that a loop which misses EOF stops instead of reading EOF forever.
+    pub fn next_token_checked(&mut self) -> Option<Token> {
+        if self.eof_returned {
+            return None;
         }
+        let token = self.next_token();
+        self.eof_returned = token.token_type == TokenKind::EOF;
+        Some(token)
     }
 
     pub fn next_token(&mut self) -> Token {
         self.skip_whitespace();
+        self.token_position = self.position;
         let token;
         match self.ch {
+            // a comment reaches here only with comment tokens, and it doesn't include the newline.
+            b'#' if self.comment_tokens => {
+                let position = self.position;
+                while self.peek_char() != b'\n' && self.peek_char() != 0 {
+                    self.read_char();
+                }
+                token = Token{token_type: TokenKind::COMMENT, literal: String::from(&self.input[position..self.read_position])};
+            }
             b'=' => {
                 // if peek_char is '=', the literal will be '==',
                 if self.peek_char() == b'='{
@@ -121,11 +360,58 @@ impl<'a>  Lexer<'a>  {
                 token = Self::new_token(TokenKind::BANG, self.ch);
                 }
             }
+            b'|' => {
+                // '|' is used only in '|>'.
+                if self.peek_char() == b'>' {
+                    let curent_position = self.position;
+                    self.read_char();
+                    token =  Token {
+                        token_type: TokenKind::PIPE,
+                        literal: String::from(&self.input[curent_position..self.read_position])
+                        }
+                } else {
+                token = Self::new_token(TokenKind::ILLEGAL, self.ch);
+                }
+            }
+            b'?' => {
+                // '?' is used only in '?.' and '??'.
+                if self.peek_char() == b'.' {
+                    let curent_position = self.position;
+                    self.read_char();
+                    token =  Token {
+                        token_type: TokenKind::OptionalChain,
+                        literal: String::from(&self.input[curent_position..self.read_position])
+                        }
+                } else if self.peek_char() == b'?' {
+                    let curent_position = self.position;
+                    self.read_char();
+                    token =  Token {
+                        token_type: TokenKind::NullCoalesce,
+                        literal: String::from(&self.input[curent_position..self.read_position])
+                        }
+                } else {
+                token = Self::new_token(TokenKind::ILLEGAL, self.ch);
+                }
+            }
             b'*' => {
                 token = Self::new_token(TokenKind::ASTERISK, self.ch);
             }
+            b'.' => {
+                // a member access like m.pi, the dot in a float like 1.5 is read by read_number.
+                token = Self::new_token(TokenKind::DOT, self.ch);
+            }
             b'/' => {
+                // if peek_char is '/', the literal will be '//',
+                if self.peek_char() == b'/' {
+                    let curent_position = self.position;
+                    self.read_char();
+                    token =  Token {
+                        token_type: TokenKind::IntDiv,
+                        literal: String::from(&self.input[curent_position..self.read_position])
+                        }
+                } else {
                 token = Self::new_token(TokenKind::SLASH, self.ch);
+                }
             }
             b'<' => {
                 token = Self::new_token(TokenKind::LT, self.ch);
@@ -136,6 +422,9 @@ impl<'a>  Lexer<'a>  {
             b';' => {
                 token = Self::new_token(TokenKind::SEMICOLON, self.ch);
             }
+            b'\n' => {
+                token = Self::new_token(TokenKind::NEWLINE, self.ch);
+            }
             b'(' => {
                 token = Self::new_token(TokenKind::LPAREN, self.ch);
             }
@@ -164,9 +453,24 @@ impl<'a>  Lexer<'a>  {
                 token = Self::new_token(TokenKind::COLON, self.ch);
             }
             b'"' => {
-                token = Token {
-                token_type: TokenKind::STRING,
-                literal: self.read_string()
+                let start = self.position;
+                // a string without the closing quote is illegal until the end of input like a raw string.
+                token = match self.read_string() {
+                    // a string with a malformed escape is illegal as a whole.
+                    Some(literal) => match Self::decode_escapes(&literal) {
+                        Some(decoded) => Token {
+                                         token_type: TokenKind::STRING,
+                                         literal: decoded
+                                         },
+                        None => Token {
+                                token_type: TokenKind::ILLEGAL,
+                                literal
+                                }
+                        },
+                    None => Token {
+                            token_type: TokenKind::ILLEGAL,
+                            literal: self.input[start..].to_string()
+                            }
                     }
             }
             0 => {
@@ -178,7 +482,9 @@ impl<'a>  Lexer<'a>  {
             _   => {
                     // if token is identifier or integer, read_char() is not implemented,
                     // because read_char() is implemented in read_identifier().
-                    if Self::is_letter(&self.ch) {
+                    if let Some(hashes) = self.raw_string_hashes() {
+                        token = self.read_raw_string(hashes);
+                    } else if Self::is_letter(&self.ch) {
                         let ident = self.read_identifier();
                         let ident_token = get_keyword(&ident);
                             token =  Token {
@@ -187,11 +493,7 @@ impl<'a>  Lexer<'a>  {
                             };
                      return token
                     } else if Self::is_digit(&self.ch) {
-                        token =  Token {
-                            token_type: TokenKind::INT,
-                            literal: self.read_number()
-                        };
-                        return token
+                        return self.read_number()
                     } else {
                     token = Self::new_token(TokenKind::ILLEGAL, self.ch);
                            }
@@ -202,11 +504,73 @@ impl<'a>  Lexer<'a>  {
     }
 }
 
+// lex the whole input and return each token with its line and column, not including EOF.
+pub fn tokenize_with_positions(input: &str) -> Vec<(Token, usize, usize)> {
+    let mut lexer = Lexer::new(input);
+    let mut tokens = vec![];
+    loop {
+        let token = lexer.next_token();
+        if token.token_type == TokenKind::EOF {
+            return tokens;
+        }
+        let (line, column) = lexer.line_column(lexer.token_position());
+        tokens.push((token, line, column));
+    }
+}
+
+// the kind of a token for syntax highlighting.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Category {
+    Keyword,
+    Operator,
+    Number,
+    String,
+    Ident,
+    Comment,
+    Punct,
+}
+
+// lex the whole input including comments and classify each token, not including EOF.
+// delimiters and illegal letters are Punct.
+pub fn highlight(input: &str) -> Vec<(Token, Category)> {
+    let mut lexer = Lexer::new(input).with_comment_tokens();
+    let mut tokens = vec![];
+    loop {
+        let token = lexer.next_token();
+        if token.token_type == TokenKind::EOF {
+            return tokens;
+        }
+        let category = category(token.token_type);
+        tokens.push((token, category));
+    }
+}
+
+fn category(kind: TokenKind) -> Category {
+    // every kind is listed, so that a new kind must be given its category.
+    match kind {
+        TokenKind::FUNCTION | TokenKind::LET | TokenKind::CONST | TokenKind::TRUE | TokenKind::FALSE |
+        TokenKind::IF | TokenKind::ELSE | TokenKind::RETURN | TokenKind::WHILE | TokenKind::BREAK |
+        TokenKind::DO | TokenKind::IMPORT | TokenKind::AS => Category::Keyword,
+        TokenKind::INT | TokenKind::FLOAT => Category::Number,
+        TokenKind::STRING => Category::String,
+        TokenKind::IDENT => Category::Ident,
+        TokenKind::COMMENT => Category::Comment,
+        TokenKind::ASSIGN | TokenKind::PLUS | TokenKind::MINUS | TokenKind::BANG | TokenKind::ASTERISK | TokenKind::SLASH |
+        TokenKind::IntDiv | TokenKind::LT | TokenKind::GT | TokenKind::EQ | TokenKind::NotEq | TokenKind::PIPE |
+        TokenKind::OptionalChain | TokenKind::DOT | TokenKind::NullCoalesce => Category::Operator,
+        TokenKind::COMMA | TokenKind::SEMICOLON | TokenKind::COLON | TokenKind::NEWLINE |
+        TokenKind::LPAREN | TokenKind::RPAREN | TokenKind::LBRACE | TokenKind::RBRACE |
+        TokenKind::LBRACKET | TokenKind::RBRACKET => Category::Punct,
+        // kinds which aren't highlighted as anything else.
+        TokenKind::ILLEGAL | TokenKind::EOF | TokenKind::LOWEST | TokenKind::DEFAULT => Category::Punct,
+    }
+}
+
 // if cfg(test) is written, test code is compiled only when test runs
 #[cfg(test)]// test runs only when execute cargo run
 mod testing {
-    use crate::lexer::Lexer;
-    use crate::token::TokenKind;
+    use crate::lexer::{Lexer, tokenize_with_positions, highlight, category, Category};
+    use crate::token::{Token, TokenKind, KEYWORDS, PRECEDENCES};
 
     #[test]
     fn test_next_token() {
@@ -331,4 +695,403 @@ if (5 < 10) {
         assert_eq!(_token.literal, test.1);
         }
     }
+
+    #[test]
+    fn test_remaining() {
+        let mut lexer = Lexer::new("let x");
+        assert_eq!(lexer.remaining(), "let x");
+        lexer.next_token();
+        assert_eq!(lexer.remaining(), " x");
+        lexer.next_token();
+        assert_eq!(lexer.remaining(), "");
+        lexer.next_token();
+        assert_eq!(lexer.remaining(), "");
+
+        let mut lexer = Lexer::new("(1 == 2)");
+        lexer.next_token();
+        assert_eq!(lexer.remaining(), "1 == 2)");
+        lexer.next_token();
+        lexer.next_token();
+        assert_eq!(lexer.remaining(), " 2)");
+    }
+
+    #[test]
+    fn test_new_from_str_types() {
+        let owned = String::from("let x = 1;");
+        let borrowed: &str = "let x = 1;";
+        let source = "let x = 1; let y = 2;";
+        let slice = &source[..10];
+        let expected: Vec<Token> = {
+            let mut lexer = Lexer::new(borrowed);
+            (0..6).map(|_| lexer.next_token()).collect()
+        };
+        for mut lexer in vec![Lexer::new(&owned), Lexer::new(borrowed), Lexer::new(slice), Lexer::new(&borrowed)].into_iter() {
+            let tokens: Vec<Token> = (0..6).map(|_| lexer.next_token()).collect();
+            assert_eq!(tokens, expected);
+        }
+        assert_eq!(expected[5].token_type, TokenKind::EOF);
+    }
+
+    #[test]
+    fn test_new_at() {
+        let input = r#"let add = fn(x, y){ x + y; };
+let result = add(5, "ten");
+[1, 2] == 10;"#;
+        // lex whole input and keep the offsets where each token is resumed from.
+        let mut lexer = Lexer::new(input);
+        let mut tokens = vec![];
+        loop {
+            let offset = lexer.position();
+            let token = lexer.next_token();
+            let is_eof = token.token_type == TokenKind::EOF;
+            tokens.push((offset, token));
+            if is_eof {
+                break;
+            }
+        }
+        for (i, (offset, _)) in tokens.iter().enumerate() {
+            let mut resumed = Lexer::new_at(input, *offset);
+            for (_, expected) in tokens[i..].iter() {
+                assert_eq!(resumed.next_token(), *expected);
+            }
+        }
+    }
+
+    #[test]
+    fn test_shebang() {
+        let input = "#!/usr/bin/env monkey\nlet x = 1;";
+        let mut lexer = Lexer::new(input);
+        let token = lexer.next_token();
+        assert_eq!(token.token_type, TokenKind::LET);
+        assert_eq!(lexer.line_column(lexer.token_position()), (2, 1));
+
+        // only the first line is a shebang, and '#!' anywhere else is a comment.
+        let mut lexer = Lexer::new("1;\n#!x");
+        let tokens: Vec<TokenKind> = (0..3).map(|_| lexer.next_token().token_type).collect();
+        assert_eq!(tokens, vec![TokenKind::INT, TokenKind::SEMICOLON, TokenKind::EOF]);
+
+        let mut lexer = Lexer::new("#!/usr/bin/env monkey");
+        assert_eq!(lexer.next_token().token_type, TokenKind::EOF);
+    }
+
+    #[test]
+    fn test_hash_comments() {
+        let input = "# a comment\nlet x = 1; # after a statement\n#\nx # ;";
+        let tests = vec![
+               (TokenKind::LET, "let"),
+               (TokenKind::IDENT, "x"),
+               (TokenKind::ASSIGN, "="),
+               (TokenKind::INT, "1"),
+               (TokenKind::SEMICOLON, ";"),
+               (TokenKind::IDENT, "x"),
+               (TokenKind::EOF, ""),
+               ];
+        let mut lexer = Lexer::new(input);
+        for (token_type, literal) in tests.into_iter() {
+            let token = lexer.next_token();
+            assert_eq!(token.token_type, token_type);
+            assert_eq!(token.literal, literal);
+        }
+
+        // the newline after a comment still ends a statement.
+        let mut lexer = Lexer::new("1 # comment\n2").with_newline_tokens();
+        let tokens: Vec<TokenKind> = (0..4).map(|_| lexer.next_token().token_type).collect();
+        assert_eq!(tokens, vec![TokenKind::INT, TokenKind::NEWLINE, TokenKind::INT, TokenKind::EOF]);
+    }
+
+    #[test]
+    fn test_tokenize_with_positions() {
+        let tokens = tokenize_with_positions("let x = 1;\n  x + 2");
+        assert_eq!(tokens.len(), 8);
+        let (token, line, column) = &tokens[5];
+        assert_eq!(token.token_type, TokenKind::IDENT);
+        assert_eq!(token.literal, "x");
+        assert_eq!((*line, *column), (2, 3));
+        let (token, line, column) = &tokens[7];
+        assert_eq!(token.literal, "2");
+        assert_eq!((*line, *column), (2, 7));
+        assert!(tokenize_with_positions("").is_empty());
+    }
+
+    #[test]
+    fn test_highlight() {
+        let categories = |input: &str| -> Vec<(String, Category)> {
+            highlight(input).into_iter().map(|(token, category)| (token.literal, category)).collect()
+        };
+        let expected = |tokens: Vec<(&str, Category)>| -> Vec<(String, Category)> {
+            tokens.into_iter().map(|(literal, category)| (literal.to_string(), category)).collect()
+        };
+        // '//' is integer division, not a comment.
+        assert_eq!(categories("let x = 5 // c"), expected(vec![
+            ("let", Category::Keyword),
+            ("x", Category::Ident),
+            ("=", Category::Operator),
+            ("5", Category::Number),
+            ("//", Category::Operator),
+            ("c", Category::Ident),
+            ]));
+        assert_eq!(categories("let s = \"a\"; # note\nf(true, 1.5)"), expected(vec![
+            ("let", Category::Keyword),
+            ("s", Category::Ident),
+            ("=", Category::Operator),
+            ("a", Category::String),
+            (";", Category::Punct),
+            ("# note", Category::Comment),
+            ("f", Category::Ident),
+            ("(", Category::Punct),
+            ("true", Category::Keyword),
+            (",", Category::Punct),
+            ("1.5", Category::Number),
+            (")", Category::Punct),
+            ]));
+        assert!(highlight("").is_empty());
+
+        // the categories agree with the keywords and the operators which the parser knows.
+        for (_, kind) in KEYWORDS.iter() {
+            assert_eq!(category(*kind), Category::Keyword, "{:?}", kind);
+        }
+        for (kind, _) in PRECEDENCES.iter() {
+            if *kind != TokenKind::LPAREN && *kind != TokenKind::LBRACKET {
+                assert_eq!(category(*kind), Category::Operator, "{:?}", kind);
+            }
+        }
+    }
+
+    #[test]
+    fn test_multi_line_strings() {
+        let tokens = tokenize_with_positions("let s = \"first\nsecond\";\nlet t = 1;");
+        let (token, line, column) = &tokens[3];
+        assert_eq!(token.token_type, TokenKind::STRING);
+        assert_eq!(token.literal, "first\nsecond");
+        assert_eq!((*line, *column), (1, 9));
+        let (token, line, column) = &tokens[4];
+        assert_eq!(token.token_type, TokenKind::SEMICOLON);
+        assert_eq!((*line, *column), (2, 8));
+        let (token, line, column) = &tokens[5];
+        assert_eq!(token.token_type, TokenKind::LET);
+        assert_eq!((*line, *column), (3, 1));
+
+        // a newline in a string doesn't end a statement.
+        let mut lexer = Lexer::new("\"a\r\nb\"\nc").with_newline_tokens();
+        assert_eq!(lexer.next_token(), Token{token_type: TokenKind::STRING, literal: String::from("a\r\nb")});
+        assert_eq!(lexer.next_token().token_type, TokenKind::NEWLINE);
+        assert_eq!(lexer.next_token().token_type, TokenKind::IDENT);
+        assert_eq!(lexer.line_column(lexer.token_position()), (3, 1));
+
+        // a string without the closing quote is illegal until the end of input.
+        let tests = vec![
+               ("\"abc", "\"abc"),
+               ("let s = \"a\nb;", "\"a\nb;"),
+               ("\"a\\\"", "\"a\\\""),
+               ];
+        for (input, literal) in tests.into_iter() {
+            let mut lexer = Lexer::new(input);
+            let mut token = lexer.next_token();
+            while token.token_type != TokenKind::ILLEGAL && token.token_type != TokenKind::EOF {
+                token = lexer.next_token();
+            }
+            assert_eq!(token, Token{token_type: TokenKind::ILLEGAL, literal: String::from(literal)}, "{}", input);
+            assert_eq!(lexer.next_token().token_type, TokenKind::EOF, "{}", input);
+        }
+    }
+
+    #[test]
+    fn test_unicode_escapes() {
+        let tests = vec![
+               (r#""\u{41}""#, TokenKind::STRING, "A"),
+               (r#""caf\u{e9}""#, TokenKind::STRING, "café"),
+               (r#""\u{1F600}!""#, TokenKind::STRING, "😀!"),
+               (r#""a\nb""#, TokenKind::STRING, "a\nb"),
+               (r#""a\tb""#, TokenKind::STRING, "a\tb"),
+               (r#""say \"hi\"""#, TokenKind::STRING, "say \"hi\""),
+               (r#""C:\\u{41}""#, TokenKind::STRING, "C:\\u{41}"),
+               // a backslash followed by another letter is kept as it is.
+               (r#""a\qb""#, TokenKind::STRING, "a\\qb"),
+               (r#""\u{41""#, TokenKind::ILLEGAL, "\\u{41"),
+               (r#""\u41""#, TokenKind::ILLEGAL, "\\u41"),
+               (r#""\u{}""#, TokenKind::ILLEGAL, "\\u{}"),
+               (r#""\u{XYZ}""#, TokenKind::ILLEGAL, "\\u{XYZ}"),
+               (r#""\u{110000}""#, TokenKind::ILLEGAL, "\\u{110000}"),
+               (r#""\u{D800}""#, TokenKind::ILLEGAL, "\\u{D800}"),
+               ];
+        for (input, token_type, literal) in tests.into_iter() {
+            let token = Lexer::new(input).next_token();
+            assert_eq!(token.token_type, token_type, "{}", input);
+            assert_eq!(token.literal, literal, "{}", input);
+        }
+    }
+
+    #[test]
+    fn test_raw_strings() {
+        let tests = vec![
+               (r#"r"C:\path\no\escapes""#, TokenKind::STRING, r"C:\path\no\escapes"),
+               // \u{...} is decoded only in a string which isn't raw.
+               (r#"r"\u{41}""#, TokenKind::STRING, r"\u{41}"),
+               (r#""\u{41}""#, TokenKind::STRING, "A"),
+               (r#"r"\n""#, TokenKind::STRING, r"\n"),
+               (r#"r"""#, TokenKind::STRING, ""),
+               (r##"r#"say "hi""#"##, TokenKind::STRING, r#"say "hi""#),
+               (r###"r##"a "# b"##"###, TokenKind::STRING, r##"a "# b"##),
+               (r#"r"unterminated"#, TokenKind::ILLEGAL, r#"r"unterminated"#),
+               (r##"r#"only one quote""##, TokenKind::ILLEGAL, r##"r#"only one quote""##),
+               ];
+        for (input, token_type, literal) in tests.into_iter() {
+            let token = Lexer::new(input).next_token();
+            assert_eq!(token.token_type, token_type, "{}", input);
+            assert_eq!(token.literal, literal, "{}", input);
+        }
+        // a backslash in a raw string is a letter, but it is an escape in a string which isn't raw.
+        assert_eq!(Lexer::new(r#"r"\n""#).next_token().literal.len(), 2);
+        assert_eq!(Lexer::new(r#""\n""#).next_token().literal.len(), 1);
+
+        // r is still an identifier unless a string follows it.
+        let tests = vec![
+               (r#"r"a" + r"#, vec![(TokenKind::STRING, "a"), (TokenKind::PLUS, "+"), (TokenKind::IDENT, "r")]),
+               (r##"r#"a"# r#x"##, vec![(TokenKind::STRING, "a"), (TokenKind::IDENT, "r")]),
+               (r#"rest(r)"#, vec![(TokenKind::IDENT, "rest"), (TokenKind::LPAREN, "("), (TokenKind::IDENT, "r"), (TokenKind::RPAREN, ")")]),
+               ];
+        for (input, expected) in tests.into_iter() {
+            let tokens: Vec<(TokenKind, String)> = tokenize_with_positions(input).into_iter().map(|(token, _, _)| (token.token_type, token.literal)).collect();
+            let expected: Vec<(TokenKind, String)> = expected.into_iter().map(|(kind, literal)| (kind, literal.to_string())).collect();
+            assert_eq!(tokens, expected, "{}", input);
+        }
+    }
+
+    #[test]
+    fn test_next_token_checked() {
+        let mut lexer = Lexer::new("x");
+        assert_eq!(lexer.next_token_checked().map(|token| token.token_type), Some(TokenKind::IDENT));
+        assert_eq!(lexer.next_token_checked().map(|token| token.token_type), Some(TokenKind::EOF));
+        assert_eq!(lexer.next_token_checked(), None);
+        assert_eq!(lexer.next_token_checked(), None);
+
+        // next_token keeps returning EOF.
+        let mut lexer = Lexer::new("");
+        assert_eq!(lexer.next_token().token_type, TokenKind::EOF);
+        assert_eq!(lexer.next_token().token_type, TokenKind::EOF);
+    }
+
+    #[test]
+    fn test_int_div() {
+        let tests = vec![
+               (TokenKind::INT, "7"),
+               (TokenKind::IntDiv, "//"),
+               (TokenKind::INT, "2"),
+               (TokenKind::SLASH, "/"),
+               (TokenKind::INT, "3"),
+               (TokenKind::EOF, ""),
+               ];
+        let mut lexer = Lexer::new("7 // 2 / 3");
+        for (token_type, literal) in tests.into_iter() {
+            let token = lexer.next_token();
+            assert_eq!(token.token_type, token_type);
+            assert_eq!(token.literal, literal);
+        }
+    }
+
+    #[test]
+    fn test_pipe() {
+        let tests = vec![
+               (TokenKind::IDENT, "x"),
+               (TokenKind::PIPE, "|>"),
+               (TokenKind::IDENT, "f"),
+               (TokenKind::ILLEGAL, "|"),
+               (TokenKind::GT, ">"),
+               (TokenKind::EOF, ""),
+               ];
+        let mut lexer = Lexer::new("x |> f | >");
+        for (token_type, literal) in tests.into_iter() {
+            let token = lexer.next_token();
+            assert_eq!(token.token_type, token_type);
+            assert_eq!(token.literal, literal);
+        }
+    }
+
+    #[test]
+    fn test_optional_chain() {
+        let tests = vec![
+               (TokenKind::IDENT, "a"),
+               (TokenKind::OptionalChain, "?."),
+               (TokenKind::LBRACKET, "["),
+               (TokenKind::INT, "0"),
+               (TokenKind::RBRACKET, "]"),
+               (TokenKind::NullCoalesce, "??"),
+               (TokenKind::ILLEGAL, "?"),
+               (TokenKind::IDENT, "b"),
+               (TokenKind::EOF, ""),
+               ];
+        let mut lexer = Lexer::new("a?.[0] ?? ?b");
+        for (token_type, literal) in tests.into_iter() {
+            let token = lexer.next_token();
+            assert_eq!(token.token_type, token_type);
+            assert_eq!(token.literal, literal);
+        }
+    }
+
+    #[test]
+    fn test_dot() {
+        let tests = vec![
+               (TokenKind::IDENT, "m"),
+               (TokenKind::DOT, "."),
+               (TokenKind::IDENT, "pi"),
+               (TokenKind::FLOAT, "1.5"),
+               (TokenKind::EOF, ""),
+               ];
+        let mut lexer = Lexer::new("m.pi 1.5");
+        for (token_type, literal) in tests.into_iter() {
+            let token = lexer.next_token();
+            assert_eq!(token.token_type, token_type);
+            assert_eq!(token.literal, literal);
+        }
+    }
+
+    #[test]
+    fn test_float_literals() {
+        let input = "3.14 1e3 2.5e-2 1E6 7e+1 1.x 1e 1e+ 1e-;";
+        let tests = vec![
+               (TokenKind::FLOAT, "3.14"),
+               (TokenKind::FLOAT, "1e3"),
+               (TokenKind::FLOAT, "2.5e-2"),
+               (TokenKind::FLOAT, "1E6"),
+               (TokenKind::FLOAT, "7e+1"),
+               (TokenKind::INT, "1"),
+               (TokenKind::DOT, "."),
+               (TokenKind::IDENT, "x"),
+               (TokenKind::ILLEGAL, "1e"),
+               (TokenKind::ILLEGAL, "1e+"),
+               (TokenKind::ILLEGAL, "1e-"),
+               (TokenKind::SEMICOLON, ";"),
+               (TokenKind::EOF, ""),
+               ];
+        let mut lexer = Lexer::new(input);
+        for (token_type, literal) in tests.into_iter() {
+            let token = lexer.next_token();
+            assert_eq!(token.token_type, token_type);
+            assert_eq!(token.literal, literal);
+        }
+    }
+
+    #[test]
+    fn test_boolean_keywords() {
+        // a keyword is a whole identifier, so a longer name which starts or ends with it is IDENT.
+        let input = "true false trueish false_ untrue True !true;";
+        let tests = vec![
+               (TokenKind::TRUE, "true"),
+               (TokenKind::FALSE, "false"),
+               (TokenKind::IDENT, "trueish"),
+               (TokenKind::IDENT, "false_"),
+               (TokenKind::IDENT, "untrue"),
+               (TokenKind::IDENT, "True"),
+               (TokenKind::BANG, "!"),
+               (TokenKind::TRUE, "true"),
+               (TokenKind::SEMICOLON, ";"),
+               (TokenKind::EOF, ""),
+               ];
+        let mut lexer = Lexer::new(input);
+        for (token_type, literal) in tests.into_iter() {
+            let token = lexer.next_token();
+            assert_eq!(token.token_type, token_type);
+            assert_eq!(token.literal, literal);
+        }
+    }
 }
\ No newline at end of file
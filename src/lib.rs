@@ -6,4 +6,13 @@ mod parser;
 mod errors;
 mod object;
 mod evaluator;
-mod builtins;
\ No newline at end of file
+mod builtins;
+mod cli;
+mod lint;
+mod ast_json;
+mod resolver;
+mod invariant;
+mod typecheck;
+mod metrics;
+mod visit;
+mod format;
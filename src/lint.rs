@@ -0,0 +1,248 @@
+use std::collections::{BTreeSet, HashMap};
+
+use super::ast::{Program, Statement, Expression};
+use super::errors::{Errors};
+use super::evaluator::{hoisted_functions};
+
+// report let bindings which are never referenced after they are declared.
+// a function body is a new scope, and its parameters hide outer bindings of the same name.
+pub fn unused_lets(program: &Program) -> Vec<Errors> {
+    let mut linter = Linter::new();
+    linter.hoist(&program.statements);
+    linter.walk_statements(&program.statements);
+    linter.bindings.iter()
+                   .filter(|binding| binding.reportable && !binding.used)
+                   .map(|binding| Errors::UnusedVariable(binding.name.clone()))
+                   .collect()
+}
+
+struct Binding {
+    name: String,
+    used: bool,
+    // parameters and constants aren't reported, but they hide outer bindings.
+    reportable: bool,
+}
+
+// the bindings which each name may refer to.
+// a name can refer to more than one binding after if or while,
+// because it isn't known which branch is executed.
+type Scope = HashMap<String, BTreeSet<usize>>;
+
+struct Linter {
+    bindings: Vec<Binding>,
+    scopes: Vec<Scope>,
+    // a statement in a loop is walked twice to find references from the next iteration,
+    // so the binding is kept per statement to be declared only once.
+    declared: HashMap<*const Statement, usize>,
+}
+
+impl Linter {
+    fn new() -> Linter {
+        Linter{bindings: Vec::new(), scopes: vec![Scope::new()], declared: HashMap::new()}
+    }
+
+    // functions bound at the top level can call the ones bound after them,
+    // so the bindings of the hoisted functions are declared before walking the statements.
+    fn hoist(&mut self, statements: &[Statement]) {
+        let names = hoisted_functions(statements);
+        for statement in statements.iter() {
+            let statement = statement.without_comments();
+            let (identifier, reportable) = match statement {
+                Statement::LetStatement{identifier, ..} => (identifier, true),
+                Statement::ConstStatement{identifier, ..} => (identifier, false),
+                _ => continue
+            };
+            if let Expression::Identifier(name) = identifier {
+                if names.contains(name) {
+                    self.declare(statement, identifier, reportable);
+                }
+            }
+        }
+    }
+
+    fn walk_statements(&mut self, statements: &[Statement]) {
+        for statement in statements.iter() {
+            self.walk_statement(statement);
+        }
+    }
+
+    fn walk_statement(&mut self, statement: &Statement) {
+        match statement {
+            Statement::LetStatement{identifier, value, ..} => {
+                // the value is walked first, because `let x = x + 1` refers to the previous x.
+                self.walk_expression(value);
+                self.declare(statement, identifier, true);
+            },
+            Statement::ConstStatement{identifier, value} => {
+                self.walk_expression(value);
+                self.declare(statement, identifier, false);
+            },
+            // the target is also walked, because assigning into a[0] reads a.
+            Statement::Assign{target, value} => {
+                self.walk_expression(value);
+                self.walk_expression(target);
+            },
+            Statement::Return(value) => self.walk_expression(value),
+            Statement::Break(Some(value)) => self.walk_expression(value),
+            Statement::Break(None) | Statement::Import{..} => {},
+            Statement::ExpressionStatement(value) => self.walk_expression(value),
+            Statement::Block(statements) => self.walk_statements(statements),
+            Statement::Commented{statement, ..} => self.walk_statement(statement),
+        }
+    }
+
+    fn walk_expression(&mut self, expression: &Expression) {
+        match expression {
+            Expression::Identifier(name) => self.refer(name),
+            Expression::ResolvedIdentifier{name, ..} => self.refer(name),
+            Expression::Array(values) => {
+                for value in values.iter() {
+                    self.walk_expression(value);
+                }
+            },
+            Expression::Hashmap(tree) => {
+                for (key, value) in tree.iter() {
+                    self.walk_expression(key);
+                    self.walk_expression(value);
+                }
+            },
+            Expression::IndexExpression{array, subscript} | Expression::OptionalIndexExpression{array, subscript} => {
+                self.walk_expression(array);
+                self.walk_expression(subscript);
+            },
+            Expression::PrefixExpression{right_expression, ..} => self.walk_expression(right_expression),
+            Expression::InfixExpression{left_expression, right_expression, ..} => {
+                self.walk_expression(left_expression);
+                self.walk_expression(right_expression);
+            },
+            Expression::IfExpression{condition, consequence, alternative} => {
+                self.walk_expression(condition);
+                let before = self.current_scope().clone();
+                self.walk_statement(consequence);
+                let after_consequence = std::mem::replace(self.current_scope(), before);
+                if let Some(alternative) = alternative {
+                    self.walk_statement(alternative);
+                }
+                self.merge(after_consequence);
+            },
+            Expression::WhileExpression{condition, body} => {
+                self.walk_expression(condition);
+                // the second walk finds bindings which are used in the next iteration.
+                for _ in 0..2 {
+                    let before = self.current_scope().clone();
+                    self.walk_statement(body);
+                    self.walk_expression(condition);
+                    self.merge(before);
+                }
+            },
+            Expression::DoExpression{body} => self.walk_statement(body),
+            Expression::FunctionLiteral{parameters, body} => {
+                self.scopes.push(Scope::new());
+                for parameter in parameters.iter() {
+                    self.declare_name(parameter, false);
+                }
+                self.walk_statement(body);
+                self.scopes.pop();
+            },
+            Expression::CallExpression{function, body} => {
+                self.walk_expression(function);
+                for argument in body.iter() {
+                    self.walk_expression(argument);
+                }
+            },
+            _ => {}
+        }
+    }
+
+    fn current_scope(&mut self) -> &mut Scope {
+        self.scopes.last_mut().unwrap()
+    }
+
+    fn declare(&mut self, statement: &Statement, identifier: &Expression, reportable: bool) {
+        let name = match identifier {
+            Expression::Identifier(name) => name.clone(),
+            _ => return
+        };
+        let key = statement as *const Statement;
+        let index = match self.declared.get(&key) {
+            Some(index) => *index,
+            None => {
+                self.bindings.push(Binding{name: name.clone(), used: false, reportable: reportable});
+                self.declared.insert(key, self.bindings.len() - 1);
+                self.bindings.len() - 1
+            }
+        };
+        self.current_scope().insert(name, vec![index].into_iter().collect());
+    }
+
+    fn declare_name(&mut self, identifier: &Expression, reportable: bool) {
+        if let Expression::Identifier(name) = identifier {
+            self.bindings.push(Binding{name: name.clone(), used: false, reportable: reportable});
+            let index = self.bindings.len() - 1;
+            self.current_scope().insert(name.clone(), vec![index].into_iter().collect());
+        }
+    }
+
+    // mark bindings which the name may refer to in the innermost scope that has it.
+    fn refer(&mut self, name: &str) {
+        for scope in self.scopes.iter().rev() {
+            if let Some(indexes) = scope.get(name) {
+                for index in indexes.iter() {
+                    self.bindings[*index].used = true;
+                }
+                return
+            }
+        }
+    }
+
+    fn merge(&mut self, other: Scope) {
+        let scope = self.current_scope();
+        for (name, indexes) in other.into_iter() {
+            scope.entry(name).or_default().extend(indexes);
+        }
+    }
+}
+
+#[cfg(test)]// test runs only when execute cargo run
+mod testing {
+    use crate::parser::{Parser};
+    use crate::errors::{Errors};
+    use crate::lint::{unused_lets};
+
+    fn lint(input: &str) -> Vec<Errors> {
+        let mut parser = Parser::new_from_source(input);
+        unused_lets(&parser.parse_program().unwrap())
+    }
+
+    #[test]
+    fn test_unused_lets() {
+        let tests = vec![
+            ("let x = 1; 2", vec!["x"]),
+            ("let x = 1; x", vec![]),
+            ("let x = 1; let y = 2; y", vec!["x"]),
+            // shadowing
+            ("let x = 1; let x = 2; x", vec!["x"]),
+            ("let x = 1; let x = x + 1; x", vec![]),
+            // function parameters hide outer bindings.
+            ("let x = 1; let f = fn(x) { x }; f(2)", vec!["x"]),
+            ("let x = 1; let f = fn() { x }; f()", vec![]),
+            ("let f = fn(x) { let y = x; 2 }; f(1)", vec!["y"]),
+            ("let f = fn(x) { 2 }; f(1)", vec![]),
+            // branches and loops
+            ("let x = 1; if (true) { let x = 2; } x", vec![]),
+            ("let x = 1; if (true) { let x = 2; } else { let x = 3; } x", vec!["x"]),
+            ("let i = 0; while (i < 3) { let i = i + 1; }", vec![]),
+            ("let i = 0; while (i < 3) { let j = i; let i = i + 1; }", vec!["j"]),
+            ("const x = 1; 2", vec![]),
+            // a function at the top level can call one bound after it.
+            ("let f = fn() { g() }; let g = fn() { 1 }; f()", vec![]),
+            ("let isEven = fn(n) { if (n == 0) { true } else { isOdd(n - 1) } }; \
+              let isOdd = fn(n) { if (n == 0) { false } else { isEven(n - 1) } }; isEven(4)", vec![]),
+            ("let f = fn() { 1 }; let g = fn() { 2 }; f()", vec!["g"]),
+            ];
+        for (input, expected) in tests.into_iter() {
+            let expected: Vec<Errors> = expected.into_iter().map(|name| Errors::UnusedVariable(name.to_string())).collect();
+            assert_eq!(lint(input), expected, "{}", input);
+        }
+    }
+}
@@ -7,8 +7,24 @@ mod errors;
 mod evaluator;
 mod object;
 mod builtins;
+mod cli;
+mod lint;
+mod ast_json;
+mod resolver;
+mod invariant;
+mod typecheck;
+mod metrics;
+mod visit;
+mod format;
+
+use std::env;
+use std::process;
 
 fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    if !args.is_empty() {
+        process::exit(cli::run_cli(&args));
+    }
     println!("Hello! This is the Monkey programming language!");
     println!("Feel free to type in commands");
     println!("");
@@ -0,0 +1,81 @@
+use super::ast::{Program, Statement, Expression};
+use super::visit::{Visitor, walk_statement, walk_expression};
+
+// the number of statements and expressions in a program, including identifiers
+// which are bound like parameters and the names of let.
+// a block counts as a statement, so `if (x) { 1 }` has the if, x, the block and 1.
+pub fn node_count(program: &Program) -> usize {
+    measure(program).count
+}
+
+// the number of nodes on the longest way from a statement at the top level to a leaf,
+// so `1` is 2 deep with the expression statement and `1 + 2 * 3` is 4 deep.
+pub fn max_depth(program: &Program) -> usize {
+    measure(program).max_depth
+}
+
+#[derive(Default)]
+struct Metrics {
+    count: usize,
+    max_depth: usize,
+    // the depth of the node being visited.
+    depth: usize,
+}
+
+fn measure(program: &Program) -> Metrics {
+    let mut metrics = Metrics::default();
+    for statement in program.statements.iter() {
+        metrics.visit_statement(statement);
+    }
+    metrics
+}
+
+impl Metrics {
+    fn enter(&mut self) {
+        self.depth += 1;
+        self.count += 1;
+        self.max_depth = self.max_depth.max(self.depth);
+    }
+}
+
+impl Visitor for Metrics {
+    fn visit_statement(&mut self, statement: &Statement) {
+        self.enter();
+        walk_statement(self, statement);
+        self.depth -= 1;
+    }
+
+    fn visit_expression(&mut self, expression: &Expression) {
+        self.enter();
+        walk_expression(self, expression);
+        self.depth -= 1;
+    }
+}
+
+#[cfg(test)]// test runs only when execute cargo run
+mod testing {
+    use crate::parser::{Parser};
+    use crate::metrics::{node_count, max_depth};
+
+    #[test]
+    fn test_node_count() {
+        let tests = vec![
+                // the statement, +, 1, * , 2 and 3.
+                ("1 + 2 * 3", 6, 4),
+                ("1", 2, 2),
+                ("", 0, 0),
+                ("let x = 1; x", 5, 2),
+                ("[1, {\"a\": 2}]", 6, 4),
+                // the statement, if, x, the block, the statement in it and 1.
+                ("if (x) { 1 }", 6, 5),
+                // the statement, fn, x, the block, the statement in it and x.
+                ("fn(x) { x }", 6, 5),
+                ("f(1, 2)", 5, 3),
+                ];
+        for (input, count, depth) in tests.into_iter() {
+            let program = Parser::new_from_source(input).parse_program().unwrap();
+            assert_eq!(node_count(&program), count, "{}", input);
+            assert_eq!(max_depth(&program), depth, "{}", input);
+        }
+    }
+}
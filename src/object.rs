@@ -1,27 +1,53 @@
 use std::fmt;
+use std::mem;
+use std::cmp::Ordering;
 use std::collections::BTreeMap;
+use std::rc::Rc;
 
-use super::evaluator::{Environment};
-use super::ast::{Expression, Statement};
+use super::evaluator::{Environment, Input, Rng};
+use super::ast::{Expression, Statement, Float};
 use super::errors::{Errors};
 
-#[derive(Debug,PartialEq, Clone, Eq, Ord, PartialOrd)]
+// objects are ordered by the value inside if they are the same type,
+// otherwise by the type in the order of
+// null < boolean < number < string < array < hash < function < builtin.
+// an integer and a float are ordered by their values, and 1 < 1.0.
+#[derive(Debug,PartialEq, Clone, Eq)]
 pub enum Object {
     Identifier(String),
-    String(String),
+    // a string shares its storage with the copies of it, and string literals are interned by the evaluator,
+    // so that copying one doesn't allocate and comparing the same string is a pointer comparison.
+    String(Rc<str>),
     Integer(i32),
+    Float(Float),
     Boolean(bool),
     Return(Box<Object>),
+    Break(Box<Object>),
     Let(Box<Object>),
     Array(Vec<Object>),
     Hash(BTreeMap<Box<HashKey>, Box<HashPair>>),
     Function{params: Vec<Expression>,
-             body: Statement,
+             body: Rc<Statement>,
              env: Environment
             },
     Builtin{
         func: fn(Vec<Object>) -> Object
        },
+    // the read_line builtin, which reads a line from the input of the config.
+    ReadLine{input: Input},
+    // the rand builtin, or rand_range if ranged, which draw numbers from the generator of the config.
+    Rand{rng: Rng,
+         ranged: bool
+        },
+    // a function which calls outer with the result of inner, made by compose().
+    Composed{outer: Box<Object>,
+             inner: Box<Object>
+            },
+    // a function made by curry(), which keeps arguments until arity of them are given.
+    Partial{function: Box<Object>,
+            arity: usize,
+            args: Vec<Object>
+           },
     Error(Errors),
     Null,
     Default
@@ -33,8 +59,10 @@ impl fmt::Display for Object {
            Object::Identifier(value) => write!(f, "{}", value),
            Object::String(value) => write!(f, "{}", value),
            Object::Integer(value) => write!(f, "{}", value),
+           Object::Float(value) => write!(f, "{}", value),
            Object::Boolean(value) => write!(f, "{}", value),
            Object::Return(value) => write!(f, "{}", value),
+           Object::Break(value) => write!(f, "{}", value),
            Object::Let(value) => write!(f, "{}", value),
            Object::Hash(tree) => {
             match tree {
@@ -42,11 +70,15 @@ impl fmt::Display for Object {
                 _ =>  unreachable!()}
             },
            Object::Array(value) => write!(f, "[{}]", value.iter().map(|expression| format!("{}", &expression)).collect::<Vec<_>>().join(", ")),
-           Object::Function{params, body, env} => write!(f, "{} {} {:?}", params.iter().map(|expression| format!("{}", &expression)).collect::<Vec<_>>().join(", ")
+           Object::Function{params, body, env: _} => write!(f, "fn ({}) {{{}}}", params.iter().map(|expression| format!("{}", &expression)).collect::<Vec<_>>().join(", ")
                                                                         , body
-                                                                        , env
                                                         ),
            Object::Builtin{func: _} => write!(f, "builtin functions"),
+           Object::ReadLine{..} => write!(f, "builtin functions"),
+           Object::Rand{..} => write!(f, "builtin functions"),
+           Object::Composed{outer, inner} => write!(f, "compose({}, {})", outer, inner),
+           Object::Partial{function, arity, args} => write!(f, "curry({}, {})({})", function, arity,
+                                                            args.iter().map(|arg| format!("{}", arg)).collect::<Vec<_>>().join(", ")),
            Object::Null => write!(f, ""),
            Object::Default => write!(f, "default"),
            Object::Error(value) => write!(f, "{}", value)
@@ -54,6 +86,165 @@ impl fmt::Display for Object {
     }
 }
 
+impl Object {
+    // the name of the type which is shown in error messages.
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Object::Identifier(_) => "IDENTIFIER",
+            Object::String(_) => "STRING",
+            Object::Integer(_) => "INTEGER",
+            Object::Float(_) => "FLOAT",
+            Object::Boolean(_) => "BOOLEAN",
+            Object::Return(_) => "RETURN_VALUE",
+            Object::Break(_) => "BREAK_VALUE",
+            Object::Let(_) => "LET",
+            Object::Array(_) => "ARRAY",
+            Object::Hash(_) => "HASH",
+            Object::Function{..} => "FUNCTION",
+            Object::Builtin{..} => "BUILTIN",
+            Object::ReadLine{..} => "BUILTIN",
+            Object::Rand{..} => "BUILTIN",
+            Object::Composed{..} => "FUNCTION",
+            Object::Partial{..} => "FUNCTION",
+            Object::Error(_) => "ERROR",
+            Object::Null => "NULL",
+            Object::Default => "DEFAULT",
+        }
+    }
+}
+
+impl Object {
+    // the position of the type in the ordering between different types.
+    fn type_rank(&self) -> u8 {
+        match self {
+            Object::Null => 0,
+            Object::Boolean(_) => 1,
+            Object::Integer(_) => 2,
+            Object::Float(_) => 2,
+            Object::String(_) => 3,
+            Object::Array(_) => 4,
+            Object::Hash(_) => 5,
+            Object::Function{..} => 6,
+            Object::Builtin{..} => 7,
+            Object::ReadLine{..} => 8,
+            Object::Rand{..} => 9,
+            Object::Composed{..} => 10,
+            Object::Partial{..} => 11,
+            Object::Identifier(_) => 12,
+            Object::Return(_) => 13,
+            Object::Break(_) => 14,
+            Object::Let(_) => 15,
+            Object::Error(_) => 16,
+            Object::Default => 17,
+        }
+    }
+}
+
+// limits of inspect(), so that printing a huge or deeply nested value doesn't flood the output.
+#[derive(Debug, PartialEq, Clone, Eq)]
+pub struct InspectConfig {
+    // arrays and hashes nested deeper than this are shown as [...] or {...}.
+    pub max_depth: usize,
+    // elements of an array or pairs of a hash after this many are shown as ....
+    pub max_elements: usize,
+}
+
+impl Default for InspectConfig {
+    fn default() -> InspectConfig {
+        InspectConfig{max_depth: 8, max_elements: 100}
+    }
+}
+
+impl Object {
+    // the same as Display, but arrays and hashes are cut off at the limits of config.
+    // a value can't contain itself, because a[0] = a puts a copy of a into a,
+    // so a value is a tree and printing it always ends. deep nesting made by repeating it is cut off at max_depth.
+    pub fn inspect(&self, config: &InspectConfig) -> String {
+        self.inspect_at(config, 0)
+    }
+
+    fn inspect_at(&self, config: &InspectConfig, depth: usize) -> String {
+        match self {
+            Object::Array(_) if depth >= config.max_depth => String::from("[...]"),
+            Object::Hash(_) if depth >= config.max_depth => String::from("{...}"),
+            Object::Array(values) => {
+                let elements = values.iter().map(|value| value.inspect_at(config, depth + 1));
+                format!("[{}]", Self::elide(elements, values.len(), config))
+            },
+            Object::Hash(tree) => {
+                let pairs = tree.iter().map(|(key, pair)| format!("{}: {}", key, pair.value.inspect_at(config, depth + 1)));
+                format!("{{{}}}", Self::elide(pairs, tree.len(), config))
+            },
+            _ => format!("{}", self)
+        }
+    }
+
+    fn elide(elements: impl Iterator<Item=String>, length: usize, config: &InspectConfig) -> String {
+        let mut shown: Vec<String> = elements.take(config.max_elements).collect();
+        if length > config.max_elements {
+            shown.push(String::from("..."));
+        }
+        shown.join(", ")
+    }
+
+    // a rough number of bytes which the object uses, to show how much memory values cost.
+    // the object itself is counted with strings, elements and pairs inside it,
+    // but environments captured by functions aren't counted.
+    pub fn approx_size(&self) -> usize {
+        let inner = match self {
+            Object::Identifier(value) => value.len(),
+            Object::String(value) => value.len(),
+            Object::Return(value) | Object::Break(value) | Object::Let(value) => value.approx_size(),
+            Object::Array(values) => values.iter().map(|value| value.approx_size()).sum(),
+            Object::Hash(pairs) => pairs.iter().map(|(key, pair)| key.approx_size() + pair.key.approx_size() + pair.value.approx_size()).sum(),
+            Object::Composed{outer, inner} => outer.approx_size() + inner.approx_size(),
+            Object::Partial{function, args, ..} => function.approx_size() + args.iter().map(|arg| arg.approx_size()).sum::<usize>(),
+            _ => 0
+        };
+        mem::size_of::<Object>() + inner
+    }
+}
+
+impl Ord for Object {
+    fn cmp(&self, other: &Object) -> Ordering {
+        match (self, other) {
+            (Object::Boolean(left), Object::Boolean(right)) => left.cmp(right),
+            (Object::Integer(left), Object::Integer(right)) => left.cmp(right),
+            (Object::Float(left), Object::Float(right)) => left.cmp(right),
+            // an integer is placed just before the float of the same value.
+            (Object::Integer(left), Object::Float(right)) => Float(*left as f64).cmp(right).then(Ordering::Less),
+            (Object::Float(left), Object::Integer(right)) => left.cmp(&Float(*right as f64)).then(Ordering::Greater),
+            (Object::String(left), Object::String(right)) => left.cmp(right),
+            (Object::Array(left), Object::Array(right)) => left.cmp(right),
+            (Object::Hash(left), Object::Hash(right)) => left.cmp(right),
+            (Object::Function{params: left_params, body: left_body, env: left_env},
+             Object::Function{params: right_params, body: right_body, env: right_env}) =>
+                (left_params, left_body, left_env).cmp(&(right_params, right_body, right_env)),
+            (Object::Builtin{func: left}, Object::Builtin{func: right}) => left.cmp(right),
+            (Object::ReadLine{input: left}, Object::ReadLine{input: right}) => left.cmp(right),
+            (Object::Rand{rng: left_rng, ranged: left_ranged}, Object::Rand{rng: right_rng, ranged: right_ranged}) =>
+                (left_rng, left_ranged).cmp(&(right_rng, right_ranged)),
+            (Object::Composed{outer: left_outer, inner: left_inner}, Object::Composed{outer: right_outer, inner: right_inner}) =>
+                (left_outer, left_inner).cmp(&(right_outer, right_inner)),
+            (Object::Partial{function: left_function, arity: left_arity, args: left_args},
+             Object::Partial{function: right_function, arity: right_arity, args: right_args}) =>
+                (left_function, left_arity, left_args).cmp(&(right_function, right_arity, right_args)),
+            (Object::Identifier(left), Object::Identifier(right)) => left.cmp(right),
+            (Object::Return(left), Object::Return(right)) => left.cmp(right),
+            (Object::Break(left), Object::Break(right)) => left.cmp(right),
+            (Object::Let(left), Object::Let(right)) => left.cmp(right),
+            (Object::Error(left), Object::Error(right)) => left.cmp(right),
+            (left, right) => left.type_rank().cmp(&right.type_rank()),
+        }
+    }
+}
+
+impl PartialOrd for Object {
+    fn partial_cmp(&self, other: &Object) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
 #[derive(Debug,PartialEq, Clone, Eq, Ord, PartialOrd)]
 pub struct HashPair {
     pub key: Object,
@@ -86,13 +277,84 @@ impl fmt::Display for HashKey {
 }
 
 impl HashKey {
+    fn approx_size(&self) -> usize {
+        match self {
+            HashKey::String(value) => mem::size_of::<HashKey>() + value.len(),
+            _ => mem::size_of::<HashKey>()
+        }
+    }
+
     pub fn get_hashkey(key: &Object) -> Self {
         match key {
             Object::Integer(key) => HashKey::Integer(*key),
-            Object::String(key) => HashKey::String(key.clone()),
+            Object::String(key) => HashKey::String(key.to_string()),
             Object::Boolean(key) => HashKey::Boolean(*key),
             _ => HashKey::Null
         }
     }
 }
 
+#[cfg(test)]// test runs only when execute cargo run
+mod testing {
+    use crate::object::{Object, InspectConfig};
+    use crate::ast::Float;
+
+    #[test]
+    fn test_ordering_in_same_type() {
+        assert!(Object::Integer(-1) < Object::Integer(2));
+        assert!(Object::String("apple".into()) < Object::String("banana".into()));
+        assert!(Object::String("Z".into()) < Object::String("a".into()));
+        assert!(Object::Boolean(false) < Object::Boolean(true));
+        assert!(Object::Array(vec![Object::Integer(1)]) < Object::Array(vec![Object::Integer(1), Object::Integer(0)]));
+        assert!(Object::Array(vec![Object::Integer(2)]) > Object::Array(vec![Object::Integer(1), Object::Integer(3)]));
+    }
+
+    #[test]
+    fn test_ordering_across_types() {
+        let ordered = vec![
+            Object::Null,
+            Object::Boolean(true),
+            Object::Integer(-100),
+            Object::Float(Float(-1.5)),
+            Object::Integer(1),
+            Object::Float(Float(1.0)),
+            Object::Integer(100),
+            Object::String("".into()),
+            Object::Array(vec![]),
+            ];
+        for window in ordered.windows(2) {
+            assert!(window[0] < window[1], "{:?} < {:?}", window[0], window[1]);
+        }
+    }
+
+    #[test]
+    fn test_approx_size() {
+        let scalar = Object::Integer(1);
+        let small = Object::Array(vec![Object::Integer(1)]);
+        let large = Object::Array((0..10).map(Object::Integer).collect());
+        assert!(scalar.approx_size() < small.approx_size());
+        assert!(small.approx_size() < large.approx_size());
+        assert!(Object::String("a".into()).approx_size() < Object::String("abc".into()).approx_size());
+
+        let nested = Object::Array(vec![large.clone()]);
+        assert!(large.approx_size() < nested.approx_size());
+    }
+
+    #[test]
+    fn test_inspect() {
+        let config = InspectConfig::default();
+        let large = Object::Array((0..1000).map(Object::Integer).collect());
+        let inspected = large.inspect(&config);
+        assert!(inspected.starts_with("[0, 1, 2, "), "{}", inspected);
+        assert!(inspected.ends_with(", 98, 99, ...]"), "{}", inspected);
+
+        let small = Object::Array(vec![Object::Integer(1), Object::String("a".into())]);
+        assert_eq!(small.inspect(&config), format!("{}", small));
+
+        let config = InspectConfig{max_depth: 2, max_elements: 2};
+        let nested = Object::Array(vec![Object::Array(vec![Object::Array(vec![Object::Integer(1)])])]);
+        assert_eq!(nested.inspect(&config), "[[[...]]]");
+        let three = Object::Array(vec![Object::Integer(1), Object::Integer(2), Object::Integer(3)]);
+        assert_eq!(three.inspect(&config), "[1, 2, ...]");
+    }
+}
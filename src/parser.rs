@@ -3,30 +3,142 @@ use super::token::{Token, TokenKind};
 use super::lexer;
 use super::errors::{Errors};
 use super::ast::{Program, Statement, Statement::LetStatement,
-                 Expression, Precedence};
+                 Expression, Precedence, Float};
+
+// features of the language which can be turned off,
+// so that a subset of the language can be taught step by step.
+#[derive(Debug, PartialEq, Clone, Eq)]
+pub struct ParserConfig {
+    // hash literals like {"a": 1}.
+    pub hashes: bool,
+    // array literals like [1, 2].
+    pub arrays: bool,
+    // function literals like fn(x) { x } and named functions like fn f(x) { x }.
+    pub functions: bool,
+}
+
+impl Default for ParserConfig {
+    fn default() -> ParserConfig {
+        ParserConfig{hashes: true, arrays: true, functions: true}
+    }
+}
 
 #[derive(Debug, Clone)]
 pub struct Parser<'a>  {
     lexer: lexer::Lexer<'a>,
     current_token: Token,
     next_token: Token,
+    current_position: usize,
+    next_position: usize,
+    // end an expression at the end of a line even without ';', which the REPL uses.
+    // it's set when the lexer emits NEWLINE.
+    newline_terminates_statement: bool,
+    // whether the next token is on a new line, not inside parentheses or brackets.
+    newline_before_next: bool,
+    // the number of parentheses and brackets which are open.
+    nesting: usize,
+    // the number of tokens which the parser has moved past.
+    consumed: usize,
+    // comments which have been read but not attached to a statement yet, with their positions.
+    // the lexer emits them only when the parser is made by new_with_comments.
+    comments: Vec<(usize, String)>,
+    config: ParserConfig,
 }
 
 impl<'a>  Parser<'a>  {
     pub fn new(l: lexer::Lexer<'a>) -> Self {
         let mut p = Parser{
-            lexer: l,
             current_token: Token{token_type: TokenKind::DEFAULT, literal: "default".to_string() },
             next_token: Token{token_type: TokenKind::DEFAULT, literal: "default".to_string() },
+            current_position: 0,
+            next_position: 0,
+            newline_terminates_statement: l.newline_tokens(),
+            newline_before_next: false,
+            nesting: 0,
+            consumed: 0,
+            comments: vec![],
+            config: ParserConfig::default(),
+            lexer: l,
         };
         p.next_token();
         p.next_token();
         p
     }
 
+    // construct a parser with a lexer for input.
+    pub fn new_from_source(input: &'a str) -> Self {
+        Parser::new(lexer::Lexer::new(input))
+    }
+
+    // construct a parser where a newline ends an expression statement like ';'.
+    // a newline inside parentheses or brackets doesn't end it,
+    // and neither does a newline after an operator because the operator needs its right side.
+    pub fn new_newline_terminated(input: &'a str) -> Self {
+        Parser::new(lexer::Lexer::new(input).with_newline_tokens())
+    }
+
+    // construct a parser which keeps comments, so that a formatter can write them again.
+    // a statement is wrapped in Statement::Commented with the comments above it and the one after it on the same line.
+    // a comment inside a statement which isn't taken by a statement in its block goes above the statement,
+    // like one after the last statement of a block. comments after the last statement of the program aren't kept.
+    pub fn new_with_comments(input: &'a str) -> Self {
+        Parser::new(lexer::Lexer::new(input).with_comment_tokens())
+    }
+
+    // a disabled feature is an error where it is used.
+    pub fn with_config(mut self, config: ParserConfig) -> Self {
+        self.config = config;
+        self
+    }
+
+    fn check_feature(&self, enabled: bool, name: &str) -> Result<(), Errors> {
+        if enabled {
+            Ok(())
+        } else {
+            Err(Errors::FeatureDisabled(name.to_string()))
+        }
+    }
+
+    // the number of tokens consumed, and an estimate of the tokens remaining including the current one,
+    // which assumes the rest of the input has as many bytes per token as the consumed part.
+    pub fn progress(&self) -> (usize, usize) {
+        if self.is_current_token(TokenKind::EOF) {
+            return (self.consumed, 0)
+        }
+        let remaining_bytes = self.lexer.input_length().saturating_sub(self.current_position);
+        if self.consumed == 0 || self.current_position == 0 {
+            // a token has at least one byte.
+            return (self.consumed, remaining_bytes.max(1))
+        }
+        let estimate = (remaining_bytes * self.consumed + self.current_position - 1) / self.current_position;
+        (self.consumed, estimate.max(1))
+    }
+
     pub fn next_token(&mut self) {
+        match self.current_token.token_type {
+            TokenKind::DEFAULT | TokenKind::EOF => {},
+            _ => self.consumed += 1,
+        }
         self.current_token = self.next_token.clone();
-        self.next_token = self.lexer.next_token();
+        self.current_position = self.next_position;
+        self.newline_before_next = false;
+        loop {
+            self.next_token = self.lexer.next_token();
+            self.next_position = self.lexer.token_position();
+            if self.is_next_token(TokenKind::COMMENT) {
+                self.comments.push((self.next_position, self.next_token.literal.clone()));
+                continue;
+            }
+            if !self.is_next_token(TokenKind::NEWLINE) {
+                break;
+            }
+            self.newline_before_next = self.newline_before_next || self.nesting == 0;
+        }
+        match self.next_token.token_type {
+            TokenKind::LPAREN | TokenKind::LBRACKET => self.nesting += 1,
+            TokenKind::RPAREN | TokenKind::RBRACKET => self.nesting = self.nesting.saturating_sub(1),
+            _ => {}
+        }
     }
 
     pub fn parse_program(&mut self) -> Result<Program, Errors> {
@@ -41,14 +153,89 @@ impl<'a>  Parser<'a>  {
         Ok(Program {statements: statements})
     }
 
+    // parse whole input even if there are syntax errors, so that all of them
+    // can be reported at once. each error has the position of the token where it happened.
+    pub fn parse_program_with_errors(&mut self) -> Result<Program, Vec<Errors>> {
+        let mut statements: Vec<Statement> = vec![];
+        let mut errors: Vec<Errors> = vec![];
+
+        while !self.is_current_token(TokenKind::EOF){
+            match self.parse_statement() {
+                Ok(statement) => statements.push(statement),
+                Err(error) => {
+                    let (line, column) = self.lexer.line_column(self.current_position);
+                    errors.push(Errors::Located{error: Box::new(error), line, column});
+                    // skip the rest of the broken sentence and restart from the next one.
+                    while !self.is_current_token(TokenKind::SEMICOLON) && !self.is_current_token(TokenKind::EOF) {
+                        self.next_token();
+                    }
+                    if self.is_current_token(TokenKind::EOF) {
+                        break;
+                    }
+                }
+            }
+            self.next_token();
+        };
+        if errors.is_empty() {
+            Ok(Program {statements: statements})
+        } else {
+            Err(errors)
+        }
+    }
+
     fn parse_statement(&mut self) -> Result<Statement, Errors> {
+        if !self.lexer.comment_tokens() {
+            return self.parse_uncommented_statement()
+        }
+        // the comments before the first token are taken first, so that a statement inside doesn't take them.
+        let start = self.current_position;
+        let mut leading_comments = self.take_comments(|position| position < start);
+        let statement = self.parse_uncommented_statement()?;
+        let end = self.current_position;
+        leading_comments.extend(self.take_comments(|position| position < end));
+        // the comments left are after the statement, and the first one may be on the same line as its last token.
+        let trailing_comment = match self.comments.first() {
+            Some((position, _)) if self.lexer.line_column(*position).0 == self.lexer.line_column(end).0 => Some(self.comments.remove(0).1),
+            _ => None
+        };
+        if leading_comments.is_empty() && trailing_comment.is_none() {
+            return Ok(statement)
+        }
+        Ok(Statement::Commented{leading_comments, statement: Box::new(statement), trailing_comment})
+    }
+
+    // remove the comments at the positions which match and return them in the order they are written.
+    fn take_comments<F: Fn(usize) -> bool>(&mut self, matches: F) -> Vec<String> {
+        let (taken, left): (Vec<_>, Vec<_>) = self.comments.drain(..).partition(|(position, _)| matches(*position));
+        self.comments = left;
+        taken.into_iter().map(|(_, comment)| comment).collect()
+    }
+
+    fn parse_uncommented_statement(&mut self) -> Result<Statement, Errors> {
         match self.current_token.token_type {
             TokenKind::LET => {
                 Ok((self.parse_let_statement()?))
             },
+            TokenKind::CONST => {
+                Ok(self.parse_const_statement()?)
+            },
             TokenKind::RETURN => {
                 Ok(self.parse_return_statement()?)
             },
+            TokenKind::BREAK => {
+                Ok(self.parse_break_statement()?)
+            },
+            TokenKind::IMPORT => {
+                Ok(self.parse_import_statement()?)
+            },
+            TokenKind::FUNCTION if self.is_next_token(TokenKind::IDENT) => {
+                self.check_feature(self.config.functions, "functions")?;
+                Ok(self.parse_named_function()?)
+            },
+            // closing brackets and separators are left when there are more of them than expected.
+            TokenKind::RPAREN | TokenKind::RBRACKET | TokenKind::RBRACE | TokenKind::COMMA | TokenKind::COLON => {
+                Err(Errors::UnexpectedToken(self.current_token.clone()))
+            },
             _ => {
                 Ok(self.parse_expression_statement()?)
             }
@@ -59,14 +246,26 @@ impl<'a>  Parser<'a>  {
         // Since current token is let, next_token() is implemented in order to read identifier next to 'let'.
         self.next_token();
 
+        if self.is_current_token(TokenKind::EOF) {
+            return Err(Errors::UnexpectedEof{expected: Some(TokenKind::IDENT)})
+        }
         // If current token is identifier, next token must be '==' or '!='
         if !self.is_current_token(TokenKind::IDENT) || self.expect_next_token(TokenKind::IDENT){
             return Err(Errors::TokenInvalid(self.next_token.clone()))
         }
         let identifier = Expression::Identifier(self.current_token.literal.clone());
+        // `: Type` between the identifier and assign is an annotation.
+        let annotation = if self.expect_next_token(TokenKind::COLON) {
+            if !self.expect_next_token(TokenKind::IDENT) {
+                return Err(self.next_token_error(TokenKind::IDENT))
+            }
+            Some(self.current_token.literal.clone())
+        } else {
+            None
+        };
         // If there isn't assign next to identifier, return error.
         if !self.expect_next_token(TokenKind::ASSIGN) {
-            return Err(Errors::TokenInvalid(self.next_token.clone()))
+            return Err(self.next_token_error(TokenKind::ASSIGN))
         }
         // skip a assign token
         self.next_token();
@@ -78,49 +277,132 @@ impl<'a>  Parser<'a>  {
         }
         let stmt = LetStatement {
                 identifier: identifier,
+                annotation: annotation,
                 value: stmt_value
         };
         return Ok(stmt)
     }
 
+    fn parse_const_statement(&mut self) -> Result<Statement, Errors> {
+        // const has the same grammar as let, only the binding rule differs.
+        match self.parse_let_statement()? {
+            LetStatement{identifier, annotation: None, value} => Ok(Statement::ConstStatement{identifier, value}),
+            // only let can be annotated.
+            LetStatement{annotation: Some(_), ..} => Err(Errors::TokenInvalid(Token{token_type: TokenKind::COLON, literal: String::from(":")})),
+            _ => unreachable!()
+        }
+    }
+
     fn parse_return_statement(&mut self) -> Result<Statement, Errors> {
         // skip return value and then read value next to return.
         self.next_token();
         let return_value = self.parse_expression(Precedence::LOWEST)?;
 
-        // read token until it reaches at the end of sentence.
-        while !self.is_current_token(TokenKind::SEMICOLON) {
-            self.next_token()
+        if self.is_next_token(TokenKind::SEMICOLON) {
+            self.next_token();
         }
         return Ok(Statement::Return(return_value))
     }
 
+    // the path of the file to import is a string literal like import "lib.mk",
+    // which can be followed by the name of the namespace like import "lib.mk" as lib.
+    fn parse_import_statement(&mut self) -> Result<Statement, Errors> {
+        if !self.expect_next_token(TokenKind::STRING) {
+            return Err(self.next_token_error(TokenKind::STRING))
+        }
+        let path = self.current_token.literal.clone();
+        let alias = if self.expect_next_token(TokenKind::AS) {
+            if !self.expect_next_token(TokenKind::IDENT) {
+                return Err(self.next_token_error(TokenKind::IDENT))
+            }
+            Some(self.current_token.literal.clone())
+        } else {
+            None
+        };
+        if self.is_next_token(TokenKind::SEMICOLON) {
+            self.next_token();
+        }
+        Ok(Statement::Import{path, alias})
+    }
+
+    fn parse_break_statement(&mut self) -> Result<Statement, Errors> {
+        // break without value stops a loop with null.
+        if self.is_next_token(TokenKind::SEMICOLON) || self.is_next_token(TokenKind::RBRACE) || self.is_next_token(TokenKind::EOF) {
+            if self.is_next_token(TokenKind::SEMICOLON) {
+                self.next_token();
+            }
+            return Ok(Statement::Break(None))
+        }
+        self.next_token();
+        let break_value = self.parse_expression(Precedence::LOWEST)?;
+        if self.is_next_token(TokenKind::SEMICOLON) {
+            self.next_token();
+        }
+        return Ok(Statement::Break(Some(break_value)))
+    }
+
     fn parse_expression_statement(&mut self) -> Result<Statement, Errors> {
         let expression = self.parse_expression(Precedence::LOWEST)?;
+        if self.is_next_token(TokenKind::ASSIGN) {
+            // only a variable or an element of an array or a hash can be assigned.
+            if let Expression::Identifier(_) | Expression::IndexExpression{..} = expression {
+                return self.parse_assign_statement(expression)
+            }
+            return Err(Errors::InvalidAssignTarget(format!("{}", expression)))
+        }
         if self.is_next_token(TokenKind::SEMICOLON) {
             self.next_token()
         }
         return Ok(Statement::ExpressionStatement(expression))
     }
 
+    fn parse_assign_statement(&mut self, target: Expression) -> Result<Statement, Errors> {
+        // skip the target and a assign token
+        self.next_token();
+        self.next_token();
+        let value = self.parse_expression(Precedence::LOWEST)?;
+        if self.is_next_token(TokenKind::SEMICOLON) {
+            self.next_token();
+        }
+        Ok(Statement::Assign{target: target, value: value})
+    }
+
     fn parse_expression(&mut self, precedence: Precedence) -> Result<Expression, Errors> {
         let mut exp = match self.current_token.token_type {
             TokenKind::IDENT => {Expression::Identifier(self.parse_identifier()?)},
             TokenKind::STRING => {
                 Expression::String(self.parse_string()?)},
             TokenKind::INT => Expression::Integer(self.parse_integer()?),
+            TokenKind::FLOAT => Expression::Float(self.parse_float()?),
             TokenKind::TRUE => Expression::Bool(true),
             TokenKind::FALSE => Expression::Bool(false),
             TokenKind::IF =>   self.parse_if_expression()?,
+            TokenKind::WHILE => self.parse_while_expression()?,
+            TokenKind::DO => self.parse_do_expression()?,
             TokenKind::LPAREN => self.parse_grouped_expression()?,
-            TokenKind::LBRACE => self.parse_hash_literal()?,
-            TokenKind::LBRACKET => self.parse_array_literal()?,
-            TokenKind::FUNCTION => self.parse_function_expression()?,
+            TokenKind::LBRACE => {
+                self.check_feature(self.config.hashes, "hashes")?;
+                self.parse_hash_literal()?
+            },
+            TokenKind::LBRACKET => {
+                self.check_feature(self.config.arrays, "arrays")?;
+                self.parse_array_literal()?
+            },
+            TokenKind::FUNCTION => {
+                self.check_feature(self.config.functions, "functions")?;
+                self.parse_function_expression()?
+            },
             TokenKind::BANG => self.parse_prefix_expression()?,
             TokenKind::MINUS => self.parse_prefix_expression()?,
+            TokenKind::PLUS => self.parse_prefix_expression()?,
+            TokenKind::EOF => return Err(Errors::UnexpectedEof{expected: None}),
+            // a string which isn't closed yet runs until the end of input.
+            TokenKind::ILLEGAL if self.current_token.literal.starts_with('"') && self.is_next_token(TokenKind::EOF) => {
+                return Err(Errors::UnexpectedEof{expected: None})
+            },
             _ => return Err(Errors::TokenInvalid(self.current_token.clone()))
         };
-        while !self.is_next_token(TokenKind::SEMICOLON) && precedence < self.next_precedence() {
+        while !self.is_next_token(TokenKind::SEMICOLON) && precedence < self.next_precedence() && !self.is_end_of_line() {
             //operator must be set in current position,
             //so token must be read once forward.
             match self.next_token.token_type {
@@ -136,6 +418,10 @@ impl<'a>  Parser<'a>  {
                     self.next_token();
                     exp =  self.parse_infix_expression(exp)?;
                 },
+                TokenKind::IntDiv => {
+                    self.next_token();
+                    exp =  self.parse_infix_expression(exp)?;
+                },
                 TokenKind::ASTERISK => {
                     self.next_token();
                     exp =  self.parse_infix_expression(exp)?;
@@ -156,6 +442,14 @@ impl<'a>  Parser<'a>  {
                     self.next_token();
                     exp =  self.parse_infix_expression(exp)?;
                 },
+                TokenKind::NullCoalesce => {
+                    self.next_token();
+                    exp =  self.parse_infix_expression(exp)?;
+                },
+                TokenKind::PIPE => {
+                    self.next_token();
+                    exp =  self.parse_pipe_expression(exp)?;
+                },
                 TokenKind::LPAREN => {
                     self.next_token();
                     exp =  self.parse_call_arguments(exp)?;
@@ -164,6 +458,14 @@ impl<'a>  Parser<'a>  {
                     self.next_token();
                     exp =  self.parse_index_expression(exp)?;
                 },
+                TokenKind::OptionalChain => {
+                    self.next_token();
+                    exp =  self.parse_optional_index_expression(exp)?;
+                },
+                TokenKind::DOT => {
+                    self.next_token();
+                    exp =  self.parse_dot_expression(exp)?;
+                },
                 _ => {
                     return Ok(exp);                
                 }
@@ -183,6 +485,17 @@ impl<'a>  Parser<'a>  {
     fn parse_integer(&mut self) -> Result<i32, Errors> {
         return Ok(self.current_token.literal.parse::<i32>().unwrap())
     }
+
+    fn parse_float(&mut self) -> Result<Float, Errors> {
+        match self.current_token.literal.parse::<f64>() {
+            Ok(value) => Ok(Float(value)),
+            Err(_) => Err(Errors::TokenInvalid(self.current_token.clone()))
+        }
+    }
+
+    // `{` in expression position always starts a hash literal, so `{}` is an empty hash.
+    // a block is written only after `do`, `fn (...)`, `if (...)`, `else` or `while (...)`,
+    // and `{ let x = 1; }` in expression position is a syntax error.
     fn parse_hash_literal(&mut self) -> Result<Expression, Errors> {
         let mut pairs = BTreeMap::new();
 
@@ -190,18 +503,18 @@ impl<'a>  Parser<'a>  {
             self.next_token();
             let key = self.parse_expression(Precedence::LOWEST)?;
             if !self.expect_next_token(TokenKind::COLON) {
-                return Ok(Expression::Null)
+                return Err(self.next_token_error(TokenKind::COLON))
             }
             self.next_token();
             let value = self.parse_expression(Precedence::LOWEST)?;
             // the values inside btree_map is alphabetically ordered.
             pairs.insert(Box::new(key), Box::new(value));
             if !self.is_next_token(TokenKind::RBRACE) && !self.expect_next_token(TokenKind::COMMA) {
-                return Ok(Expression::Null)
+                return Err(self.next_token_error(TokenKind::RBRACE))
             }
         }
         if !self.expect_next_token(TokenKind::RBRACE) {
-            return Ok(Expression::Null)
+            return Err(self.next_token_error(TokenKind::RBRACE))
         }
 
         return Ok(Expression::Hashmap(pairs))
@@ -234,7 +547,7 @@ impl<'a>  Parser<'a>  {
             if self.expect_next_token(end) {
                 Ok(list)
             } else {
-                unimplemented!()
+                Err(self.next_token_error(end))
             }
         }
     }
@@ -243,30 +556,55 @@ impl<'a>  Parser<'a>  {
         self.next_token();
         let index = self.parse_expression(Precedence::LOWEST)?;
         if !self.expect_next_token(TokenKind::RBRACKET) {
-            return Ok(Expression::Null)
+            return Err(self.next_token_error(TokenKind::RBRACKET))
         }
         Ok(Expression::IndexExpression{array: Box::new(left), subscript: Box::new(index)})
     }
 
+    // a?.[k] looks up k like a[k], and a?.name is the same as a?.["name"].
+    fn parse_optional_index_expression(&mut self, left: Expression) -> Result<Expression, Errors> {
+        let subscript = if self.expect_next_token(TokenKind::LBRACKET) {
+            self.next_token();
+            let index = self.parse_expression(Precedence::LOWEST)?;
+            if !self.expect_next_token(TokenKind::RBRACKET) {
+                return Err(self.next_token_error(TokenKind::RBRACKET))
+            }
+            Box::new(index)
+        } else if self.expect_next_token(TokenKind::IDENT) {
+            Box::new(Expression::String(self.parse_identifier()?))
+        } else {
+            return Err(self.next_token_error(TokenKind::LBRACKET))
+        };
+        Ok(Expression::OptionalIndexExpression{array: Box::new(left), subscript: subscript})
+    }
+
+    // a.name is the same as a["name"].
+    fn parse_dot_expression(&mut self, left: Expression) -> Result<Expression, Errors> {
+        if !self.expect_next_token(TokenKind::IDENT) {
+            return Err(self.next_token_error(TokenKind::IDENT))
+        }
+        Ok(Expression::IndexExpression{array: Box::new(left), subscript: Box::new(Expression::String(self.parse_identifier()?))})
+    }
+
     fn parse_grouped_expression(&mut self) -> Result<Expression, Errors> {
         self.next_token();
         let lparen = self.parse_expression(Precedence::LOWEST)?;
         if self.expect_next_token(TokenKind::RPAREN) {
              return Ok(lparen)
         }  else {
-            Err(Errors::TokenInvalid(self.current_token.clone()))
+            Err(self.next_token_error(TokenKind::RPAREN))
         }
 }
 
     fn parse_if_expression(&mut self) ->  Result<Expression, Errors> {
         if !self.is_next_token(TokenKind::LPAREN) {
-            return Ok(Expression::Null)
+            return Err(self.next_token_error(TokenKind::LPAREN))
         }
         self.next_token();
         let condition = self.parse_expression(Precedence::LOWEST);
 
         if !self.expect_next_token(TokenKind::LBRACE) {
-            return Ok(Expression::Null)
+            return Err(self.next_token_error(TokenKind::LBRACE))
             }
         let expression = Expression::IfExpression{
                             condition: Box::new(condition?),
@@ -276,6 +614,31 @@ impl<'a>  Parser<'a>  {
         Ok(expression)
     }
 
+    fn parse_while_expression(&mut self) ->  Result<Expression, Errors> {
+        if !self.is_next_token(TokenKind::LPAREN) {
+            return Err(self.next_token_error(TokenKind::LPAREN))
+        }
+        self.next_token();
+        let condition = self.parse_expression(Precedence::LOWEST)?;
+
+        if !self.expect_next_token(TokenKind::LBRACE) {
+            return Err(self.next_token_error(TokenKind::LBRACE))
+            }
+        let expression = Expression::WhileExpression{
+                            condition: Box::new(condition),
+                            body: Box::new(self.parse_block_statements(TokenKind::LBRACE)?),
+                                                  };
+        Ok(expression)
+    }
+
+    // `do { ... }` is a block, while `{ ... }` is a hash literal.
+    fn parse_do_expression(&mut self) ->  Result<Expression, Errors> {
+        if !self.expect_next_token(TokenKind::LBRACE) {
+            return Err(self.next_token_error(TokenKind::LBRACE))
+        }
+        Ok(Expression::DoExpression{body: Box::new(self.parse_block_statements(TokenKind::LBRACE)?)})
+    }
+
     fn parse_block_statements(&mut self, token_kind: TokenKind) -> Result<Statement, Errors> {
         self.next_token();
         let mut statements: Vec<Statement> = vec![];
@@ -284,6 +647,10 @@ impl<'a>  Parser<'a>  {
             statements.push(statement);
             self.next_token();
         }
+        // if input ends before a block is closed, more input is needed.
+        if self.is_current_token(TokenKind::EOF) {
+            return Err(Errors::UnexpectedEof{expected: Some(TokenKind::RBRACE)})
+        }
         Ok(Statement::Block(statements))
     }
 
@@ -294,21 +661,32 @@ impl<'a>  Parser<'a>  {
             let alternative = self.parse_block_statements(TokenKind::LBRACE)?;
             Ok(Some(Box::new(alternative)))
         }else {
-            return Err(Errors::TokenInvalid(self.current_token.clone()))
+            return Err(self.next_token_error(TokenKind::LBRACE))
         }
     } else {
            Ok(None)
         }
     }
 
+    // `fn name(x) { ... }` is the same as `let name = fn(x) { ... };`.
+    fn parse_named_function(&mut self) -> Result<Statement, Errors> {
+        self.next_token();
+        let identifier = Expression::Identifier(self.current_token.literal.clone());
+        let function = self.parse_function_expression()?;
+        if self.is_next_token(TokenKind::SEMICOLON) {
+            self.next_token();
+        }
+        Ok(LetStatement{identifier: identifier, annotation: None, value: function})
+    }
+
     fn parse_function_expression(&mut self) -> Result<Expression, Errors> {
-        if self.expect_next_token(TokenKind::LPAREN) {
-            println!("TokenKind should be LPAREN but actually is {:?}",self.next_token.token_type)            
+        if !self.expect_next_token(TokenKind::LPAREN) {
+            return Err(self.next_token_error(TokenKind::LPAREN))
         }
         let parameters = self.parse_function_parameters()?;
-        if self.expect_next_token(TokenKind::LBRACE) {
-            println!("TokenKind should be LBRACE but actually is {:?}",self.next_token.token_type)            
-        }        
+        if !self.expect_next_token(TokenKind::LBRACE) {
+            return Err(self.next_token_error(TokenKind::LBRACE))
+        }
 
         let body = self.parse_block_statements(TokenKind::LBRACE)?;
         let expression = Expression::FunctionLiteral{
@@ -335,7 +713,13 @@ impl<'a>  Parser<'a>  {
         identifiers.push(Expression::Identifier(self.current_token.literal.clone()));
         }
         if !self.expect_next_token(TokenKind::RPAREN) {
-            panic!()
+            return Err(self.next_token_error(TokenKind::RPAREN))
+        }
+        // a parameter with the same name as an earlier one would hide it.
+        for (i, identifier) in identifiers.iter().enumerate() {
+            if identifiers[..i].contains(identifier) {
+                return Err(Errors::DuplicateParameter(format!("{}", identifier)))
+            }
         }
         Ok(identifiers)
     }
@@ -355,12 +739,26 @@ impl<'a>  Parser<'a>  {
             arguments.push(self.parse_expression(Precedence::LOWEST)?);
         }
         if !self.expect_next_token(TokenKind::RPAREN) {
-            return Ok(Expression::Null)
+            return Err(self.next_token_error(TokenKind::RPAREN))
                 }
             }
         Ok(Expression::CallExpression{function: Box::new(func), body: arguments})
     }
 
+    // `x |> f` is rewritten as `f(x)`, and `x |> f(a)` as `f(x, a)`.
+    fn parse_pipe_expression(&mut self, left: Expression) -> Result<Expression, Errors> {
+        let precedence = self.current_precedence();
+        self.next_token();
+        match self.parse_expression(precedence)? {
+            Expression::CallExpression{function, body} => {
+                let mut arguments = vec![left];
+                arguments.extend(body);
+                Ok(Expression::CallExpression{function: function, body: arguments})
+            },
+            function => Ok(Expression::CallExpression{function: Box::new(function), body: vec![left]})
+        }
+    }
+
     fn parse_prefix_expression(&mut self) -> Result<Expression, Errors> {
         let current_token = self.current_token.literal.to_string();
         self.next_token();
@@ -378,10 +776,12 @@ impl<'a>  Parser<'a>  {
             TokenKind::MINUS => "-".to_string(),
             TokenKind::ASTERISK => "*".to_string(),
             TokenKind::SLASH => "/".to_string(),
+            TokenKind::IntDiv => "//".to_string(),
             TokenKind::EQ => "==".to_string(),
             TokenKind::NotEq => "!=".to_string(),
             TokenKind::LT => "<".to_string(),
             TokenKind::GT => ">".to_string(),
+            TokenKind::NullCoalesce => "??".to_string(),
             _ => {panic!()}
         };
         // current token will be read in parse_expression().
@@ -413,6 +813,20 @@ impl<'a>  Parser<'a>  {
         self.next_token.token_type == token_kind
     }
 
+    fn is_end_of_line(&self) -> bool {
+        self.newline_terminates_statement && self.newline_before_next
+    }
+
+    // the error when next token isn't expected one.
+    // if input has already ended, it means the sentence needs more input.
+    fn next_token_error(&self, expected: TokenKind) -> Errors {
+        if self.is_next_token(TokenKind::EOF) {
+            Errors::UnexpectedEof{expected: Some(expected)}
+        } else {
+            Errors::TokenInvalid(self.next_token.clone())
+        }
+    }
+
     fn expect_next_token(&mut self, token_kind: TokenKind) -> bool {
         if self.is_next_token(token_kind){
             self.next_token();
@@ -427,7 +841,10 @@ impl<'a>  Parser<'a>  {
 #[cfg(test)]// test runs only when execute cargo run
 mod testing {
     use crate::lexer::Lexer;
-    use crate::parser::Parser;
+    use crate::parser::{Parser, ParserConfig};
+    use crate::token::TokenKind;
+    use crate::errors::Errors;
+    use crate::ast::{Statement, Expression};
     use std::str::FromStr;
 
 
@@ -470,8 +887,7 @@ mod testing {
     fn test_identifier_expression() {
         let input = "foobar;".to_string();
         
-        let lexer = Lexer::new(&input);
-        let mut parser = Parser::new(lexer);
+        let mut parser = Parser::new_from_source(&input);
         let program = parser.parse_program().unwrap();
         assert_eq!(program.statements.len(), 1); // 識別子が一つであること
         let stmt = format!("{}", program.statements[0]);
@@ -482,21 +898,37 @@ mod testing {
         fn test_interger_expression() {
             let input = "5".to_string();
             
-            let lexer = Lexer::new(&input);
-            let mut parser = Parser::new(lexer);
+            let mut parser = Parser::new_from_source(&input);
             let program = parser.parse_program().unwrap();
             assert_eq!(program.statements.len(), 1); // confirm the number of statements is 1.
             let stmt = format!("{}", program.statements[0]);
             assert_eq!(stmt, "5".to_string());
             }
 
+        #[test]
+        fn test_float_expression() {
+            let tests = vec![("2.5", "2.5"), ("1e3", "1000.0"), ("2.5e-2", "0.025"), ("1E6", "1000000.0")];
+            for (input, expected) in tests.into_iter() {
+                let mut parser = Parser::new_from_source(input);
+                let program = parser.parse_program().unwrap();
+                assert_eq!(format!("{}", program.statements[0]), expected);
+            }
+            for input in vec!["1e", "1e+", "let x = 2.5e-;"].into_iter() {
+                let mut parser = Parser::new_from_source(input);
+                match parser.parse_program() {
+                    Err(Errors::TokenInvalid(token)) => assert_eq!(token.token_type, TokenKind::ILLEGAL),
+                    result => panic!("{} should be a lex error, got {:?}", input, result),
+                }
+            }
+        }
+
         #[test]
         fn test_prefix_expression() {
             let prefix_tests = vec!["!5","-15"];
             // compare the result of parseing the first element of tuple
             // with second, third elements.
             for test in prefix_tests.iter() {
-                let lexer = Lexer::new(test);
+                let lexer = Lexer::new(*test);
                 let mut parser = Parser::new(lexer);
                 let program = parser.parse_program().unwrap();
                 assert_eq!(program.statements.len(), 1); // confirm the number of statements is 1.
@@ -514,7 +946,7 @@ mod testing {
                 // compare the result of parseing the first element of tuple
                 // with second, third elements.
                 for test in infix_tests.iter() {
-                    let lexer = Lexer::new(test);
+                    let lexer = Lexer::new(*test);
                     let mut parser = Parser::new(lexer);
                     let program = parser.parse_program().unwrap();
                     assert_eq!(program.statements.len(), 1); // confirm the number of statements is 1.
@@ -529,17 +961,20 @@ mod testing {
             fn test_operator_precedence_parsing() {
                   let infix_tests = vec![
                                         ("((-a) * b)", "-a * b"),
+                                        ("((+a) * b)", "+a * b"),
+                                        ("(a + (+5))", "a + +5"),
                                         ("(!(-a))", "!-a"), 
                                         ("((a + b) + c)", "a + b + c"),
                                         ("((a + b) - c)", "a + b - c"),
                                         ("((a * b) * c)", "a * b * c"),
                                         ("((a * b) / c)", "a * b / c"), 
                                         ("(a + (b / c))", "a + b / c"),
-                                        ("(1 + (2 + 3)) + 4","1 + 2 + 3 + 4"),
-                                        ("((5 + 5) * 2)", "5 + 5 * 2"),
-                                        ("(2 / (5 + 5))", "2 / 5 + 5"),
-                                        ("(-(5 + 5))", "-5 + 5"),
-                                        ("(!(true == true))", "!true == true"),
+                                        ("(1 + (2 + 3)) + 4","1 + (2 + 3) + 4"),
+                                        ("((5 + 5) * 2)", "(5 + 5) * 2"),
+                                        ("(2 / (5 + 5))", "2 / (5 + 5)"),
+                                        ("((a // b) * c)", "a // b * c"),
+                                        ("(-(5 + 5))", "-(5 + 5)"),
+                                        ("(!(true == true))", "!(true == true)"),
                                         ("((a * ([1, 2, 3, 4][(b * c)])) * d)", "a * [1, 2, 3, 4][b * c] * d"),
                                         ];
                 // compare the result of parseing the first element of tuple
@@ -553,6 +988,256 @@ mod testing {
                 }
             }
 
+            #[test]
+            fn test_newline_terminates_statement() {
+                let tests = vec![
+                    ("1 + 1\n2 + 2", vec!["1 + 1", "2 + 2"]),
+                    ("1\n-1", vec!["1", "-1"]),
+                    ("f\n(1)", vec!["f", "1"]),
+                    // a newline after an operator or inside brackets doesn't end a statement.
+                    ("1 +\n2", vec!["1 + 2"]),
+                    ("f(1,\n2\n)", vec!["f(1, 2);"]),
+                    ("[1\n- 2]", vec!["[1 - 2]"]),
+                    ("let x = 1\n-1", vec!["let x = 1;", "-1"]),
+                    ("let x = 1 # comment\nx", vec!["let x = 1;", "x"]),
+                    ];
+                for (input, expected) in tests.into_iter() {
+                    let mut parser = Parser::new_newline_terminated(input);
+                    let program = parser.parse_program().unwrap();
+                    let statements: Vec<String> = program.statements.iter().map(|statement| format!("{}", statement)).collect();
+                    assert_eq!(statements, expected, "{}", input);
+                }
+                // newlines are whitespace in files.
+                let mut parser = Parser::new_from_source("1\n-1");
+                assert_eq!(format!("{}", parser.parse_program().unwrap()), "1 - 1\n");
+                let mut parser = Parser::new_from_source("let x = 1 # comment");
+                assert_eq!(parser.parse_program().unwrap().statements.len(), 1);
+            }
+
+            #[test]
+            fn test_comments_around_statements() {
+                let input = "# above\n# x\nlet x = 1; # one\nfn(y) {\n    # inside\n    y # after y\n};\n1 + # in 1 + 2\n2";
+                let program = Parser::new_with_comments(input).parse_program().unwrap();
+                let body = Statement::Block(vec![Statement::Commented{leading_comments: vec![String::from("# inside")],
+                                                                      statement: Box::new(Statement::ExpressionStatement(Expression::Identifier(String::from("y")))),
+                                                                      trailing_comment: Some(String::from("# after y"))}]);
+                assert_eq!(program.statements, vec![
+                    Statement::Commented{leading_comments: vec![String::from("# above"), String::from("# x")],
+                                         statement: Box::new(Statement::LetStatement{identifier: Expression::Identifier(String::from("x")), annotation: None, value: Expression::Integer(1)}),
+                                         trailing_comment: Some(String::from("# one"))},
+                    Statement::ExpressionStatement(Expression::FunctionLiteral{parameters: vec![Expression::Identifier(String::from("y"))], body: Box::new(body)}),
+                    Statement::Commented{leading_comments: vec![String::from("# in 1 + 2")],
+                                         statement: Box::new(Parser::new_from_source("1 + 2").parse_program().unwrap().statements[0].clone()),
+                                         trailing_comment: None},
+                    ]);
+            }
+
+            #[test]
+            fn test_assign_statement() {
+                let tests = vec![
+                    ("x = 1;", "x = 1;"),
+                    ("a[0] = 9", "a[0] = 9;"),
+                    ("a[i + 1][0] = b[0] * 2;", "a[i + 1][0] = b[0] * 2;"),
+                    ];
+                for (input, expected) in tests.into_iter() {
+                    let mut parser = Parser::new_from_source(input);
+                    let program = parser.parse_program().unwrap();
+                    assert_eq!(program.statements.len(), 1);
+                    match &program.statements[0] {
+                        Statement::Assign{..} => {},
+                        statement => panic!("{} should be an assignment, got {:?}", input, statement),
+                    }
+                    assert_eq!(format!("{}", program.statements[0]), expected);
+                }
+            }
+
+            #[test]
+            fn test_invalid_assign_target() {
+                let tests = vec![("5 = 3", "5"), ("f() = 1", "f();"), ("(a + b) = 1", "a + b"), ("\"a\" = 1;", "a")];
+                for (input, expected) in tests.into_iter() {
+                    let mut parser = Parser::new_from_source(input);
+                    assert_eq!(parser.parse_program(), Err(Errors::InvalidAssignTarget(expected.to_string())), "{}", input);
+                }
+            }
+
+            #[test]
+            fn test_unexpected_token() {
+                let tests = vec![("1 + 2 )", ")"), ("[1, 2]]", "]"), ("let x = 1; }", "}"), ("f(1), 2", ","), ("x : 1", ":")];
+                for (input, literal) in tests.into_iter() {
+                    let mut parser = Parser::new_from_source(input);
+                    match parser.parse_program() {
+                        Err(error @ Errors::UnexpectedToken(_)) => assert_eq!(format!("{}", error), format!("unexpected token: '{}'", literal), "{}", input),
+                        result => panic!("{} should be an unexpected token, got {:?}", input, result),
+                    }
+                }
+                // the same in the REPL, where the newline ends the first statement.
+                let mut parser = Parser::new_newline_terminated("1 + 2\n)");
+                assert!(match parser.parse_program() { Err(Errors::UnexpectedToken(_)) => true, _ => false });
+            }
+
+            #[test]
+            fn test_minimal_parentheses() {
+                let tests = vec![
+                    ("a + b * c", "a + b * c"),
+                    ("(a + b) * c", "(a + b) * c"),
+                    ("a - (b - c)", "a - (b - c)"),
+                    ("(a - b) - c", "a - b - c"),
+                    ("((a))", "a"),
+                    ("-(a + b)", "-(a + b)"),
+                    ("(-a) * b", "-a * b"),
+                    ("(a == b) == c", "a == b == c"),
+                    ("a == (b < c)", "a == b < c"),
+                    ("(a ?? b) + c", "(a ?? b) + c"),
+                    ("(a + b)[0]", "(a + b)[0]"),
+                    ("(-a)[0]", "(-a)[0]"),
+                    ("-a[0]", "-a[0]"),
+                    ("(f + g)(1)", "(f + g)(1);"),
+                    ("f(a * (b + c))", "f(a * (b + c));"),
+                    ];
+                for (input, expected) in tests.into_iter() {
+                    let program = Parser::new_from_source(input).parse_program().unwrap();
+                    let printed = format!("{}", program.statements[0]);
+                    assert_eq!(printed, expected, "{}", input);
+                    // the printed expression is parsed into the same AST.
+                    assert_eq!(Parser::new_from_source(&printed).parse_program().unwrap(), program, "{}", input);
+                }
+            }
+
+            #[test]
+            fn test_import_statement() {
+                let program = Parser::new_from_source("import \"lib.mk\"; import \"other.mk\" as other").parse_program().unwrap();
+                assert_eq!(program.statements, vec![Statement::Import{path: String::from("lib.mk"), alias: None},
+                                                    Statement::Import{path: String::from("other.mk"), alias: Some(String::from("other"))}]);
+                assert_eq!(format!("{}", program.statements[0]), "import \"lib.mk\";");
+                assert_eq!(format!("{}", program.statements[1]), "import \"other.mk\" as other;");
+                assert!(Parser::new_from_source("import \"lib.mk\" as 1").parse_program().is_err());
+                assert_eq!(Parser::new_from_source("import \"lib.mk\" as").parse_program(), Err(Errors::UnexpectedEof{expected: Some(TokenKind::IDENT)}));
+                assert!(match Parser::new_from_source("import lib").parse_program() { Err(Errors::TokenInvalid(_)) => true, _ => false });
+                assert_eq!(Parser::new_from_source("import").parse_program(), Err(Errors::UnexpectedEof{expected: Some(TokenKind::STRING)}));
+            }
+
+            #[test]
+            fn test_disabled_features() {
+                let tests = vec![
+                    ("[1, 2, 3]", ParserConfig{arrays: false, ..ParserConfig::default()}, "arrays"),
+                    ("let h = {\"a\": 1};", ParserConfig{hashes: false, ..ParserConfig::default()}, "hashes"),
+                    ("let f = fn(x) { x };", ParserConfig{functions: false, ..ParserConfig::default()}, "functions"),
+                    ("fn f(x) { x }", ParserConfig{functions: false, ..ParserConfig::default()}, "functions"),
+                    ];
+                for (input, config, feature) in tests.into_iter() {
+                    let mut parser = Parser::new_from_source(input).with_config(config);
+                    assert_eq!(parser.parse_program(), Err(Errors::FeatureDisabled(feature.to_string())), "{}", input);
+                    // everything is enabled by default.
+                    assert!(Parser::new_from_source(input).parse_program().is_ok(), "{}", input);
+                }
+                let mut parser = Parser::new_from_source("[1, 2, 3]").with_config(ParserConfig{arrays: false, ..ParserConfig::default()});
+                assert_eq!(format!("{}", parser.parse_program().unwrap_err()), "arrays are disabled");
+                // a disabled feature doesn't affect the others.
+                let mut parser = Parser::new_from_source("let f = fn(x) { x };").with_config(ParserConfig{arrays: false, hashes: false, functions: true});
+                assert!(parser.parse_program().is_ok());
+            }
+
+            #[test]
+            fn test_type_annotations() {
+                let program = Parser::new_from_source("let x: Int = 5;").parse_program().unwrap();
+                assert_eq!(program.statements, vec![Statement::LetStatement{identifier: Expression::Identifier(String::from("x")),
+                                                                            annotation: Some(String::from("Int")),
+                                                                            value: Expression::Integer(5)}]);
+                assert_eq!(format!("{}", program.statements[0]), "let x: Int = 5;");
+                let program = Parser::new_from_source("let x = 5;").parse_program().unwrap();
+                assert_eq!(format!("{}", program.statements[0]), "let x = 5;");
+
+                assert_eq!(Parser::new_from_source("let x:").parse_program(), Err(Errors::UnexpectedEof{expected: Some(TokenKind::IDENT)}));
+                assert!(match Parser::new_from_source("let x: 5 = 5;").parse_program() { Err(Errors::TokenInvalid(_)) => true, _ => false });
+                assert!(match Parser::new_from_source("const x: Int = 5;").parse_program() { Err(Errors::TokenInvalid(_)) => true, _ => false });
+            }
+
+            #[test]
+            fn test_progress() {
+                let input = "let x = 1;\nlet y = x + 2;\nlet add = fn(a, b) { a + b };\nadd(x, y);";
+                let mut parser = Parser::new_from_source(input);
+                let (consumed, remaining) = parser.progress();
+                assert_eq!(consumed, 0);
+                assert!(remaining > 0);
+                let mut snapshots = vec![];
+                while !parser.is_current_token(TokenKind::EOF) {
+                    parser.parse_statement().unwrap();
+                    parser.next_token();
+                    snapshots.push(parser.progress());
+                }
+                for pair in snapshots.windows(2) {
+                    assert!(pair[0].0 < pair[1].0, "{:?}", pair);
+                }
+                let total = crate::lexer::tokenize_with_positions(input).len();
+                assert_eq!(snapshots.last(), Some(&(total, 0)));
+                // the estimate is exact when tokens are the same size.
+                let mut parser = Parser::new_from_source("a;b;c;d;");
+                parser.next_token();
+                parser.next_token();
+                assert_eq!(parser.progress(), (2, 6));
+            }
+
+            #[test]
+            fn test_pipe_expression() {
+                // the pipe is rewritten into calls, so it is compared with the calls written directly.
+                let tests = vec![
+                    ("1 |> inc |> double", "double(inc(1))"),
+                    ("x |> f(a, b)", "f(x, a, b)"),
+                    ("1 + 2 |> f", "f(1 + 2)"),
+                    ("a == b |> f", "f(a == b)"),
+                    ("[1, 2] |> map(fn(x) { x * 2 }) |> len", "len(map([1, 2], fn(x) { x * 2 }))"),
+                    ];
+                for (input, expected) in tests.into_iter() {
+                    let mut parser = Parser::new_from_source(input);
+                    let mut expected_parser = Parser::new_from_source(expected);
+                    assert_eq!(parser.parse_program().unwrap(), expected_parser.parse_program().unwrap(), "{}", input);
+                }
+            }
+
+            #[test]
+            fn test_int_div_precedence() {
+                // `//` binds as tightly as `/` and `*`, and is left associative.
+                let tests = vec![
+                    ("a + b // c", "+", "b // c"),
+                    ("a // b + c", "+", "c"),
+                    ("a * b // c", "//", "c"),
+                    ("a // b / c", "/", "c"),
+                    ];
+                for (input, operator, right) in tests.into_iter() {
+                    let mut parser = Parser::new_from_source(input);
+                    let program = parser.parse_program().unwrap();
+                    match &program.statements[0] {
+                        Statement::ExpressionStatement(Expression::InfixExpression{operator: got, right_expression, ..}) => {
+                            assert_eq!(got, operator, "{}", input);
+                            assert_eq!(format!("{}", right_expression), right, "{}", input);
+                        },
+                        statement => panic!("{} should be an infix expression, got {:?}", input, statement),
+                    }
+                }
+            }
+
+            #[test]
+            fn test_null_coalesce_precedence() {
+                // `??` binds looser than any other operator, and is left associative.
+                let tests = vec![
+                    ("a ?? b + c", "??", "b + c"),
+                    ("a == b ?? c", "??", "c"),
+                    ("a ?? b |> f", "??", "f(b);"),
+                    ("a ?? b ?? c", "??", "c"),
+                    ("a ?? b?.[0]", "??", "b?.[0]"),
+                    ];
+                for (input, operator, right) in tests.into_iter() {
+                    let mut parser = Parser::new_from_source(input);
+                    let program = parser.parse_program().unwrap();
+                    match &program.statements[0] {
+                        Statement::ExpressionStatement(Expression::InfixExpression{operator: got, right_expression, ..}) => {
+                            assert_eq!(got, operator, "{}", input);
+                            assert_eq!(format!("{}", right_expression), right, "{}", input);
+                        },
+                        statement => panic!("{} should be an infix expression, got {:?}", input, statement),
+                    }
+                }
+            }
 
                 #[test]
                 fn test_bool_expression() {
@@ -563,7 +1248,7 @@ mod testing {
                     // compare the result of parseing the first element of tuple
                     // with second, third elements.
                     for (test, right) in bool_tests.iter() {
-                        let lexer = Lexer::new(test);
+                        let lexer = Lexer::new(*test);
                         let mut parser = Parser::new(lexer);
                         let program = parser.parse_program().unwrap();
                         assert_eq!(program.statements.len(), 1); // confirm the number of statements is 1.
@@ -580,7 +1265,7 @@ mod testing {
                 // compare the result of parseing the first element of tuple
                 // with second, third elements.
                 for (test, left, operator, right, bool_ident, bool_literal) in bool_tests.iter() {
-                    let lexer = Lexer::new(test);
+                    let lexer = Lexer::new(*test);
                     let mut parser = Parser::new(lexer);
                     let program = parser.parse_program();
                     }
@@ -597,6 +1282,90 @@ mod testing {
                 assert_eq!(input, statements);
                 }
 
+            #[test]
+            fn test_while_expression() {
+                let tests = vec![
+                    ("while (x < 10) {let x = x + 1;}", "while (x < 10) {let x = x + 1;}"),
+                    ("while (true) {break 42;}", "while (true) {break 42}"),
+                    ("while (true) {break}", "while (true) {break}"),
+                    ("let x = while (true) {break;};", "let x = while (true) {break};"),
+                    ];
+                for (input, expected) in tests.iter() {
+                    let lexer = Lexer::new(input);
+                    let mut parser = Parser::new(lexer);
+                    let program = parser.parse_program().unwrap();
+                    assert_eq!(program.statements.len(), 1);
+                    assert_eq!(format!("{}", program.statements[0]), *expected);
+                }
+                }
+
+            #[test]
+            fn test_do_expression() {
+                let tests = vec![
+                    ("let x = do { let y = 2; y * 3 };", "let x = do {let y = 2;y * 3};"),
+                    ("do {}", "do {}"),
+                    ];
+                for (input, expected) in tests.iter() {
+                    let mut parser = Parser::new_from_source(input);
+                    let program = parser.parse_program().unwrap();
+                    assert_eq!(program.statements.len(), 1);
+                    assert_eq!(format!("{}", program.statements[0]), *expected);
+                }
+                let mut parser = Parser::new_from_source("do 1");
+                assert!(parser.parse_program().is_err());
+                }
+
+            #[test]
+            fn test_optional_chain() {
+                let tests = vec![
+                    ("a?.[0]", "a?.[0]"),
+                    ("a?.b?.[1 + 2]", "a?.[b]?.[1 + 2]"),
+                    ("a?.b[0]", "a?.[b][0]"),
+                    ("-a?.[0]", "-a?.[0]"),
+                    ];
+                for (input, expected) in tests.iter() {
+                    let mut parser = Parser::new_from_source(input);
+                    let program = parser.parse_program().unwrap();
+                    assert_eq!(format!("{}", program.statements[0]), *expected);
+                }
+                for input in vec!["a?.1", "a?.[0", "a?."].into_iter() {
+                    let mut parser = Parser::new_from_source(input);
+                    assert!(parser.parse_program().is_err(), "{}", input);
+                }
+                }
+
+            #[test]
+            fn test_dot_expression() {
+                let tests = vec![
+                    ("m.pi", "m[\"pi\"]"),
+                    ("m.add(1, 2)", "m[\"add\"](1, 2)"),
+                    ("a.b.c", "a[\"b\"][\"c\"]"),
+                    ("-m.pi", "-m[\"pi\"]"),
+                    ("m.x + 1", "m[\"x\"] + 1"),
+                    ];
+                for (input, expected) in tests.into_iter() {
+                    let program = Parser::new_from_source(input).parse_program().unwrap();
+                    assert_eq!(program, Parser::new_from_source(expected).parse_program().unwrap(), "{}", input);
+                }
+                for input in vec!["m.1", "m.", "m.[0]"].into_iter() {
+                    assert!(Parser::new_from_source(input).parse_program().is_err(), "{}", input);
+                }
+            }
+
+            #[test]
+            fn test_brace_disambiguation() {
+                use std::collections::BTreeMap;
+                let parse = |input: &str| Parser::new_from_source(input).parse_program().map(|program| program.statements);
+                assert_eq!(parse("{}"), Ok(vec![Statement::ExpressionStatement(Expression::Hashmap(BTreeMap::new()))]));
+                assert_eq!(parse("let x = {};"), Ok(vec![Statement::LetStatement{identifier: Expression::Identifier(String::from("x")), annotation: None,
+                                                                                  value: Expression::Hashmap(BTreeMap::new())}]));
+                assert_eq!(parse("do {}"), Ok(vec![Statement::ExpressionStatement(Expression::DoExpression{body: Box::new(Statement::Block(vec![]))})]));
+                assert_eq!(parse("fn() {}"), Ok(vec![Statement::ExpressionStatement(Expression::FunctionLiteral{parameters: vec![],
+                                                                                                                body: Box::new(Statement::Block(vec![]))})]));
+                // statements can't be written in a hash.
+                assert!(parse("{ let x = 1; }").is_err());
+                }
+
             #[test]
             fn test_function_expression() {
                 let input = "fn (x, y) {x + y}".to_string();
@@ -607,11 +1376,47 @@ mod testing {
                 assert_eq!(input, statements);
                 }
 
+            #[test]
+            fn test_named_function() {
+                let tests = vec![
+                    ("fn add(x, y) {x + y}", "let add = fn (x, y) {x + y};"),
+                    ("fn one() {1}; one()", "let one = fn () {1};\none();\n"),
+                    ];
+                for (input, expected) in tests.into_iter() {
+                    let mut parser = Parser::new_from_source(input);
+                    let program = parser.parse_program().unwrap();
+                    assert_eq!(format!("{}", program).trim_end(), expected.trim_end());
+                }
+                // an anonymous function stays an expression.
+                let mut parser = Parser::new_from_source("fn (x) {x}(1)");
+                let program = parser.parse_program().unwrap();
+                assert!(match &program.statements[0] { Statement::ExpressionStatement(_) => true, _ => false });
+                let mut parser = Parser::new_from_source("fn add {}");
+                assert!(parser.parse_program().is_err());
+                }
+
+            #[test]
+            fn test_duplicate_parameter() {
+                let tests = vec![
+                    ("fn(x, x) {}", Some("x")),
+                    ("fn(x, y, x) { x }", Some("x")),
+                    ("fn f(a, b, b) {}", Some("b")),
+                    ("fn(x, y) {}", None),
+                    ("fn(x) { fn(x) { x } }", None),
+                    ];
+                for (input, expected) in tests.into_iter() {
+                    let mut parser = Parser::new_from_source(input);
+                    match expected {
+                        Some(name) => assert_eq!(parser.parse_program(), Err(Errors::DuplicateParameter(name.to_string())), "{}", input),
+                        None => assert!(parser.parse_program().is_ok(), "{}", input),
+                    }
+                }
+                }
+
             #[test]
             fn test_call_expression() {
                 let input = "add(1, 2 * 3, 4 + 5);".to_string();
-                let lexer = Lexer::new(&input);
-                let mut parser = Parser::new(lexer);
+                let mut parser = Parser::new_from_source(&input);
                 let program = parser.parse_program().unwrap();
                 let statements = format!("{}", program.statements[0]);
                 assert_eq!(input, statements);
@@ -629,8 +1434,7 @@ mod testing {
             #[test]
             fn test_parse_array_literals() {
                 let input = "[1, 2 * 2, 3 + 3]";
-                let lexer = Lexer::new(&input);
-                let mut parser = Parser::new(lexer);
+                let mut parser = Parser::new_from_source(input);
                 let program = parser.parse_program().unwrap();
                 let statements = format!("{}", program.statements[0]);
                 assert_eq!("[1, 2 * 2, 3 + 3]", statements);
@@ -671,4 +1475,80 @@ mod testing {
                 let statements = format!("{}", program.statements[0]);
                 assert_eq!("{one: 0 + 1, three: 15 / 5, two: 10 - 8}", statements);
                     }
-            }
\ No newline at end of file
+
+            #[test]
+            fn test_unexpected_eof() {
+                let tests = vec![
+                    ("fn() {", Some(TokenKind::RBRACE)),
+                    ("fn(x, y", Some(TokenKind::RPAREN)),
+                    ("[1,", None),
+                    ("[1, 2", Some(TokenKind::RBRACKET)),
+                    ("{\"a\": 1", Some(TokenKind::RBRACE)),
+                    ("add(1, 2", Some(TokenKind::RPAREN)),
+                    ("(1 + 2", Some(TokenKind::RPAREN)),
+                    ("if (x) { 1 } else", Some(TokenKind::LBRACE)),
+                    ("let x", Some(TokenKind::ASSIGN)),
+                    ("1 +", None),
+                    ("let s = \"a", None),
+                    ("print(\"a\n", None),
+                    ];
+                for (input, expected) in tests.iter() {
+                    let lexer = Lexer::new(input);
+                    let mut parser = Parser::new(lexer);
+                    assert_eq!(parser.parse_program(), Err(Errors::UnexpectedEof{expected: *expected}));
+                }
+
+                let lexer = Lexer::new("let = 5");
+                let mut parser = Parser::new(lexer);
+                match parser.parse_program() {
+                    Err(Errors::UnexpectedEof{..}) => panic!("let = 5 should be a syntax error"),
+                    Err(_) => {},
+                    Ok(_) => panic!("let = 5 should not be parsed"),
+                }
+                }
+
+            #[test]
+            fn test_parse_program_with_errors() {
+                let input = "let = 5;\nlet x = 1;\n  let y 2;\nx;";
+                let lexer = Lexer::new(&input);
+                let mut parser = Parser::new(lexer);
+                let errors = parser.parse_program_with_errors().unwrap_err();
+                let messages = errors.iter().map(|error| format!("{}", error)).collect::<Vec<_>>();
+                assert_eq!(messages.len(), 2);
+                assert!(messages[0].starts_with("line 1, column 5: "));
+                assert!(messages[1].starts_with("line 3, column 7: "));
+
+                let lexer = Lexer::new("let x = 1; x;");
+                let mut parser = Parser::new(lexer);
+                assert_eq!(parser.parse_program_with_errors().unwrap().statements.len(), 2);
+
+                // a character of several bytes is one column.
+                let lexer = Lexer::new("let s = \"é\"; let t 2;");
+                let mut parser = Parser::new(lexer);
+                let errors = parser.parse_program_with_errors().unwrap_err();
+                assert!(format!("{}", errors[0]).starts_with("line 1, column 18: "), "{}", errors[0]);
+
+                // the lines in a string are counted.
+                let mut parser = Parser::new_from_source("let s = \"a\nb\";\nlet t 2;");
+                let errors = parser.parse_program_with_errors().unwrap_err();
+                assert!(format!("{}", errors[0]).starts_with("line 3, column 5: "), "{}", errors[0]);
+                }
+
+            // run with `cargo test --features serde`.
+            #[cfg(feature = "serde")]
+            #[test]
+            fn test_serde_round_trip() {
+                let input = r#"let add = fn(x, y) { x + y; };
+let map = {"one": 1, 2: [1.5, true]};
+if (add(1, 2) > 2) { map["one"] } else { -1 }"#;
+                let mut parser = Parser::new_from_source(input);
+                let program = parser.parse_program().unwrap();
+                let json = serde_json::to_string(&program).unwrap();
+                let deserialized: crate::ast::Program = serde_json::from_str(&json).unwrap();
+                assert_eq!(deserialized, program);
+
+                let token = crate::token::Token{token_type: TokenKind::IDENT, literal: String::from("x")};
+                let json = serde_json::to_string(&token).unwrap();
+                assert_eq!(serde_json::from_str::<crate::token::Token>(&json).unwrap(), token);
+                }
+            }
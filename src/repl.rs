@@ -1,39 +1,202 @@
 extern crate rustyline;
 
+use std::io::{self, BufRead, Write};
+use std::time::Instant;
+
 use rustyline::error::ReadlineError;
 use rustyline::Editor;
 
 use crate::{lexer, parser, ast, errors, evaluator, object};
 
+// prompts which are shown before reading each line.
+pub struct ReplOptions {
+    pub prompt: String,
+    // used while a function or a block written over multiple lines isn't closed.
+    pub continuation_prompt: String,
+}
+
+impl Default for ReplOptions {
+    fn default() -> ReplOptions {
+        ReplOptions{prompt: String::from(">> "), continuation_prompt: String::from(".. ")}
+    }
+}
+
+enum Action {
+    Continue,
+    Exit,
+}
+
+// the state of the REPL which is kept between lines, so that a program can evaluate
+// lines one by one and read the variables bound by them like the console does.
+// it doesn't read or write anything, so other frontends than the console can use it.
+pub struct ReplSession {
+    // if environment is defined inside loop,
+    // it is initialized per iterator, and can't contain variables.
+    environment: evaluator::Environment,
+    // kept to make a new environment by reset().
+    config: evaluator::EvalConfig,
+}
+
+impl ReplSession {
+    pub fn new() -> ReplSession {
+        ReplSession::with_config(evaluator::EvalConfig::default())
+    }
+
+    pub fn with_config(config: evaluator::EvalConfig) -> ReplSession {
+        ReplSession{environment: evaluator::Environment::with_config(config.clone()), config}
+    }
+
+    // evaluate a complete line, and an error in the program is returned as Err.
+    pub fn eval_line(&mut self, line: &str) -> Result<object::Object, errors::Errors> {
+        let program = parse(line)?;
+        self.eval_program(&program)
+    }
+
+    // evaluate a program which has already been parsed, in the same way as eval_line.
+    pub fn eval_program(&mut self, program: &ast::Program) -> Result<object::Object, errors::Errors> {
+        match self.environment.evaluate(program)? {
+            object::Object::Error(error) => Err(error),
+            evaluated => Ok(evaluated)
+        }
+    }
+
+    // forget all variables bound so far, and start again with the same config.
+    pub fn reset(&mut self) {
+        self.environment = evaluator::Environment::with_config(self.config.clone());
+    }
+
+    // the environment which keeps the variables bound by the lines evaluated so far.
+    pub fn env(&self) -> &evaluator::Environment {
+        &self.environment
+    }
+}
+
+// reads lines for the session, and shows the results of them.
+struct Repl {
+    session: ReplSession,
+    // lines are kept until they make a complete program,
+    // so that a function or a block can be written over multiple lines.
+    input: String,
+}
+
+impl Repl {
+    fn new() -> Repl {
+        Repl{session: ReplSession::new(), input: String::new()}
+    }
+
+    fn prompt<'a>(&self, options: &'a ReplOptions) -> &'a str {
+        if self.input.is_empty() {
+            &options.prompt
+        } else {
+            &options.continuation_prompt
+        }
+    }
+
+    fn read_line(&mut self, line: &str, writer: &mut dyn Write) -> io::Result<Action> {
+        if self.input.is_empty() {
+            if line == "" {
+                return Ok(Action::Continue);
+            }
+            // the command to exit
+            if line == "exit()" {
+                writeln!(writer, "Bye!")?;
+                return Ok(Action::Exit);
+            }
+
+            else if line == "exit" {
+                writeln!(writer, "if you would like to exit, please use exit(), ctrl-c, or ctrl-d")?;
+                return Ok(Action::Continue);
+            }
+            // evaluate code written in the same line and show how long it took.
+            else if line.starts_with(":time ") {
+                let start = Instant::now();
+                self.evaluate(&format!("{}\n", &line[":time ".len()..]), writer)?;
+                writeln!(writer, "time: {:?}", start.elapsed())?;
+                return Ok(Action::Continue);
+            }
+            // show the tokens of code written in the same line with their positions.
+            else if line.starts_with(":lex ") {
+                for (token, row, column) in lexer::tokenize_with_positions(&line[":lex ".len()..]) {
+                    writeln!(writer, "{}:{}\t{:?}\t{}", row, column, token.token_type, token.literal)?;
+                }
+                return Ok(Action::Continue);
+            }
+            // evaluate code written in the same line and show only the type of the result.
+            else if line.starts_with(":type ") {
+                self.show_type(&format!("{}\n", &line[":type ".len()..]), writer)?;
+                return Ok(Action::Continue);
+            }
+        }
+
+        self.input.push_str(line);
+        self.input.push('\n');
+        let input = self.input.clone();
+        if !self.evaluate(&input, writer)? {
+            // the program isn't closed yet, so wait for the next line.
+            return Ok(Action::Continue);
+        }
+        self.input.clear();
+        Ok(Action::Continue)
+    }
+
+    // evaluate the input and show the result.
+    // false is returned if the input isn't closed yet.
+    fn evaluate(&mut self, input: &str, writer: &mut dyn Write) -> io::Result<bool> {
+        let program = match parse(input) {
+            Ok(program) => program,
+            Err(errors::Errors::UnexpectedEof{..}) => return Ok(false),
+            Err(error) => {
+                writeln!(writer, "{}", error)?;
+                return Ok(true);
+            }
+        };
+        match self.session.eval_program(&program) {
+            Ok(object::Object::Null) => {},
+            Ok(evaluated) => if is_expression(&program) {
+                writeln!(writer, "{}", evaluated.inspect(&object::InspectConfig::default()))?
+            },
+            Err(error) => writeln!(writer, "{}", error)?,
+        }
+        Ok(true)
+    }
+
+    // show the type name of the result instead of its value, or the error if it fails.
+    fn show_type(&mut self, input: &str, writer: &mut dyn Write) -> io::Result<()> {
+        match self.session.eval_line(input) {
+            Ok(evaluated) => writeln!(writer, "{}", evaluated.type_name()),
+            Err(error) => writeln!(writer, "{}", error),
+        }
+    }
+}
+
+// only the value of an expression is shown, because the value of
+// a statement like let is the same as what was just written.
+fn is_expression(program: &ast::Program) -> bool {
+    match program.statements.last() {
+        Some(ast::Statement::ExpressionStatement(ast::Expression::WhileExpression{..})) => false,
+        Some(ast::Statement::ExpressionStatement(_)) => true,
+        _ => false
+    }
+}
+
 pub fn start() {
+    let options = ReplOptions::default();
     let mut rl = Editor::<()>::new();
-    // if environment is defined outside loop,
-    // initialize it per iterator, and can't contain variables.
-    let mut environment = evaluator::Environment::new();
+    let mut repl = Repl::new();
+    let stdout = io::stdout();
     loop {
-        let readline = rl.readline(">> ");
+        let readline = rl.readline(repl.prompt(&options));
         match readline {
             Ok(line) => {
                 rl.add_history_entry(&line);
-                if line == "" {
-                    continue;
-                }
-                // the command to exit
-                if line == "exit()" {
-                    println!("Bye!");
-                    break;
+                match repl.read_line(&line, &mut stdout.lock()) {
+                    Ok(Action::Continue) => continue,
+                    Ok(Action::Exit) => break,
+                    Err(err) => {
+                        println!("error: {:?}", err);
+                        break
+                    }
                 }
-
-                else if line == "exit" {
-                    println!("if you would like to exit, please use exit(), ctrl-c, or ctrl-d");
-                    continue;
-                }
-
-                let lexer = lexer::Lexer::new(&line);
-                let mut parser = parser::Parser::new(lexer);
-                let program = parser.parse_program().unwrap();
-                let evaluated = environment.evaluate(&program);
-                println!("{}", evaluated.unwrap());
             },
             Err(ReadlineError::Interrupted) => {
                 println!("ctrl-c");
@@ -50,3 +213,154 @@ pub fn start() {
         }
     }
 }
+
+// run the REPL over any input and output instead of the terminal.
+pub fn start_with_options<R: BufRead, W: Write>(mut reader: R, mut writer: W, options: ReplOptions) -> io::Result<()> {
+    let mut repl = Repl::new();
+    loop {
+        write!(writer, "{}", repl.prompt(&options))?;
+        writer.flush()?;
+        let mut line = String::new();
+        if reader.read_line(&mut line)? == 0 {
+            return Ok(());
+        }
+        let line = line.trim_end_matches(|ch| ch == '\n' || ch == '\r');
+        if let Action::Exit = repl.read_line(line, &mut writer)? {
+            return Ok(());
+        }
+    }
+}
+
+// a newline ends a statement in the REPL, so that `1\n-1` isn't read as `1 - 1`.
+fn parse(input: &str) -> Result<ast::Program, errors::Errors> {
+    let mut parser = parser::Parser::new_newline_terminated(input);
+    parser.parse_program()
+}
+
+#[cfg(test)]// test runs only when execute cargo run
+mod testing {
+    use std::io::Cursor;
+    use crate::repl::{start_with_options, parse, ReplOptions, ReplSession};
+    use crate::object::Object;
+    use crate::errors::Errors;
+    use crate::evaluator::EvalConfig;
+
+    fn run(input: &str, options: ReplOptions) -> String {
+        let mut output: Vec<u8> = Vec::new();
+        start_with_options(Cursor::new(input), &mut output, options).unwrap();
+        String::from_utf8(output).unwrap()
+    }
+
+    #[test]
+    fn test_prompts() {
+        let output = run("let x = 1;\nx\nexit()\n", ReplOptions::default());
+        assert_eq!(output, ">> >> 1\n>> Bye!\n");
+
+        let options = ReplOptions{prompt: String::from("monkey> "), continuation_prompt: String::from("... ")};
+        let output = run("[1,\n2,\n3]\n", options);
+        assert_eq!(output, "monkey> ... ... [1, 2, 3]\nmonkey> ");
+
+        // a string is continued until the closing quote.
+        let output = run("let s = \"a\nb\";\nlen(s)\n", ReplOptions::default());
+        assert_eq!(output, ">> .. >> 3\n>> ");
+    }
+
+    #[test]
+    fn test_echo_only_expressions() {
+        let tests = vec![
+            ("let x = 1;\n", ">> >> "),
+            ("const x = 1;\n", ">> >> "),
+            ("return 1;\n", ">> >> "),
+            ("1 + 1\n", ">> 2\n>> "),
+            ("let x = 1; x + 1\n", ">> 2\n>> "),
+            ("let f = fn(x) {x};\nf(3)\n", ">> >> 3\n>> "),
+            ("if (false) {1}\n", ">> >> "),
+            ("if (true) {1}\n", ">> 1\n>> "),
+            ("if (true) {print(\"\")}\n", ">> >> "),
+            ("let i = 0; while (i < 2) {let i = i + 1; i}\n", ">> >> "),
+            ("let x = while (true) {break 3;}; x\n", ">> 3\n>> "),
+            ("5 + true\n", ">> type mismatch: INTEGER + BOOLEAN\n>> "),
+            ];
+        for (input, expected) in tests.iter() {
+            assert_eq!(run(input, ReplOptions::default()), *expected);
+        }
+    }
+
+    #[test]
+    fn test_long_array_is_cut_off() {
+        let output = run("let a = [];\nlet i = 0; while (i < 150) { let a = push(a, i); let i = i + 1; }\na\n", ReplOptions::default());
+        let items: Vec<String> = (0..100).map(|i| i.to_string()).collect();
+        assert_eq!(output, format!(">> >> >> [{}, ...]\n>> ", items.join(", ")));
+    }
+
+    #[test]
+    fn test_time_command() {
+        let output = run(":time 1 + 1\n", ReplOptions::default());
+        let lines: Vec<&str> = output.trim_start_matches(">> ").lines().collect();
+        assert_eq!(lines[0], "2");
+        // the duration depends on the machine, so only the format is checked.
+        assert!(lines[1].starts_with("time: "), "{}", lines[1]);
+        assert!(lines[1].ends_with('s'), "{}", lines[1]);
+
+        // the environment is shared with other lines.
+        let output = run("let x = 3;\n:time x * 2\nx\n", ReplOptions::default());
+        let lines: Vec<&str> = output.lines().collect();
+        assert_eq!(lines[0], ">> >> 6");
+        assert!(lines[1].starts_with("time: "), "{}", lines[1]);
+        assert_eq!(lines[2], ">> 3");
+    }
+
+    #[test]
+    fn test_lex_command() {
+        let output = run(":lex let x = 10;\n", ReplOptions::default());
+        assert_eq!(output, ">> 1:1\tLET\tlet\n1:5\tIDENT\tx\n1:7\tASSIGN\t=\n1:9\tINT\t10\n1:11\tSEMICOLON\t;\n>> ");
+    }
+
+    #[test]
+    fn test_type_command() {
+        let tests = vec![
+            (":type \"x\"\n", ">> STRING\n>> "),
+            (":type [1,2,3]\n", ">> ARRAY\n>> "),
+            (":type if (false) {1}\n", ">> NULL\n>> "),
+            (":type 5 + true\n", ">> type mismatch: INTEGER + BOOLEAN\n>> "),
+            (":type (1 + 2\n", ">> unexpected end of input, expected RPAREN\n>> "),
+            // the environment is shared with other lines.
+            ("let f = fn(x) {x};\n:type f\n", ">> >> FUNCTION\n>> "),
+            ];
+        for (input, expected) in tests.iter() {
+            assert_eq!(run(input, ReplOptions::default()), *expected, "{}", input);
+        }
+    }
+
+    #[test]
+    fn test_session() {
+        let mut session = ReplSession::new();
+        assert_eq!(session.eval_line("let x = 2;"), Ok(Object::Integer(2)));
+        assert_eq!(session.eval_line("let y = x * 3"), Ok(Object::Integer(6)));
+        assert_eq!(session.env().get("y"), Some(Object::Integer(6)));
+        assert_eq!(session.env().get("z"), None);
+
+        assert_eq!(session.eval_line("y + true"), Err(Errors::TypeMismatch{left: String::from("INTEGER"),
+                                                                           operator: String::from("+"),
+                                                                           right: String::from("BOOLEAN")}));
+        assert_eq!(session.eval_line("[1,"), Err(Errors::UnexpectedEof{expected: None}));
+
+        // a program which has already been parsed is evaluated in the same environment.
+        let program = parse("x + 1").unwrap();
+        assert_eq!(session.eval_program(&program), Ok(Object::Integer(3)));
+    }
+
+    #[test]
+    fn test_session_reset() {
+        let config = EvalConfig{bool_arithmetic: true, ..EvalConfig::default()};
+        let mut session = ReplSession::with_config(config);
+        assert_eq!(session.eval_line("let x = true + true"), Ok(Object::Integer(2)));
+        assert_eq!(session.eval_line("x * 2"), Ok(Object::Integer(4)));
+
+        session.reset();
+        assert_eq!(session.env().get("x"), None);
+        assert_eq!(session.eval_line("x"), Ok(Object::Null));
+        // the config is kept after reset.
+        assert_eq!(session.eval_line("true + 1"), Ok(Object::Integer(2)));
+    }
+}
@@ -0,0 +1,239 @@
+use std::collections::BTreeSet;
+
+use super::ast::{Program, Statement, Expression};
+
+// annotate identifiers with the number of function scopes between where they are used
+// and where they are bound, so that evaluation looks up the scope directly
+// instead of searching every scope on the way.
+// a scope is counted only if nothing can bind the name in the scopes inside it,
+// because let inside if or while binds the name only when it runs.
+// names bound nowhere like builtins are left as they are.
+pub fn resolve(program: &Program) -> Program {
+    let mut resolver = Resolver{scopes: vec![bound_names(&program.statements, &[])]};
+    Program{statements: program.statements.iter().map(|statement| resolver.statement(statement)).collect()}
+}
+
+struct Resolver {
+    // names which may be bound in each function scope, from the outermost program.
+    scopes: Vec<BTreeSet<String>>,
+}
+
+impl Resolver {
+    fn statement(&mut self, statement: &Statement) -> Statement {
+        match statement {
+            Statement::LetStatement{identifier, annotation, value} => Statement::LetStatement{identifier: identifier.clone(), annotation: annotation.clone(), value: self.expression(value)},
+            Statement::ConstStatement{identifier, value} => Statement::ConstStatement{identifier: identifier.clone(), value: self.expression(value)},
+            Statement::Assign{target, value} => Statement::Assign{target: self.expression(target), value: self.expression(value)},
+            Statement::Return(value) => Statement::Return(self.expression(value)),
+            Statement::Break(value) => Statement::Break(value.as_ref().map(|value| self.expression(value))),
+            Statement::ExpressionStatement(value) => Statement::ExpressionStatement(self.expression(value)),
+            Statement::Block(statements) => Statement::Block(statements.iter().map(|statement| self.statement(statement)).collect()),
+            Statement::Import{path, alias} => Statement::Import{path: path.clone(), alias: alias.clone()},
+            Statement::Commented{leading_comments, statement, trailing_comment} =>
+                Statement::Commented{leading_comments: leading_comments.clone(), statement: Box::new(self.statement(statement)), trailing_comment: trailing_comment.clone()},
+        }
+    }
+
+    fn expression(&mut self, expression: &Expression) -> Expression {
+        match expression {
+            Expression::Identifier(name) => match self.depth(name) {
+                Some(depth) => Expression::ResolvedIdentifier{name: name.clone(), depth: depth},
+                None => expression.clone()
+            },
+            Expression::Array(values) => Expression::Array(values.iter().map(|value| self.expression(value)).collect()),
+            Expression::Hashmap(tree) => Expression::Hashmap(tree.iter().map(|(key, value)| (Box::new(self.expression(key)), Box::new(self.expression(value)))).collect()),
+            Expression::IndexExpression{array, subscript} => Expression::IndexExpression{array: Box::new(self.expression(array)),
+                                                                                         subscript: Box::new(self.expression(subscript))},
+            Expression::OptionalIndexExpression{array, subscript} => Expression::OptionalIndexExpression{array: Box::new(self.expression(array)),
+                                                                                                         subscript: Box::new(self.expression(subscript))},
+            Expression::PrefixExpression{operator, right_expression} => Expression::PrefixExpression{operator: operator.clone(),
+                                                                                                     right_expression: Box::new(self.expression(right_expression))},
+            Expression::InfixExpression{left_expression, operator, right_expression} => Expression::InfixExpression{left_expression: Box::new(self.expression(left_expression)),
+                                                                                                                    operator: operator.clone(),
+                                                                                                                    right_expression: Box::new(self.expression(right_expression))},
+            Expression::IfExpression{condition, consequence, alternative} => Expression::IfExpression{condition: Box::new(self.expression(condition)),
+                                                                                                      consequence: Box::new(self.statement(consequence)),
+                                                                                                      alternative: alternative.as_ref().map(|alternative| Box::new(self.statement(alternative)))},
+            Expression::WhileExpression{condition, body} => Expression::WhileExpression{condition: Box::new(self.expression(condition)),
+                                                                                        body: Box::new(self.statement(body))},
+            Expression::DoExpression{body} => Expression::DoExpression{body: Box::new(self.statement(body))},
+            Expression::FunctionLiteral{parameters, body} => {
+                self.scopes.push(bound_names(std::slice::from_ref(&**body), parameters));
+                let body = self.statement(body);
+                self.scopes.pop();
+                Expression::FunctionLiteral{parameters: parameters.clone(), body: Box::new(body)}
+            },
+            Expression::CallExpression{function, body} => Expression::CallExpression{function: Box::new(self.expression(function)),
+                                                                                      body: body.iter().map(|argument| self.expression(argument)).collect()},
+            _ => expression.clone()
+        }
+    }
+
+    fn depth(&self, name: &str) -> Option<usize> {
+        self.scopes.iter().rev().position(|scope| scope.contains(name))
+    }
+}
+
+// names bound by parameters and let or const anywhere in a function body,
+// except for inside functions defined in it.
+fn bound_names(statements: &[Statement], parameters: &[Expression]) -> BTreeSet<String> {
+    let mut names = BTreeSet::new();
+    for parameter in parameters.iter() {
+        if let Expression::Identifier(name) = parameter {
+            names.insert(name.clone());
+        }
+    }
+    for statement in statements.iter() {
+        collect_statement(statement, &mut names);
+    }
+    names
+}
+
+fn collect_statement(statement: &Statement, names: &mut BTreeSet<String>) {
+    match statement {
+        Statement::LetStatement{identifier, value, ..} | Statement::ConstStatement{identifier, value} => {
+            if let Expression::Identifier(name) = identifier {
+                names.insert(name.clone());
+            }
+            collect_expression(value, names);
+        },
+        Statement::Assign{target, value} => {
+            collect_expression(target, names);
+            collect_expression(value, names);
+        },
+        Statement::Return(value) | Statement::Break(Some(value)) | Statement::ExpressionStatement(value) => collect_expression(value, names),
+        Statement::Import{alias: Some(alias), ..} => {
+            names.insert(alias.clone());
+        },
+        // names bound by an imported file aren't known until it is evaluated, so they are left unresolved.
+        Statement::Break(None) | Statement::Import{alias: None, ..} => {},
+        Statement::Commented{statement, ..} => collect_statement(statement, names),
+        Statement::Block(statements) => {
+            for statement in statements.iter() {
+                collect_statement(statement, names);
+            }
+        },
+    }
+}
+
+fn collect_expression(expression: &Expression, names: &mut BTreeSet<String>) {
+    match expression {
+        Expression::Array(values) => {
+            for value in values.iter() {
+                collect_expression(value, names);
+            }
+        },
+        Expression::Hashmap(tree) => {
+            for (key, value) in tree.iter() {
+                collect_expression(key, names);
+                collect_expression(value, names);
+            }
+        },
+        Expression::IndexExpression{array, subscript} | Expression::OptionalIndexExpression{array, subscript} => {
+            collect_expression(array, names);
+            collect_expression(subscript, names);
+        },
+        Expression::PrefixExpression{right_expression, ..} => collect_expression(right_expression, names),
+        Expression::InfixExpression{left_expression, right_expression, ..} => {
+            collect_expression(left_expression, names);
+            collect_expression(right_expression, names);
+        },
+        Expression::IfExpression{condition, consequence, alternative} => {
+            collect_expression(condition, names);
+            collect_statement(consequence, names);
+            if let Some(alternative) = alternative {
+                collect_statement(alternative, names);
+            }
+        },
+        Expression::WhileExpression{condition, body} => {
+            collect_expression(condition, names);
+            collect_statement(body, names);
+        },
+        Expression::DoExpression{body} => collect_statement(body, names),
+        Expression::CallExpression{function, body} => {
+            collect_expression(function, names);
+            for argument in body.iter() {
+                collect_expression(argument, names);
+            }
+        },
+        // a function has its own scope.
+        _ => {}
+    }
+}
+
+#[cfg(test)]// test runs only when execute cargo run
+mod testing {
+    use std::time::Instant;
+    use crate::parser::{Parser};
+    use crate::ast::{Program};
+    use crate::ast_json::{ast_to_json};
+    use crate::evaluator::{Environment, EvalConfig};
+    use crate::resolver::{resolve};
+
+    fn parse(input: &str) -> Program {
+        let mut parser = Parser::new_from_source(input);
+        parser.parse_program().unwrap()
+    }
+
+    #[test]
+    fn test_resolve_depth() {
+        let json = ast_to_json(&resolve(&parse("let x = 1; let f = fn(y) { fn(z) { x + y + z + len } };")));
+        assert!(json.contains("{\"type\":\"ResolvedIdentifier\",\"name\":\"x\",\"depth\":2}"));
+        assert!(json.contains("{\"type\":\"ResolvedIdentifier\",\"name\":\"y\",\"depth\":1}"));
+        assert!(json.contains("{\"type\":\"ResolvedIdentifier\",\"name\":\"z\",\"depth\":0}"));
+        // builtins aren't bound anywhere.
+        assert!(json.contains("{\"type\":\"Identifier\",\"name\":\"len\"}"));
+        // the names which are bound aren't changed.
+        assert!(json.contains("\"identifier\":{\"type\":\"Identifier\",\"name\":\"f\"}"));
+    }
+
+    #[test]
+    fn test_evaluate_resolved() {
+        let tests = vec![
+                // a variable captured several scopes out.
+                ("let x = 1; let f = fn(y) { fn(z) { fn() { x + y + z } } }; f(2)(3)()", "6"),
+                ("let x = 1; let f = fn(x) { fn() { x } }; f(2)()", "2"),
+                // x may be bound inside f, so it isn't resolved to the outer x.
+                ("let x = 1; let f = fn(c) { if (c) { let x = 2; } x }; [f(true), f(false)]", "[2, 1]"),
+                ("let x = 1; let f = fn() { let y = x; let x = 2; [y, x] }; f()", "[1, 2]"),
+                ("let a = [1, 2]; let f = fn() { a[0] = 3; a }; [f(), a]", "[[3, 2], [1, 2]]"),
+                ("let n = 0; while (n < 3) { n = n + 1; } n", "3"),
+                ("len([1, 2])", "2"),
+                ];
+        for (input, expected) in tests.into_iter() {
+            let program = parse(input);
+            let resolved = resolve(&program);
+            let unresolved_value = Environment::new().evaluate(&program).unwrap();
+            let resolved_value = Environment::new().evaluate(&resolved).unwrap();
+            assert_eq!(format!("{}", resolved_value), expected, "{}", input);
+            assert_eq!(resolved_value, unresolved_value, "{}", input);
+            // the evaluator resolves the program by itself with the config.
+            let config = EvalConfig{resolve_names: true, ..EvalConfig::default()};
+            assert_eq!(Environment::with_config(config).evaluate(&program).unwrap(), unresolved_value, "{}", input);
+        }
+    }
+
+    // run with `cargo test --release -- --ignored --nocapture` to compare the time of lookups.
+    // x is bound 8 scopes out, so a lookup which isn't resolved searches each scope on the way.
+    #[test]
+    #[ignore]
+    fn bench_resolved_lookup() {
+        let input = "let x = 1;
+let f = fn(a) { fn(b) { fn(c) { fn(d) { fn(e) { fn(g) { fn(h) { fn(k) {
+  let i = 0; let total = 0; while (i < 20000) { let total = total + x + x + x; let i = i + 1; } total
+} } } } } } } };
+f(1)(2)(3)(4)(5)(6)(7)(8)";
+        let program = parse(input);
+        // the fastest of some runs, so that a pause of the machine doesn't decide the result.
+        let time = |resolve_names: bool| (0..5).map(|_| {
+            let mut env = Environment::with_config(EvalConfig{resolve_names: resolve_names, ..EvalConfig::default()});
+            let start = Instant::now();
+            assert_eq!(format!("{}", env.evaluate(&program).unwrap()), "60000");
+            start.elapsed()
+        }).min().unwrap();
+        let unresolved = time(false);
+        let resolved = time(true);
+        println!("unresolved: {:?}, resolved: {:?}", unresolved, resolved);
+        assert!(resolved < unresolved, "resolved: {:?}, unresolved: {:?}", resolved, unresolved);
+    }
+}
@@ -1,6 +1,12 @@
+use std::fmt;
+
 use super::ast::{Precedence};
 
+#[cfg(feature = "serde")]
+use serde::{Serialize, Deserialize};
+
 #[derive(Debug,Eq,PartialEq, Clone, Copy, Hash, Ord, PartialOrd)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum TokenKind {
      ILLEGAL,     // ILLEGAL
      EOF,         // EOF
@@ -8,6 +14,7 @@ pub enum TokenKind {
     // identifier and literal
      IDENT,       // IDENT
      INT,         // 123...
+     FLOAT,       // 1.5, 1e3...
 
     // operator
      ASSIGN,      // =
@@ -16,14 +23,21 @@ pub enum TokenKind {
      BANG,        // !
      ASTERISK,    // *
      SLASH,       // /
+     IntDiv,      // //
      LT,          // <
      GT,          // >
      EQ,          // ==
      NotEq,      // !=
+     PIPE,        // |>
+     OptionalChain, // ?.
+     DOT,         // .
+     NullCoalesce, // ??
 
      // delimiter
      COMMA,       // ,
      SEMICOLON,   // ;
+     NEWLINE,     // \n, only when the lexer is asked for it
+     COMMENT,     // # ..., only when the lexer is asked for it
 
      LPAREN,      // (
      RPAREN,      // )
@@ -33,11 +47,17 @@ pub enum TokenKind {
      // keyword
      FUNCTION,    // FUNCTION
      LET,         // LET
+     CONST,       // const
      TRUE,        // true
      FALSE,       // false
      IF,          // if
      ELSE,        // else
      RETURN,      // return
+     WHILE,       // while
+     BREAK,       // break
+     DO,          // do
+     IMPORT,      // import
+     AS,          // as
 
      STRING,
 
@@ -51,56 +71,140 @@ pub enum TokenKind {
      DEFAULT,
 }
 
+// keywords which are shared by the lexer and other tools like autocomplete.
+pub const KEYWORDS: &[(&str, TokenKind)] = &[
+    ("let", TokenKind::LET),
+    ("const", TokenKind::CONST),
+    ("fn", TokenKind::FUNCTION),
+    ("true", TokenKind::TRUE),
+    ("false", TokenKind::FALSE),
+    ("if", TokenKind::IF),
+    ("else", TokenKind::ELSE),
+    ("return", TokenKind::RETURN),
+    ("while", TokenKind::WHILE),
+    ("break", TokenKind::BREAK),
+    ("do", TokenKind::DO),
+    ("import", TokenKind::IMPORT),
+    ("as", TokenKind::AS),
+];
+
+pub fn lookup_keyword(ident: &str) -> Option<TokenKind> {
+    KEYWORDS.iter().find(|(keyword, _)| *keyword == ident).map(|(_, kind)| *kind)
+}
+
+// an identifier which isn't a keyword is IDENT.
 pub fn get_keyword(ident: &str) -> TokenKind {
-    match ident {
-        "let" => {
-            TokenKind::LET
-        }
-        "fn" => {
-            TokenKind::FUNCTION
-        }
-        "true" => {
-            TokenKind::TRUE
-        }
-        "false" => {
-            TokenKind::FALSE
-        }
-        "if" => {
-            TokenKind::IF
-        }
-        "else" => {
-            TokenKind::ELSE
-        }
-        "return" => {
-            TokenKind::RETURN
-        }
-        _ => {
-            TokenKind::IDENT
-        }
-    }
+    lookup_keyword(ident).unwrap_or(TokenKind::IDENT)
 }
 
 // if other module refers to Token, pub is needed to write 
 #[derive(Debug, Clone, PartialEq, Hash,Eq,Ord, PartialOrd)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Token {
     pub token_type: TokenKind,
     pub literal: String
 }
 
+// the form of a token shown in error messages.
+// names and numbers are shown as they are, and symbols and keywords are quoted.
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.token_type {
+            TokenKind::EOF => write!(f, "<EOF>"),
+            TokenKind::NEWLINE => write!(f, "<newline>"),
+            TokenKind::IDENT | TokenKind::INT | TokenKind::FLOAT => write!(f, "{}", self.literal),
+            TokenKind::STRING => write!(f, "\"{}\"", self.literal),
+            _ => write!(f, "'{}'", self.literal),
+        }
+    }
+}
+
+// operators and their precedence, which the parser and tools like documentation share.
+// a token which isn't here has the lowest precedence.
+pub const PRECEDENCES: &[(TokenKind, Precedence)] = &[
+    (TokenKind::NullCoalesce, Precedence::COALESCE),
+    (TokenKind::PIPE, Precedence::PIPE),
+    (TokenKind::EQ, Precedence::EQUALS),
+    (TokenKind::NotEq, Precedence::EQUALS),
+    (TokenKind::LT, Precedence::LESSGREATER),
+    (TokenKind::GT, Precedence::LESSGREATER),
+    (TokenKind::PLUS, Precedence::SUM),
+    (TokenKind::MINUS, Precedence::SUM),
+    (TokenKind::SLASH, Precedence::PRODUCT),
+    (TokenKind::IntDiv, Precedence::PRODUCT),
+    (TokenKind::ASTERISK, Precedence::PRODUCT),
+    (TokenKind::LPAREN, Precedence::CALL),
+    (TokenKind::LBRACKET, Precedence::LBRACKET),
+    (TokenKind::OptionalChain, Precedence::LBRACKET),
+    (TokenKind::DOT, Precedence::LBRACKET),
+];
+
+pub fn precedence_table() -> Vec<(TokenKind, Precedence)> {
+    PRECEDENCES.to_vec()
+}
+
 impl Token {
     pub fn get_precedence(&mut self) -> Precedence {
-        match self.token_type {
-            TokenKind::EQ => Precedence::EQUALS,
-            TokenKind::NotEq => Precedence::EQUALS,
-            TokenKind::LT => Precedence::LESSGREATER,        
-            TokenKind::GT => Precedence::LESSGREATER,        
-            TokenKind::PLUS => Precedence::SUM,
-            TokenKind::MINUS => Precedence::SUM,
-            TokenKind::SLASH => Precedence::PRODUCT,
-            TokenKind::ASTERISK => Precedence::PRODUCT,
-            TokenKind::LPAREN => Precedence::CALL,
-            TokenKind::LBRACKET => Precedence::LBRACKET,
-            _                   => Precedence::LOWEST
+        PRECEDENCES.iter()
+                   .find(|(kind, _)| *kind == self.token_type)
+                   .map(|(_, precedence)| *precedence)
+                   .unwrap_or(Precedence::LOWEST)
+    }
+}
+
+#[cfg(test)]// test runs only when execute cargo run
+mod testing {
+    use crate::token::{lookup_keyword, get_keyword, precedence_table, Token, TokenKind, KEYWORDS};
+    use crate::parser::Parser;
+    use crate::ast::Precedence;
+
+    #[test]
+    fn test_lookup_keyword() {
+        assert_eq!(lookup_keyword("fn"), Some(TokenKind::FUNCTION));
+        assert_eq!(lookup_keyword("while"), Some(TokenKind::WHILE));
+        assert_eq!(lookup_keyword("foo"), None);
+        assert_eq!(lookup_keyword("Fn"), None);
+        assert_eq!(get_keyword("foo"), TokenKind::IDENT);
+        for (keyword, kind) in KEYWORDS.iter() {
+            assert_eq!(get_keyword(keyword), *kind);
         }
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_precedence_table() {
+        let table = precedence_table();
+        for (kind, precedence) in table.iter() {
+            let mut token = Token{token_type: *kind, literal: String::new()};
+            assert_eq!(token.get_precedence(), *precedence, "{:?}", kind);
+            assert!(*precedence > Precedence::LOWEST, "{:?}", kind);
+        }
+        // each operator appears once.
+        for (i, (kind, _)) in table.iter().enumerate() {
+            assert!(table[i + 1..].iter().all(|(other, _)| other != kind), "{:?}", kind);
+        }
+        for kind in vec![TokenKind::BANG, TokenKind::SEMICOLON, TokenKind::IDENT, TokenKind::ASSIGN].into_iter() {
+            assert_eq!(Token{token_type: kind, literal: String::new()}.get_precedence(), Precedence::LOWEST);
+        }
+        let mut plus = Token{token_type: TokenKind::PLUS, literal: String::from("+")};
+        let mut asterisk = Token{token_type: TokenKind::ASTERISK, literal: String::from("*")};
+        assert!(plus.get_precedence() < asterisk.get_precedence());
+    }
+
+    #[test]
+    fn test_display() {
+        let tests = vec![
+            (TokenKind::LPAREN, "(", "'('"),
+            (TokenKind::IDENT, "foo", "foo"),
+            (TokenKind::INT, "12", "12"),
+            (TokenKind::STRING, "a b", "\"a b\""),
+            (TokenKind::LET, "let", "'let'"),
+            (TokenKind::ILLEGAL, "@", "'@'"),
+            (TokenKind::EOF, "", "<EOF>"),
+            ];
+        for (token_type, literal, expected) in tests.into_iter() {
+            assert_eq!(format!("{}", Token{token_type: token_type, literal: literal.to_string()}), expected);
+        }
+        let error = Parser::new_from_source("let x = );").parse_program().unwrap_err();
+        assert_eq!(format!("{}", error), "invalid token: ')'");
+    }
+}
@@ -0,0 +1,231 @@
+use super::ast::{Program, Statement, Expression};
+use super::errors::{Errors};
+use super::evaluator::{is_arithmetic};
+
+// report expressions which are an error whenever they are evaluated, like 1 + "x".
+// only the types of literals and of operators on them are inferred, and a name or a call has an unknown type,
+// so that nothing which may work at runtime is reported.
+// an expression is reported even if it is in a branch or a function which may never be evaluated,
+// but not if it is never evaluated, like a branch of if (false) or the right of ?? after a value which isn't null.
+pub fn infer(program: &Program) -> Vec<Errors> {
+    let mut checker = Checker{errors: vec![]};
+    for statement in program.statements.iter() {
+        checker.statement(statement);
+    }
+    checker.errors
+}
+
+// the type of a value in the name of Object::type_name, or None if it isn't known before evaluation.
+type Type = Option<&'static str>;
+
+struct Checker {
+    errors: Vec<Errors>,
+}
+
+impl Checker {
+    fn statement(&mut self, statement: &Statement) {
+        match statement {
+            Statement::LetStatement{value, ..} | Statement::ConstStatement{value, ..} | Statement::Return(value) |
+            Statement::Break(Some(value)) | Statement::ExpressionStatement(value) => {
+                self.expression(value);
+            },
+            Statement::Assign{target, value} => {
+                self.expression(target);
+                self.expression(value);
+            },
+            Statement::Block(statements) => {
+                for statement in statements.iter() {
+                    self.statement(statement);
+                }
+            },
+            Statement::Break(None) | Statement::Import{..} => {},
+            Statement::Commented{statement, ..} => self.statement(statement),
+        }
+    }
+
+    // the type of the expression. an expression which has been reported has an unknown type,
+    // so that an error is reported only once.
+    fn expression(&mut self, expression: &Expression) -> Type {
+        match expression {
+            Expression::Integer(_) => Some("INTEGER"),
+            Expression::Float(_) => Some("FLOAT"),
+            Expression::String(_) => Some("STRING"),
+            Expression::Bool(_) => Some("BOOLEAN"),
+            Expression::Array(values) => {
+                for value in values.iter() {
+                    self.expression(value);
+                }
+                Some("ARRAY")
+            },
+            Expression::Hashmap(tree) => {
+                for (key, value) in tree.iter() {
+                    self.expression(key);
+                    self.expression(value);
+                }
+                Some("HASH")
+            },
+            Expression::FunctionLiteral{body, ..} => {
+                self.statement(body);
+                Some("FUNCTION")
+            },
+            Expression::PrefixExpression{operator, right_expression} => {
+                let right = self.expression(right_expression);
+                self.report(prefix_type(operator, right?))
+            },
+            Expression::InfixExpression{left_expression, operator, right_expression} => {
+                let left = self.expression(left_expression);
+                // the right of ?? isn't evaluated when the left isn't null.
+                if operator == "??" && left.is_some() && left != Some("NULL") {
+                    return left;
+                }
+                let right = self.expression(right_expression);
+                match (left, operator.as_str()) {
+                    (Some("NULL"), "??") => right,
+                    _ => self.report(infix_type(left?, operator, right?))
+                }
+            },
+            Expression::IndexExpression{array, subscript} | Expression::OptionalIndexExpression{array, subscript} => {
+                self.expression(array);
+                self.expression(subscript);
+                None
+            },
+            Expression::IfExpression{condition, consequence, alternative} => {
+                self.expression(condition);
+                // a branch which a literal condition never takes isn't checked.
+                if **condition != Expression::Bool(false) {
+                    self.statement(consequence);
+                }
+                if let Some(alternative) = alternative {
+                    if **condition != Expression::Bool(true) {
+                        self.statement(alternative);
+                    }
+                }
+                None
+            },
+            Expression::WhileExpression{condition, body} => {
+                self.expression(condition);
+                self.statement(body);
+                None
+            },
+            Expression::DoExpression{body} => {
+                self.statement(body);
+                None
+            },
+            Expression::CallExpression{function, body} => {
+                self.expression(function);
+                for argument in body.iter() {
+                    self.expression(argument);
+                }
+                None
+            },
+            _ => None
+        }
+    }
+
+    fn report(&mut self, inferred: Result<Type, Errors>) -> Type {
+        match inferred {
+            Ok(inferred) => inferred,
+            Err(error) => {
+                self.errors.push(error);
+                None
+            }
+        }
+    }
+}
+
+fn is_number(name: &str) -> bool {
+    name == "INTEGER" || name == "FLOAT"
+}
+
+// the same rules as evaluate_prefix_expression.
+fn prefix_type(operator: &str, right: &'static str) -> Result<Type, Errors> {
+    match operator {
+        "!" => Ok(Some("BOOLEAN")),
+        "-" | "+" if is_number(right) => Ok(Some(right)),
+        "-" | "+" => Err(Errors::UnknownOperator{operator: operator.to_string(), right: right.to_string()}),
+        _ => Ok(None)
+    }
+}
+
+// the same rules as evaluate_infix_expression.
+// arithmetic on booleans depends on bool_arithmetic of the config, so its type isn't known.
+fn infix_type(left: &'static str, operator: &str, right: &'static str) -> Result<Type, Errors> {
+    let is_number_or_boolean = |name: &str| is_number(name) || name == "BOOLEAN";
+    if is_arithmetic(operator) && is_number_or_boolean(left) && is_number_or_boolean(right) && (left == "BOOLEAN" || right == "BOOLEAN") {
+        return Ok(None)
+    }
+    match (left, operator, right) {
+        (left, operator, right) if is_number(left) && is_number(right) => Ok(match operator {
+            _ if is_arithmetic(operator) && left == "INTEGER" && right == "INTEGER" => Some("INTEGER"),
+            _ if is_arithmetic(operator) => Some("FLOAT"),
+            "<" | ">" | "==" | "!=" => Some("BOOLEAN"),
+            _ => None
+        }),
+        ("BOOLEAN", "==", "BOOLEAN") | ("BOOLEAN", "!=", "BOOLEAN") => Ok(Some("BOOLEAN")),
+        ("BOOLEAN", _, "BOOLEAN") => Ok(None),
+        (_, "==", _) | (_, "!=", _) => Ok(Some("BOOLEAN")),
+        // strings are concatenated by +, and the other operators make null.
+        ("STRING", "+", "STRING") => Ok(Some("STRING")),
+        ("STRING", _, "STRING") => Ok(Some("NULL")),
+        ("STRING", "*", "INTEGER") | ("INTEGER", "*", "STRING") => Ok(Some("STRING")),
+        ("ARRAY", "+", "ARRAY") | ("ARRAY", "*", "INTEGER") | ("INTEGER", "*", "ARRAY") => Ok(Some("ARRAY")),
+        _ => Err(Errors::TypeMismatch{left: left.to_string(), operator: operator.to_string(), right: right.to_string()})
+    }
+}
+
+#[cfg(test)]// test runs only when execute cargo run
+mod testing {
+    use crate::parser::{Parser};
+    use crate::evaluator::{Environment};
+    use crate::typecheck::{infer};
+
+    fn check(input: &str) -> Vec<String> {
+        let mut parser = Parser::new_from_source(input);
+        infer(&parser.parse_program().unwrap()).iter().map(|error| error.to_string()).collect()
+    }
+
+    #[test]
+    fn test_infer() {
+        let tests = vec![
+            ("1 + \"x\"", vec!["type mismatch: INTEGER + STRING"]),
+            ("\"a\" - \"b\" + 1", vec!["type mismatch: NULL + INTEGER"]),
+            ("let y = 2 * 3 + [1];", vec!["type mismatch: INTEGER + ARRAY"]),
+            ("-\"x\"", vec!["unknown operator: -STRING"]),
+            ("-fn(x) { x }", vec!["unknown operator: -FUNCTION"]),
+            ("let h = {} * 2;", vec!["type mismatch: HASH * INTEGER"]),
+            // a mismatch is reported once, and the outer expression isn't.
+            ("(1 + \"x\") + 2", vec!["type mismatch: INTEGER + STRING"]),
+            ("let f = fn() { if (true) { [1] + {} } }; f()", vec!["type mismatch: ARRAY + HASH"]),
+            ("1 + 2; true - \"a\"; let s = \"a\" * 2;", vec!["type mismatch: BOOLEAN - STRING"]),
+            ];
+        for (input, expected) in tests.iter() {
+            assert_eq!(check(input), *expected, "{}", input);
+            // the reported error happens when the program is evaluated.
+            let program = Parser::new_from_source(input).parse_program().unwrap();
+            let evaluated = Environment::new().evaluate(&program).unwrap();
+            assert!(evaluated.to_string().starts_with(expected[0]), "{}: {}", input, evaluated);
+        }
+
+        // nothing is reported if it may work.
+        let tests = vec![
+            "x + y",
+            "1 + x",
+            "let x = \"a\"; x + 1",
+            "f(1) * 2",
+            "true + 1",
+            "1 == \"1\"",
+            "\"ab\" * 2 + \"c\"",
+            "[1] * 2 + [3]",
+            "1 / 2.0 < 3",
+            "null ?? 1 + 1",
+            "\"a\" ?? 1 + \"b\"",
+            "if (false) { 1 + \"x\" }",
+            "if (true) { 1 } else { 1 + \"x\" }",
+            "\"a\" < \"b\"",
+            "[1, 2][0] + 1",
+            ];
+        for input in tests.into_iter() {
+            assert_eq!(check(input), Vec::<String>::new(), "{}", input);
+        }
+    }
+}
@@ -0,0 +1,128 @@
+use super::ast::{Statement, Expression};
+
+// a walk over the statements and expressions of a program.
+// a visitor overrides the methods for the nodes it looks at, and calls walk_statement or walk_expression
+// from them to go on into the children, so that only what differs from the plain walk has to be written.
+pub trait Visitor {
+    fn visit_statement(&mut self, statement: &Statement) {
+        walk_statement(self, statement);
+    }
+
+    fn visit_expression(&mut self, expression: &Expression) {
+        walk_expression(self, expression);
+    }
+}
+
+// visit every child of the statement in the order it is written,
+// including the identifiers which let and const bind.
+pub fn walk_statement<V: Visitor + ?Sized>(visitor: &mut V, statement: &Statement) {
+    match statement {
+        Statement::LetStatement{identifier, value, ..} | Statement::ConstStatement{identifier, value} => {
+            visitor.visit_expression(identifier);
+            visitor.visit_expression(value);
+        },
+        Statement::Assign{target, value} => {
+            visitor.visit_expression(target);
+            visitor.visit_expression(value);
+        },
+        Statement::Return(value) | Statement::Break(Some(value)) | Statement::ExpressionStatement(value) => visitor.visit_expression(value),
+        Statement::Break(None) | Statement::Import{..} => {},
+        Statement::Commented{statement, ..} => visitor.visit_statement(statement),
+        Statement::Block(statements) => {
+            for statement in statements.iter() {
+                visitor.visit_statement(statement);
+            }
+        },
+    }
+}
+
+// visit every child of the expression in the order it is written, including the parameters of a function.
+pub fn walk_expression<V: Visitor + ?Sized>(visitor: &mut V, expression: &Expression) {
+    match expression {
+        Expression::Array(values) => {
+            for value in values.iter() {
+                visitor.visit_expression(value);
+            }
+        },
+        Expression::Hashmap(tree) => {
+            for (key, value) in tree.iter() {
+                visitor.visit_expression(key);
+                visitor.visit_expression(value);
+            }
+        },
+        Expression::IndexExpression{array, subscript} | Expression::OptionalIndexExpression{array, subscript} => {
+            visitor.visit_expression(array);
+            visitor.visit_expression(subscript);
+        },
+        Expression::PrefixExpression{right_expression, ..} => visitor.visit_expression(right_expression),
+        Expression::InfixExpression{left_expression, right_expression, ..} => {
+            visitor.visit_expression(left_expression);
+            visitor.visit_expression(right_expression);
+        },
+        Expression::IfExpression{condition, consequence, alternative} => {
+            visitor.visit_expression(condition);
+            visitor.visit_statement(consequence);
+            if let Some(alternative) = alternative {
+                visitor.visit_statement(alternative);
+            }
+        },
+        Expression::WhileExpression{condition, body} => {
+            visitor.visit_expression(condition);
+            visitor.visit_statement(body);
+        },
+        Expression::DoExpression{body} => visitor.visit_statement(body),
+        Expression::FunctionLiteral{parameters, body} => {
+            for parameter in parameters.iter() {
+                visitor.visit_expression(parameter);
+            }
+            visitor.visit_statement(body);
+        },
+        Expression::CallExpression{function, body} => {
+            visitor.visit_expression(function);
+            for argument in body.iter() {
+                visitor.visit_expression(argument);
+            }
+        },
+        Expression::Identifier(_) | Expression::ResolvedIdentifier{..} | Expression::String(_) | Expression::Integer(_) |
+        Expression::Float(_) | Expression::LParen(_) | Expression::Bool(_) | Expression::Null => {}
+    }
+}
+
+#[cfg(test)]// test runs only when execute cargo run
+mod testing {
+    use crate::parser::{Parser};
+    use crate::ast::{Expression};
+    use crate::visit::{Visitor, walk_expression};
+
+    // the integers in the order they are visited, skipping the bodies of functions.
+    struct Integers(Vec<i32>);
+
+    impl Visitor for Integers {
+        fn visit_expression(&mut self, expression: &Expression) {
+            match expression {
+                Expression::Integer(value) => self.0.push(*value),
+                Expression::FunctionLiteral{..} => {},
+                _ => walk_expression(self, expression)
+            }
+        }
+    }
+
+    #[test]
+    fn test_visitor() {
+        let tests = vec![
+                ("1 + 2 * 3", vec![1, 2, 3]),
+                ("let x = [1, {2: 3}][4]; x", vec![1, 2, 3, 4]),
+                ("if (1) { 2 } else { 3 }; while (4) { break 5; }", vec![1, 2, 3, 4, 5]),
+                ("f(1, fn() { 2 }, 3)", vec![1, 3]),
+                ("a[0] = do { -1 }; return 2;", vec![0, 1, 2]),
+                ];
+        for (input, expected) in tests.into_iter() {
+            let program = Parser::new_from_source(input).parse_program().unwrap();
+            let mut integers = Integers(vec![]);
+            for statement in program.statements.iter() {
+                integers.visit_statement(statement);
+            }
+            assert_eq!(integers.0, expected, "{}", input);
+        }
+    }
+}